@@ -1,29 +1,74 @@
+use std::{net::IpAddr, path::PathBuf};
+
 use clap::{Parser, Subcommand};
 
+use crate::{
+    config::Profile, database::DatabaseUrl, diagram::DiagramFormat, document::DocumentFormat,
+    index_advice::SLOW_QUERIES,
+};
+
 #[derive(Parser, Debug)]
 #[command(name = "airy", version, about, long_about = None)]
 pub struct Cli {
+    /// Profile from the config files.
+    #[arg(long, env = "AIRY_PROFILE")]
+    pub profile: Option<String>,
+
     /// OpenRouter API Key.
     #[arg(long, env)]
     pub openrouter_api_key: Option<String>,
 
-    /// OpenRouter Base URL.
-    #[arg(long, env, default_value = "https://openrouter.ai/api/v1")]
-    pub openrouter_base_url: String,
+    /// OpenRouter Base URL. Defaults to https://openrouter.ai/api/v1.
+    #[arg(long, env)]
+    pub openrouter_base_url: Option<String>,
+
+    /// OpenRouter model ID. Defaults to mistralai/devstral-small:free.
+    #[arg(long, env)]
+    pub model: Option<String>,
 
-    /// OpenRouter model ID.
-    #[arg(long, env, default_value = "mistralai/devstral-small:free")]
-    pub model: String,
+    /// Max tokens. Defaults to 1024.
+    #[arg(long)]
+    pub max_tokens: Option<i64>,
 
-    /// Max tokens.
-    #[arg(long, default_value_t = 1024)]
-    pub max_tokens: i64,
+    /// Max number of tool calls executed concurrently in one turn. Defaults to 4.
+    #[arg(long)]
+    pub tool_concurrency: Option<usize>,
 
-    /// MySQL URL.
+    /// Path to a system prompt template.
+    #[arg(long, env)]
+    pub system_prompt_file: Option<PathBuf>,
+
+    /// Open database connections as read-only.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub read_only: Option<bool>,
+
+    /// Let the model change data. Changes are previewed in a transaction and committed only on confirmation.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub write: Option<bool>,
+
+    /// Max number of rows returned by a query.
+    #[arg(long)]
+    pub row_limit: Option<usize>,
+
+    /// Include tables, columns and keys in the system prompt if they fit in a token budget.
+    #[arg(
+        long,
+        value_name = "TOKENS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2000"
+    )]
+    pub preload_schema: Option<usize>,
+
+    /// Named database, repeatable. The first one is the default for tools.
+    #[arg(long = "database", value_name = "NAME=URL")]
+    pub databases: Vec<DatabaseUrl>,
+
+    /// MySQL URL, connected as `mysql`.
     #[arg(long, env)]
     pub mysql_url: Option<String>,
 
-    /// PostgreSQL URL.
+    /// PostgreSQL URL, connected as `postgres`.
     #[arg(long, env)]
     pub postgres_url: Option<String>,
 
@@ -31,20 +76,147 @@ pub struct Cli {
     pub command: CliCommand,
 }
 
+impl Cli {
+    /// Settings given on the command line or in the environment.
+    /// `--database` entries come first, followed by `--mysql-url` and `--postgres-url`.
+    pub fn profile_overrides(&self) -> Profile {
+        let mut databases = self.databases.clone();
+        for (name, url) in [("mysql", &self.mysql_url), ("postgres", &self.postgres_url)] {
+            if let Some(url) = url {
+                databases.push(DatabaseUrl {
+                    name: name.into(),
+                    url: url.clone(),
+                });
+            }
+        }
+
+        Profile {
+            database_url: None,
+            databases: Some(databases).filter(|databases| !databases.is_empty()),
+            api_key: self.openrouter_api_key.clone(),
+            base_url: self.openrouter_base_url.clone(),
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            tool_concurrency: self.tool_concurrency,
+            system_prompt_file: self.system_prompt_file.clone(),
+            read_only: self.read_only,
+            write: self.write,
+            row_limit: self.row_limit,
+            preload_schema: self.preload_schema,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum CliCommand {
     Chat {
-        /// Set or get System prompt.
+        /// Set the system prompt template, or print the default one.
         #[arg(long, default_missing_value = "", num_args = 0..=1)]
         system_prompt: Option<String>,
+
+        /// Path to a JSON file listing external MCP servers.
+        #[arg(long, env)]
+        mcp_config: Option<PathBuf>,
     },
     Mcp {
-        /// Run as a server using SSE.
-        #[arg(long)]
-        sse: bool,
+        /// Run as an HTTP server, with streamable HTTP at /mcp and legacy SSE at /sse.
+        #[arg(long, alias = "sse")]
+        http: bool,
+
+        /// Set the bind address.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: IpAddr,
 
         /// Set the Port.
         #[arg(long, default_value_t = 8080)]
         port: u16,
+
+        /// Token required as `Authorization: Bearer <token>` or `X-API-Key: <token>`.
+        #[arg(long, env = "MCP_AUTH_TOKEN")]
+        auth_token: Option<String>,
+
+        /// Serve without an auth token on an address other than loopback.
+        #[arg(long)]
+        allow_unauthenticated: bool,
+
+        /// Path to a PEM certificate chain. Enables TLS.
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+
+        /// Path to a PEM private key.
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+    },
+    /// Draw an entity-relationship diagram of the schema.
+    Diagram {
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: DiagramFormat,
+
+        /// Table to draw, repeatable. Draws every table if omitted.
+        #[arg(long = "table", short, value_name = "TABLE")]
+        tables: Vec<String>,
+
+        /// Also draw tables up to this many foreign keys away from the given tables.
+        #[arg(long, default_value_t = 1)]
+        hops: usize,
+
+        /// Write the diagram to a file instead of stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Name of the database to draw. Defaults to the first one.
+        #[arg(long)]
+        database: Option<String>,
+    },
+    /// Write a data dictionary, with descriptions drafted by the model.
+    Document {
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: DocumentFormat,
+
+        /// Write the document to a file instead of stdout.
+        /// Descriptions in an existing file are kept.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Number of sample rows shown to the model for each table.
+        #[arg(long, default_value_t = 3)]
+        sample_rows: usize,
+
+        /// Name of the database to document. Defaults to the first one.
+        #[arg(long)]
+        database: Option<String>,
+    },
+    /// Draft a migration with the model, try it, and write it as numbered up and down SQL files.
+    MigrateGen {
+        /// The change to make, such as "add an email column to employees".
+        description: String,
+
+        /// Directory of the migration files.
+        #[arg(long, short, default_value = "migrations")]
+        dir: PathBuf,
+
+        /// Name of the migration files. Named by the model if omitted.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Name of the database to migrate. Defaults to the first one.
+        #[arg(long)]
+        database: Option<String>,
+    },
+    /// Suggest missing indexes for the slowest queries recorded by the server, or for the given queries.
+    AdviseIndexes {
+        /// Query to explain, repeatable. Explains the slowest recorded queries if omitted.
+        #[arg(long = "query", short, value_name = "QUERY")]
+        queries: Vec<String>,
+
+        /// Number of recorded queries to explain, at most 50.
+        #[arg(long, default_value_t = SLOW_QUERIES)]
+        limit: usize,
+
+        /// Name of the database to advise on. Defaults to the first one.
+        #[arg(long)]
+        database: Option<String>,
     },
 }
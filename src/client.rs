@@ -44,11 +44,11 @@ impl Client {
             r#type: ToolType::Function,
             function: Function {
                 name: tool.name.to_string(),
-                description: if tool.description.is_empty() {
-                    None
-                } else {
-                    Some(tool.description.to_string())
-                },
+                description: tool
+                    .description
+                    .as_ref()
+                    .filter(|description| !description.is_empty())
+                    .map(|description| description.to_string()),
                 // Not sure what's the correct way to handle "void" functions.
                 parameters: {
                     if tool.input_schema.is_empty()
@@ -68,6 +68,10 @@ impl Client {
         });
     }
 
+    pub fn tool_names(&self) -> impl Iterator<Item = &str> {
+        self.tools.iter().map(|tool| tool.function.name.as_str())
+    }
+
     pub async fn chat_completion(
         &self,
         mut req: ChatCompletionRequest,
@@ -0,0 +1,418 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    database::{DatabaseUrl, Dialect},
+    error::{AppError, AppResult},
+};
+
+pub const DEFAULT_BASE_URL: &str = "https://openrouter.ai/api/v1";
+pub const DEFAULT_MODEL: &str = "mistralai/devstral-small:free";
+pub const DEFAULT_MAX_TOKENS: i64 = 1024;
+pub const DEFAULT_TOOL_CONCURRENCY: usize = 4;
+
+/// Name of the project config file, looked up in the current directory and its parents.
+const PROJECT_CONFIG_FILE: &str = ".airy.toml";
+
+/// Settings that can come from the command line, the environment or a config file.
+/// Unset fields fall through to the next source.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Single database, named after its dialect.
+    pub database_url: Option<String>,
+    pub databases: Option<Vec<DatabaseUrl>>,
+    pub api_key: Option<String>,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub max_tokens: Option<i64>,
+    pub tool_concurrency: Option<usize>,
+    pub system_prompt_file: Option<PathBuf>,
+    pub read_only: Option<bool>,
+    /// Lets the model change data in transactions that are committed only on confirmation.
+    pub write: Option<bool>,
+    pub row_limit: Option<usize>,
+    /// Token budget for a schema digest in the system prompt.
+    pub preload_schema: Option<usize>,
+}
+
+impl Profile {
+    /// Fills unset fields from `fallback`.
+    /// `database_url` and `databases` are one setting, so either of them hides both in `fallback`.
+    pub fn merge(self, fallback: Profile) -> Profile {
+        let (database_url, databases) = if self.database_url.is_some() || self.databases.is_some() {
+            (self.database_url, self.databases)
+        } else {
+            (fallback.database_url, fallback.databases)
+        };
+
+        Profile {
+            database_url,
+            databases,
+            api_key: self.api_key.or(fallback.api_key),
+            base_url: self.base_url.or(fallback.base_url),
+            model: self.model.or(fallback.model),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            tool_concurrency: self.tool_concurrency.or(fallback.tool_concurrency),
+            system_prompt_file: self.system_prompt_file.or(fallback.system_prompt_file),
+            read_only: self.read_only.or(fallback.read_only),
+            write: self.write.or(fallback.write),
+            row_limit: self.row_limit.or(fallback.row_limit),
+            preload_schema: self.preload_schema.or(fallback.preload_schema),
+        }
+    }
+
+    /// Resolves the profile into settings, applying defaults.
+    pub fn into_settings(self) -> AppResult<Settings> {
+        let mut databases = self.databases.unwrap_or_default();
+        if let Some(url) = self.database_url {
+            databases.push(DatabaseUrl {
+                name: Dialect::from_url(&url)?.name().into(),
+                url,
+            });
+        }
+
+        let read_only = self.read_only.unwrap_or(false);
+        let write = self.write.unwrap_or(false);
+        if read_only && write {
+            return Err(AppError::Config(
+                "write mode can't be enabled in read-only mode".into(),
+            ));
+        }
+
+        Ok(Settings {
+            databases,
+            api_key: self.api_key,
+            base_url: self.base_url.unwrap_or_else(|| DEFAULT_BASE_URL.into()),
+            model: self.model.unwrap_or_else(|| DEFAULT_MODEL.into()),
+            max_tokens: self.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            tool_concurrency: self
+                .tool_concurrency
+                .unwrap_or(DEFAULT_TOOL_CONCURRENCY)
+                .max(1),
+            system_prompt_file: self.system_prompt_file,
+            read_only,
+            write,
+            row_limit: self.row_limit.filter(|row_limit| *row_limit > 0),
+            schema_token_budget: self.preload_schema.filter(|budget| *budget > 0),
+        })
+    }
+
+    /// Makes relative paths relative to the directory of the config file.
+    fn resolve_paths(&mut self, base_dir: &Path) {
+        if let Some(path) = &self.system_prompt_file
+            && path.is_relative()
+        {
+            self.system_prompt_file = Some(base_dir.join(path));
+        }
+    }
+}
+
+/// Effective settings of a session.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub databases: Vec<DatabaseUrl>,
+    pub api_key: Option<String>,
+    pub base_url: String,
+    pub model: String,
+    pub max_tokens: i64,
+    pub tool_concurrency: usize,
+    pub system_prompt_file: Option<PathBuf>,
+    pub read_only: bool,
+    pub write: bool,
+    /// Max number of rows returned by a query, or `None` for no limit.
+    pub row_limit: Option<usize>,
+    /// Token budget for a schema digest in the system prompt, or `None` to list table names only.
+    pub schema_token_budget: Option<usize>,
+}
+
+/// A config file. Top-level settings apply to every profile.
+///
+/// ```toml
+/// model = "openai/gpt-4.1"
+///
+/// [profiles.prod]
+/// database_url = "postgres://readonly@db.internal/app"
+/// read_only = true
+/// row_limit = 100
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigFile {
+    pub defaults: Profile,
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// Parses a config file, rejecting unknown settings, as a misspelled one such as `readonly` would be ignored otherwise.
+    pub fn parse(content: &str) -> AppResult<Self> {
+        let config_error = |err: toml::de::Error| AppError::Config(err.to_string());
+        let mut table: toml::Table = toml::from_str(content).map_err(config_error)?;
+        // Profiles are split off by hand, as serde doesn't reject unknown fields of a flattened struct.
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into().map_err(config_error)?,
+            None => HashMap::new(),
+        };
+        Ok(Self {
+            defaults: toml::Value::Table(table).try_into().map_err(config_error)?,
+            profiles,
+        })
+    }
+
+    /// Loads a config file, or returns an empty one if it doesn't exist.
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut config = Self::parse(&content)
+            .map_err(|err| AppError::Config(format!("{}: {}", path.display(), err)))?;
+
+        if let Some(base_dir) = path.parent() {
+            config.defaults.resolve_paths(base_dir);
+            for profile in config.profiles.values_mut() {
+                profile.resolve_paths(base_dir);
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// User config from `~/.config/airy/config.toml` and project config from `.airy.toml`.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub user: ConfigFile,
+    pub project: ConfigFile,
+}
+
+impl Config {
+    pub fn load() -> AppResult<Self> {
+        let user = match user_config_path() {
+            Some(path) => ConfigFile::load(&path)?,
+            None => ConfigFile::default(),
+        };
+        let project = match project_config_path()? {
+            Some(path) => ConfigFile::load(&path)?,
+            None => ConfigFile::default(),
+        };
+        Ok(Self { user, project })
+    }
+
+    /// Merges the config files for a profile.
+    /// A selected profile overrides top-level settings, and in both, the project config overrides the user config.
+    pub fn profile(&self, name: Option<&str>) -> AppResult<Profile> {
+        let defaults = self
+            .project
+            .defaults
+            .clone()
+            .merge(self.user.defaults.clone());
+        let Some(name) = name else {
+            return Ok(defaults);
+        };
+
+        let project = self.project.profiles.get(name);
+        let user = self.user.profiles.get(name);
+        if project.is_none() && user.is_none() {
+            return Err(AppError::Config(format!("unknown profile: {}", name)));
+        }
+        Ok(project
+            .cloned()
+            .unwrap_or_default()
+            .merge(user.cloned().unwrap_or_default())
+            .merge(defaults))
+    }
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::home_dir().map(|home| home.join(".config")))?;
+    Some(config_dir.join("airy").join("config.toml"))
+}
+
+fn project_config_path() -> AppResult<Option<PathBuf>> {
+    let current_dir = std::env::current_dir()?;
+    Ok(current_dir
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(user: &str, project: &str) -> Config {
+        Config {
+            user: ConfigFile::parse(user).unwrap(),
+            project: ConfigFile::parse(project).unwrap(),
+        }
+    }
+
+    #[test]
+    fn merge_prefers_set_fields() {
+        let profile = Profile {
+            model: Some("cli".into()),
+            ..Default::default()
+        }
+        .merge(Profile {
+            model: Some("config".into()),
+            max_tokens: Some(2048),
+            ..Default::default()
+        });
+
+        assert_eq!(profile.model.as_deref(), Some("cli"));
+        assert_eq!(profile.max_tokens, Some(2048));
+    }
+
+    #[test]
+    fn merge_replaces_databases_as_a_whole() {
+        let profile = Profile {
+            database_url: Some("postgres://localhost/cli".into()),
+            ..Default::default()
+        }
+        .merge(Profile {
+            databases: Some(vec![DatabaseUrl {
+                name: "app".into(),
+                url: "mysql://localhost/app".into(),
+            }]),
+            ..Default::default()
+        });
+
+        assert_eq!(profile.databases, None);
+        assert_eq!(
+            profile.into_settings().unwrap().databases,
+            vec![DatabaseUrl {
+                name: "postgres".into(),
+                url: "postgres://localhost/cli".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn project_overrides_user() {
+        let config = config(
+            r#"
+                model = "user"
+                max_tokens = 512
+            "#,
+            r#"
+                model = "project"
+            "#,
+        );
+
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.model.as_deref(), Some("project"));
+        assert_eq!(profile.max_tokens, Some(512));
+    }
+
+    #[test]
+    fn profile_overrides_top_level_settings() {
+        let config = config(
+            r#"
+                row_limit = 10
+
+                [profiles.prod]
+                database_url = "postgres://prod/app"
+                read_only = true
+            "#,
+            r#"
+                database_url = "postgres://localhost/app"
+                row_limit = 50
+
+                [profiles.prod]
+                row_limit = 100
+            "#,
+        );
+
+        let profile = config.profile(Some("prod")).unwrap();
+        assert_eq!(profile.database_url.as_deref(), Some("postgres://prod/app"));
+        assert_eq!(profile.read_only, Some(true));
+        assert_eq!(profile.row_limit, Some(100));
+
+        let profile = config.profile(None).unwrap();
+        assert_eq!(
+            profile.database_url.as_deref(),
+            Some("postgres://localhost/app")
+        );
+        assert_eq!(profile.read_only, None);
+        assert_eq!(profile.row_limit, Some(50));
+    }
+
+    #[test]
+    fn unknown_settings_are_errors() {
+        for content in [
+            "readonly = true",
+            "[profiles.prod]\nrow-limit = 100",
+            "[profiles.prod]\nread_only = true\nrowlimit = 100",
+        ] {
+            assert!(matches!(
+                ConfigFile::parse(content),
+                Err(AppError::Config(_))
+            ));
+        }
+        let config = config("read_only = true", "[profiles.prod]\nrow_limit = 100");
+        let profile = config.profile(Some("prod")).unwrap();
+        assert_eq!(profile.read_only, Some(true));
+        assert_eq!(profile.row_limit, Some(100));
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        let config = config("[profiles.dev]\nmodel = \"dev\"", "");
+        assert!(matches!(
+            config.profile(Some("prod")),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn parses_named_databases() {
+        let config = config(
+            r#"
+                [[databases]]
+                name = "app"
+                url = "postgres://localhost/app"
+
+                [[databases]]
+                name = "legacy"
+                url = "mysql://localhost/legacy"
+            "#,
+            "",
+        );
+
+        let settings = config.profile(None).unwrap().into_settings().unwrap();
+        let names: Vec<_> = settings
+            .databases
+            .iter()
+            .map(|db| db.name.as_str())
+            .collect();
+        assert_eq!(names, ["app", "legacy"]);
+    }
+
+    #[test]
+    fn settings_apply_defaults() {
+        let settings = Profile::default().into_settings().unwrap();
+        assert_eq!(settings.model, DEFAULT_MODEL);
+        assert_eq!(settings.max_tokens, DEFAULT_MAX_TOKENS);
+        assert!(!settings.read_only);
+        assert!(!settings.write);
+        assert_eq!(settings.row_limit, None);
+    }
+
+    #[test]
+    fn write_mode_conflicts_with_read_only() {
+        let profile = Profile {
+            read_only: Some(true),
+            write: Some(true),
+            ..Default::default()
+        };
+        assert!(matches!(profile.into_settings(), Err(AppError::Config(_))));
+    }
+}
@@ -0,0 +1,398 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use chrono::Local;
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, mysql::MySqlPoolOptions, postgres::PgPoolOptions};
+
+use crate::{
+    error::{AppError, AppResult},
+    schema::DatabaseSchema,
+    template,
+    tool::{ManagerArc, mysql::MySqlManager, postgres::PostgresManager},
+};
+
+/// Template of the system prompt, see [`Databases::system_prompt`] for the variables.
+pub const DEFAULT_SYSTEM_PROMPT: &str = include_str!("system_prompt.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    MySql,
+    Postgres,
+}
+
+impl Dialect {
+    pub fn from_url(url: &str) -> AppResult<Self> {
+        match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("mysql" | "mariadb") => Ok(Self::MySql),
+            Some("postgres" | "postgresql") => Ok(Self::Postgres),
+            _ => Err(AppError::InvalidArgument(format!(
+                "unsupported database URL: {}",
+                url
+            ))),
+        }
+    }
+
+    /// Name of a database given only by its URL.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MySql => "mysql",
+            Self::Postgres => "postgres",
+        }
+    }
+}
+
+/// Database connection given as `NAME=URL`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DatabaseUrl {
+    pub name: String,
+    pub url: String,
+}
+
+impl FromStr for DatabaseUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, url)) if !name.trim().is_empty() && !url.trim().is_empty() => Ok(Self {
+                name: name.trim().into(),
+                url: url.trim().into(),
+            }),
+            _ => Err(format!("expected NAME=URL, got '{}'", s)),
+        }
+    }
+}
+
+/// A named connection with its manager.
+pub struct Database {
+    pub name: String,
+    pub manager: ManagerArc,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct DatabaseInfo {
+    pub name: String,
+    pub dialect: String,
+    pub server_version: String,
+}
+
+/// Policy applied to every database of a session.
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseOptions {
+    /// Replaces the default system prompt template.
+    pub system_prompt: Option<String>,
+    /// Opens connections as read-only, so the server rejects writes.
+    pub read_only: bool,
+    /// Offers tools that run data-modifying statements in a transaction held open until the changes are confirmed.
+    pub write: bool,
+    /// Max number of rows returned by a query.
+    pub row_limit: Option<usize>,
+    /// Token budget for a schema digest in the system prompt. Without it, only table names are listed.
+    pub schema_token_budget: Option<usize>,
+}
+
+/// All databases of a session. The first one is used when a tool call doesn't name one.
+pub struct Databases {
+    databases: Vec<Database>,
+    system_prompt: String,
+    read_only: bool,
+    write: bool,
+    row_limit: Option<usize>,
+    schema_token_budget: Option<usize>,
+}
+
+impl Databases {
+    pub async fn connect(urls: Vec<DatabaseUrl>, options: DatabaseOptions) -> AppResult<Self> {
+        if urls.is_empty() {
+            return Err(AppError::MissingDatabaseUrl);
+        }
+
+        // Queries are read-only in write mode too, where changes are made with `executeWrite` only.
+        let read_only_queries = options.read_only || options.write;
+        let mut databases: Vec<Database> = Vec::with_capacity(urls.len());
+        for DatabaseUrl { name, url } in urls {
+            if databases.iter().any(|database| database.name == name) {
+                return Err(AppError::InvalidArgument(format!(
+                    "duplicate database name: {}",
+                    name
+                )));
+            }
+
+            let dialect = Dialect::from_url(&url)?;
+            let manager: ManagerArc = match dialect {
+                Dialect::MySql => {
+                    let read_only = options.read_only;
+                    let pool = MySqlPoolOptions::new()
+                        .after_connect(move |connection, _| {
+                            Box::pin(async move {
+                                if read_only {
+                                    connection
+                                        .execute("SET SESSION TRANSACTION READ ONLY")
+                                        .await?;
+                                }
+                                Ok(())
+                            })
+                        })
+                        .connect(&url)
+                        .await?;
+                    Arc::new(MySqlManager::new(pool, read_only_queries).await?)
+                }
+                Dialect::Postgres => {
+                    let read_only = options.read_only;
+                    let pool = PgPoolOptions::new()
+                        .after_connect(move |connection, _| {
+                            Box::pin(async move {
+                                if read_only {
+                                    connection
+                                        .execute("SET default_transaction_read_only = on")
+                                        .await?;
+                                }
+                                Ok(())
+                            })
+                        })
+                        .connect(&url)
+                        .await?;
+                    Arc::new(PostgresManager::new(pool, read_only_queries).await?)
+                }
+            };
+            databases.push(Database { name, manager });
+        }
+
+        Ok(Self {
+            databases,
+            system_prompt: options
+                .system_prompt
+                .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.into()),
+            read_only: options.read_only,
+            write: options.write,
+            row_limit: options.row_limit,
+            schema_token_budget: options.schema_token_budget,
+        })
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn write(&self) -> bool {
+        self.write
+    }
+
+    pub fn row_limit(&self) -> Option<usize> {
+        self.row_limit
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Database> {
+        self.databases.iter()
+    }
+
+    /// Whether more than one database is connected, so names have to be qualified.
+    pub fn is_multiple(&self) -> bool {
+        self.databases.len() > 1
+    }
+
+    /// Finds a database by name, or returns the first one if no name is given.
+    pub fn get(&self, name: Option<&str>) -> AppResult<&Database> {
+        let name = name.map(str::trim).filter(|name| !name.is_empty());
+        match name {
+            Some(name) => self
+                .databases
+                .iter()
+                .find(|database| database.name == name)
+                .ok_or_else(|| AppError::InvalidArgument(format!("unknown database: {}", name))),
+            None => Ok(&self.databases[0]),
+        }
+    }
+
+    pub fn infos(&self) -> Vec<DatabaseInfo> {
+        self.databases
+            .iter()
+            .map(|database| DatabaseInfo {
+                name: database.name.clone(),
+                dialect: database.manager.dialect().into(),
+                server_version: database.manager.server_version().into(),
+            })
+            .collect()
+    }
+
+    /// Describes the connected servers, such as "PostgreSQL 15.4" or
+    /// "app (PostgreSQL 15.4), legacy (MySQL 8.0.36)".
+    pub fn describe(&self) -> String {
+        if !self.is_multiple() {
+            let manager = &self.databases[0].manager;
+            return format!("{} {}", manager.dialect(), manager.server_version());
+        }
+
+        self.databases
+            .iter()
+            .map(|database| {
+                format!(
+                    "{} ({} {})",
+                    database.name,
+                    database.manager.dialect(),
+                    database.manager.server_version()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Renders the system prompt template.
+    ///
+    /// Variables are `{{dialect}}`, `{{server_version}}` and `{{database}}` of the first database,
+    /// `{{schema_summary}}` listing the tables (see [`schema_summary`]), `{{today}}`, `{{tools}}` and `{{row_limit}}`.
+    /// Other connected databases are listed after the prompt.
+    pub async fn system_prompt(&self, tools: &str) -> AppResult<String> {
+        let first = &self.databases[0];
+        let mut schemas = Vec::with_capacity(self.databases.len());
+        for database in &self.databases {
+            schemas.push((database.name.as_str(), database.manager.get_schema().await?));
+        }
+        let database = schemas[0].1.database.clone();
+        let schema_summary = schema_summary(&schemas, self.schema_token_budget);
+
+        let variables = HashMap::from([
+            ("dialect", first.manager.dialect().to_string()),
+            ("server_version", first.manager.server_version().to_string()),
+            ("database", database),
+            ("schema_summary", schema_summary),
+            ("today", Local::now().format("%Y-%m-%d").to_string()),
+            ("tools", tools.to_string()),
+            (
+                "row_limit",
+                self.row_limit
+                    .map_or_else(|| "unlimited".into(), |row_limit| row_limit.to_string()),
+            ),
+        ]);
+        let mut system_prompt = template::render(&self.system_prompt, &variables);
+
+        if self.is_multiple() {
+            system_prompt +=
+                "\nConnected databases, pass the name as the `database` argument of a tool:\n";
+            for database in &self.databases {
+                system_prompt += &format!(
+                    "- `{}`: {} {}\n",
+                    database.name,
+                    database.manager.dialect(),
+                    database.manager.server_version()
+                );
+            }
+        }
+        Ok(system_prompt)
+    }
+}
+
+/// Approximate number of characters per token, to keep the schema digest within its budget.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Describes the schemas of named databases for the system prompt.
+/// With a token budget, it's a digest with columns and keys if it fits, and table names otherwise,
+/// leaving the details to schema tools.
+pub fn schema_summary(schemas: &[(&str, DatabaseSchema)], token_budget: Option<usize>) -> String {
+    let multiple = schemas.len() > 1;
+    if let Some(token_budget) = token_budget {
+        let digest = schemas
+            .iter()
+            .map(|(name, schema)| {
+                if multiple {
+                    format!("Database `{}`:\n{}", name, schema.digest())
+                } else {
+                    schema.digest()
+                }
+            })
+            .collect::<String>();
+        if digest.len() / CHARS_PER_TOKEN <= token_budget {
+            return digest.trim_end().into();
+        }
+    }
+
+    schemas
+        .iter()
+        .map(|(name, schema)| {
+            if multiple {
+                format!("- `{}`: {}", name, schema.summary())
+            } else {
+                schema.summary()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{
+        ColumnSchema, TableSchema, ViewSchema,
+        fixture::{self, foreign_key, key_column, table},
+    };
+
+    fn schema() -> DatabaseSchema {
+        let column = |name: &str, data_type: &str| ColumnSchema {
+            nullable: false,
+            ..fixture::column(name, data_type)
+        };
+        DatabaseSchema {
+            views: vec![ViewSchema {
+                schema: "public".into(),
+                name: "order_counts".into(),
+                comment: None,
+                materialized: false,
+                columns: vec![column("customer_id", "integer"), column("orders", "bigint")],
+                definition: None,
+            }],
+            ..fixture::database(
+                "shop",
+                vec![
+                    table(
+                        "customers",
+                        vec![key_column("id", "integer", "PRI"), column("name", "text")],
+                        &[],
+                    ),
+                    TableSchema {
+                        foreign_keys: vec![foreign_key("orders", "customer_id", "customers")],
+                        ..table(
+                            "orders",
+                            vec![
+                                key_column("id", "integer", "PRI"),
+                                key_column("customer_id", "integer", "MUL"),
+                            ],
+                            &[],
+                        )
+                    },
+                ],
+            )
+        }
+    }
+
+    #[test]
+    fn digest_within_budget() {
+        assert_eq!(
+            schema_summary(&[("shop", schema())], Some(1000)),
+            "customers(id integer PK, name text)\n\
+             orders(id integer PK, customer_id integer FK->customers.id)\n\
+             view order_counts(customer_id integer, orders bigint)"
+        );
+    }
+
+    #[test]
+    fn table_names_over_budget() {
+        assert_eq!(
+            schema_summary(&[("shop", schema())], Some(10)),
+            "customers, orders"
+        );
+        assert_eq!(
+            schema_summary(&[("shop", schema())], None),
+            "customers, orders"
+        );
+    }
+
+    #[test]
+    fn qualifies_multiple_databases() {
+        assert_eq!(
+            schema_summary(&[("a", schema()), ("b", schema())], None),
+            "- `a`: customers, orders\n- `b`: customers, orders"
+        );
+    }
+}
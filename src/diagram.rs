@@ -0,0 +1,268 @@
+use clap::ValueEnum;
+use rmcp::schemars::{self, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{AppError, AppResult},
+    graph::RelationGraph,
+    schema::{ColumnSchema, DatabaseSchema, ForeignKeySchema, TableSchema},
+    utility::html_escape,
+};
+
+#[derive(ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    /// Mermaid `erDiagram`.
+    #[default]
+    Mermaid,
+    /// Graphviz DOT.
+    Dot,
+}
+
+/// Renders an entity-relationship diagram of `tables` and the tables within `hops` foreign keys of them,
+/// or of the whole schema if `tables` is empty.
+pub fn render(
+    schema: &DatabaseSchema,
+    tables: &[String],
+    hops: usize,
+    format: DiagramFormat,
+) -> AppResult<String> {
+    let tables = select_tables(schema, tables, hops)?;
+    Ok(match format {
+        DiagramFormat::Mermaid => mermaid(schema, &tables),
+        DiagramFormat::Dot => dot(schema, &tables),
+    })
+}
+
+fn select_tables<'a>(
+    schema: &'a DatabaseSchema,
+    names: &[String],
+    hops: usize,
+) -> AppResult<Vec<&'a TableSchema>> {
+    if names.is_empty() {
+        return Ok(schema.tables.iter().collect());
+    }
+    let mut tables = Vec::with_capacity(names.len());
+    for name in names {
+        let table = schema
+            .resolve_table(name)
+            .ok_or_else(|| AppError::InvalidArgument(format!("unknown table: {}", name)))?;
+        tables.push(table);
+    }
+    Ok(RelationGraph::new(schema).neighborhood(&tables, hops))
+}
+
+/// Foreign keys of `table` that reference one of `tables`, with the referenced table.
+fn relationships<'a>(
+    table: &'a TableSchema,
+    tables: &[&'a TableSchema],
+) -> impl Iterator<Item = (&'a ForeignKeySchema, &'a TableSchema)> {
+    table.foreign_keys.iter().filter_map(|foreign_key| {
+        let referenced = tables.iter().find(|table| {
+            table.schema == foreign_key.referenced_schema
+                && table.name == foreign_key.referenced_table
+        })?;
+        Some((foreign_key, *referenced))
+    })
+}
+
+/// Key markers of a column, such as `PK, FK`.
+pub fn key_markers(table: &TableSchema, column: &ColumnSchema) -> Vec<&'static str> {
+    let mut markers = Vec::new();
+    match column.key.as_deref() {
+        Some("PRI") => markers.push("PK"),
+        Some("UNI") => markers.push("UK"),
+        _ => {}
+    }
+    if table
+        .foreign_keys
+        .iter()
+        .any(|foreign_key| foreign_key.columns.contains(&column.name))
+    {
+        markers.push("FK");
+    }
+    markers
+}
+
+fn mermaid(schema: &DatabaseSchema, tables: &[&TableSchema]) -> String {
+    let mut diagram = String::from("erDiagram\n");
+    for table in tables {
+        diagram += &format!(
+            "    {} {{\n",
+            mermaid_identifier(&schema.qualified_name(table))
+        );
+        for column in &table.columns {
+            diagram += &format!(
+                "        {} {}",
+                mermaid_identifier(&column.data_type),
+                mermaid_identifier(&column.name)
+            );
+            let markers = key_markers(table, column);
+            if !markers.is_empty() {
+                diagram += &format!(" {}", markers.join(", "));
+            }
+            if let Some(comment) = &column.comment {
+                diagram += &format!(" \"{}\"", comment.replace('"', "'"));
+            }
+            diagram += "\n";
+        }
+        diagram += "    }\n";
+    }
+    for table in tables {
+        for (foreign_key, referenced) in relationships(table, tables) {
+            // A nullable foreign key means a row may have no parent.
+            let optional = foreign_key.columns.iter().any(|name| {
+                table
+                    .columns
+                    .iter()
+                    .any(|column| column.name == *name && column.nullable)
+            });
+            diagram += &format!(
+                "    {} }}o--{} {} : \"{}\"\n",
+                mermaid_identifier(&schema.qualified_name(table)),
+                if optional { "o|" } else { "||" },
+                mermaid_identifier(&schema.qualified_name(referenced)),
+                foreign_key.name.replace('"', "'")
+            );
+        }
+    }
+    diagram
+}
+
+/// Mermaid only accepts letters, digits, `-`, `_`, parentheses and brackets in names and types.
+fn mermaid_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "-_()[]".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn dot(schema: &DatabaseSchema, tables: &[&TableSchema]) -> String {
+    let mut diagram = format!(
+        "digraph {} {{\n    rankdir=LR;\n    node [shape=plain];\n",
+        dot_identifier(&schema.database)
+    );
+    for table in tables {
+        diagram += &format!(
+            "    {} [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"4\"><tr><td bgcolor=\"lightgrey\"><b>{}</b></td></tr>",
+            dot_identifier(&schema.qualified_name(table)),
+            html_escape(&schema.qualified_name(table))
+        );
+        for (i, column) in table.columns.iter().enumerate() {
+            diagram += &format!(
+                "<tr><td port=\"c{}\" align=\"left\">{} <i>{}</i>",
+                i,
+                html_escape(&column.name),
+                html_escape(&column.data_type)
+            );
+            let markers = key_markers(table, column);
+            if !markers.is_empty() {
+                diagram += &format!(" {}", markers.join(", "));
+            }
+            diagram += "</td></tr>";
+        }
+        diagram += "</table>>];\n";
+    }
+    for table in tables {
+        for (foreign_key, referenced) in relationships(table, tables) {
+            let port = |table: &TableSchema, column: Option<&String>| {
+                column
+                    .and_then(|name| table.columns.iter().position(|column| column.name == *name))
+                    .map(|i| format!(":c{}", i))
+                    .unwrap_or_default()
+            };
+            diagram += &format!(
+                "    {}{} -> {}{} [tooltip={}];\n",
+                dot_identifier(&schema.qualified_name(table)),
+                port(table, foreign_key.columns.first()),
+                dot_identifier(&schema.qualified_name(referenced)),
+                port(referenced, foreign_key.referenced_columns.first()),
+                dot_identifier(&foreign_key.name)
+            );
+        }
+    }
+    diagram += "}\n";
+    diagram
+}
+
+fn dot_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixture::{column, database, key_column, table};
+
+    fn schema() -> DatabaseSchema {
+        database(
+            "shop",
+            vec![
+                table(
+                    "customers",
+                    vec![
+                        key_column("id", "integer", "PRI"),
+                        ColumnSchema {
+                            nullable: false,
+                            ..column("name", "character varying(50)")
+                        },
+                    ],
+                    &[],
+                ),
+                table(
+                    "orders",
+                    vec![
+                        key_column("id", "integer", "PRI"),
+                        ColumnSchema {
+                            nullable: true,
+                            ..key_column("customers_id", "integer", "MUL")
+                        },
+                    ],
+                    &["customers"],
+                ),
+                table("warehouses", vec![key_column("id", "integer", "PRI")], &[]),
+            ],
+        )
+    }
+
+    #[test]
+    fn renders_mermaid_neighborhood() {
+        let diagram = render(&schema(), &["orders".into()], 1, DiagramFormat::Mermaid).unwrap();
+        assert_eq!(
+            diagram,
+            "erDiagram\n\
+             \x20   customers {\n\
+             \x20       integer id PK\n\
+             \x20       character_varying(50) name\n\
+             \x20   }\n\
+             \x20   orders {\n\
+             \x20       integer id PK\n\
+             \x20       integer customers_id FK\n\
+             \x20   }\n\
+             \x20   orders }o--o| customers : \"orders_customers_fkey\"\n"
+        );
+    }
+
+    #[test]
+    fn renders_dot_edges_between_columns() {
+        let diagram = render(&schema(), &[], 0, DiagramFormat::Dot).unwrap();
+        assert!(diagram.starts_with("digraph \"shop\" {\n"));
+        assert!(diagram.contains(
+            "    \"orders\":c1 -> \"customers\":c0 [tooltip=\"orders_customers_fkey\"];\n"
+        ));
+        assert!(diagram.contains("<b>warehouses</b>"));
+    }
+
+    #[test]
+    fn unknown_table_is_an_error() {
+        assert!(matches!(
+            render(&schema(), &["invoices".into()], 1, DiagramFormat::Dot),
+            Err(AppError::InvalidArgument(_))
+        ));
+    }
+}
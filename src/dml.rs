@@ -0,0 +1,626 @@
+use std::fmt::{self, Display};
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::Serialize;
+
+use crate::{
+    error::{AppError, AppResult},
+    tool::Row,
+};
+
+/// First keywords of statements that only read.
+const READ_KEYWORDS: [&str; 8] = [
+    "SELECT", "WITH", "TABLE", "VALUES", "EXPLAIN", "SHOW", "DESCRIBE", "DESC",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DmlKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A data-modifying statement, split just enough to tell which rows it touches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dml {
+    pub kind: DmlKind,
+    /// Changed table, or `None` for statements that join other tables, whose touched rows can't be selected on their own.
+    pub target: Option<Target>,
+    /// Whether the statement already has a `RETURNING` clause.
+    pub returning: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    /// Table as written in the statement, possibly qualified and quoted.
+    pub table: String,
+    pub alias: Option<String>,
+    /// `WHERE` condition of an update or delete.
+    pub condition: Option<String>,
+}
+
+impl Target {
+    /// Name the columns of the table are qualified with in the statement.
+    pub fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.table)
+    }
+
+    /// Selects the rows an update or delete touches, with the given select list.
+    /// The condition may end with a line comment, so clauses have to be appended on a new line.
+    pub fn select(&self, columns: &str) -> String {
+        let mut query = format!("SELECT {} FROM {}", columns, self.table);
+        if let Some(alias) = &self.alias {
+            query += &format!(" {}", alias);
+        }
+        if let Some(condition) = &self.condition {
+            query += &format!(" WHERE {}", condition);
+        }
+        query
+    }
+
+    /// The table name without quotes, to look it up in the schema.
+    pub fn unquoted_table(&self) -> String {
+        split_top_level(&self.table, '.')
+            .iter()
+            .map(|part| {
+                let part = part.trim();
+                match part.chars().next() {
+                    Some(quote @ ('"' | '`')) if part.len() > 1 && part.ends_with(quote) => {
+                        let doubled = format!("{}{}", quote, quote);
+                        part[1..part.len() - 1].replace(&doubled, &quote.to_string())
+                    }
+                    _ => part.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// A changed row, identified by its primary key, or by all its values if the table has none.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct RowChange {
+    pub key: Row,
+    /// Values before the change, only the changed columns of an updated row. Missing for inserted rows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Row>,
+    /// Values after the change, only the changed columns of an updated row. Missing for deleted rows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Row>,
+}
+
+impl Display for RowChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = |row: &Row| -> String {
+            row.iter()
+                .map(|(column, value)| format!("{} = {}", column, value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                let columns: Vec<String> = after
+                    .iter()
+                    .map(|(column, value)| {
+                        let old = before.get(column).cloned().unwrap_or_default();
+                        format!("{}: {} -> {}", column, old, value)
+                    })
+                    .collect();
+                write!(f, "~ {}: {}", values(&self.key), columns.join(", "))
+            }
+            (None, Some(after)) => write!(f, "+ {}", values(after)),
+            (Some(before), None) => write!(f, "- {}", values(before)),
+            (None, None) => write!(f, "  {}", values(&self.key)),
+        }
+    }
+}
+
+/// A word outside of strings, quoted identifiers, comments and parentheses, with its byte range
+/// and the index of the statement it belongs to.
+struct Word {
+    start: usize,
+    end: usize,
+    upper: String,
+    statement: usize,
+}
+
+/// Finds the top-level words of the statements in `sql`.
+fn top_level_words(sql: &str) -> Vec<Word> {
+    let bytes = sql.as_bytes();
+    let mut words = Vec::new();
+    let mut depth = 0usize;
+    let mut statement = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'\'' | b'"' | b'`' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != c {
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            // PostgreSQL dollar-quoted strings, such as function bodies in `$$ ... $$` or `$body$ ... $body$`.
+            b'$' => {
+                let tag_end = bytes[i + 1..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphabetic() || *b == b'_'))
+                    .map(|position| i + 1 + position);
+                if let Some(tag_end) = tag_end.filter(|end| bytes[*end] == b'$') {
+                    let tag = &sql[i..=tag_end];
+                    i = sql[tag_end + 1..]
+                        .find(tag)
+                        .map_or(bytes.len(), |position| tag_end + position + tag.len());
+                }
+            }
+            b'(' => depth += 1,
+            b')' => depth = depth.saturating_sub(1),
+            b';' if depth == 0 => statement += 1,
+            _ if c.is_ascii_alphanumeric() || c == b'_' => {
+                let start = i;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || b"_$".contains(&bytes[i]))
+                {
+                    i += 1;
+                }
+                if depth == 0 {
+                    words.push(Word {
+                        start,
+                        end: i,
+                        upper: sql[start..i].to_ascii_uppercase(),
+                        statement,
+                    });
+                }
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    words
+}
+
+/// First keyword of each statement in `sql`, in upper case.
+pub fn statement_keywords(sql: &str) -> Vec<String> {
+    let mut keywords: Vec<(usize, String)> = Vec::new();
+    for word in top_level_words(sql) {
+        if keywords
+            .last()
+            .is_none_or(|(statement, _)| *statement != word.statement)
+        {
+            keywords.push((word.statement, word.upper));
+        }
+    }
+    keywords.into_iter().map(|(_, keyword)| keyword).collect()
+}
+
+/// Makes sure `sql` is a single statement that only reads, such as a SELECT or an EXPLAIN.
+/// Settings can't be changed either, as they could turn read-only mode off for later statements.
+pub fn check_read(sql: &str) -> AppResult<()> {
+    match statement_keywords(sql).as_slice() {
+        [keyword] if READ_KEYWORDS.contains(&keyword.as_str()) => Ok(()),
+        [keyword] => Err(AppError::InvalidArgument(format!(
+            "{} statements aren't allowed, only read queries can be run",
+            keyword
+        ))),
+        [] => Err(AppError::InvalidArgument("the query is empty".into())),
+        _ => Err(AppError::InvalidArgument(
+            "only a single query can be run at a time".into(),
+        )),
+    }
+}
+
+/// Splits text at a separator outside of quotes and parentheses.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, _) if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Parses `table`, `table alias` or `table AS alias`.
+/// Returns `None` for lists of tables and joins.
+fn parse_table(text: &str) -> Option<(String, Option<String>)> {
+    if split_top_level(text, ',').len() > 1 {
+        return None;
+    }
+    let text = text.trim().replace(['\n', '\r', '\t'], " ");
+    let parts: Vec<&str> = split_top_level(&text, ' ')
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .collect();
+    match parts.as_slice() {
+        [table] => Some((table.to_string(), None)),
+        [table, alias] => Some((table.to_string(), Some(alias.to_string()))),
+        [table, r#as, alias] if r#as.eq_ignore_ascii_case("AS") => {
+            Some((table.to_string(), Some(alias.to_string())))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a single INSERT, UPDATE or DELETE statement.
+pub fn parse(statement: &str) -> AppResult<Dml> {
+    let sql = statement.trim();
+    let words = top_level_words(sql);
+    if words.iter().any(|word| word.statement > 0) {
+        return Err(AppError::InvalidArgument(
+            "only one statement can be run at a time".into(),
+        ));
+    }
+    let position = |keyword: &str, from: usize| {
+        words
+            .iter()
+            .skip(from)
+            .position(|word| word.upper == keyword)
+            .map(|i| i + from)
+    };
+    // Text between two words, or up to the end of the statement.
+    let between = |start: usize, end: Option<usize>| {
+        let end = end.map_or(sql.len(), |end| words[end].start);
+        sql[words[start].end..end]
+            .trim()
+            .trim_end_matches(';')
+            .trim()
+    };
+    let first_of = |keywords: &[&str], from: usize| {
+        keywords
+            .iter()
+            .filter_map(|keyword| position(keyword, from))
+            .min()
+    };
+    let returning = position("RETURNING", 0).is_some();
+    let dml = |kind, target| {
+        Ok(Dml {
+            kind,
+            target,
+            returning,
+        })
+    };
+
+    match words.first().map(|word| word.upper.as_str()) {
+        Some("INSERT" | "REPLACE") => {
+            let target = position("INTO", 0).and_then(|into| {
+                let rest = between(into, None);
+                // The name ends at the column list or a space, outside of quotes.
+                let mut quote = None;
+                let end = rest
+                    .char_indices()
+                    .find(|&(_, c)| match quote {
+                        Some(q) => {
+                            if c == q {
+                                quote = None;
+                            }
+                            false
+                        }
+                        None if c == '"' || c == '`' => {
+                            quote = Some(c);
+                            false
+                        }
+                        None => c == '(' || c.is_whitespace(),
+                    })
+                    .map_or(rest.len(), |(i, _)| i);
+                if end == 0 {
+                    return None;
+                }
+                Some(Target {
+                    table: rest[..end].to_string(),
+                    alias: None,
+                    condition: None,
+                })
+            });
+            dml(DmlKind::Insert, target)
+        }
+        Some("UPDATE") => {
+            let Some(set) = position("SET", 1) else {
+                return Err(AppError::InvalidArgument(
+                    "UPDATE statement without SET".into(),
+                ));
+            };
+            let modifiers = ["ONLY", "LOW_PRIORITY", "IGNORE"];
+            let mut start = 0;
+            while words
+                .get(start + 1)
+                .is_some_and(|word| modifiers.contains(&word.upper.as_str()))
+            {
+                start += 1;
+            }
+            let table = between(start, Some(set));
+            let joins = position("JOIN", 1).is_some_and(|join| join < set);
+            // `UPDATE ... FROM` joins other tables.
+            let target = if joins || position("FROM", set).is_some() {
+                None
+            } else {
+                parse_table(table).map(|(table, alias)| Target {
+                    table,
+                    alias,
+                    condition: position("WHERE", set).map(|condition| {
+                        between(
+                            condition,
+                            first_of(&["RETURNING", "ORDER", "LIMIT"], condition),
+                        )
+                        .to_string()
+                    }),
+                })
+            };
+            dml(DmlKind::Update, target)
+        }
+        Some("DELETE") => {
+            // `DELETE t FROM ...` names tables before `FROM` to delete from a join.
+            let from = position("FROM", 1).filter(|from| {
+                words[1..*from]
+                    .iter()
+                    .all(|word| ["LOW_PRIORITY", "QUICK", "IGNORE"].contains(&word.upper.as_str()))
+            });
+            let target = from.and_then(|from| {
+                let end = first_of(&["WHERE", "USING", "RETURNING", "ORDER", "LIMIT"], from);
+                if position("USING", from).is_some() || position("JOIN", from).is_some() {
+                    return None;
+                }
+                let (table, alias) = parse_table(between(from, end))?;
+                Some(Target {
+                    table,
+                    alias,
+                    condition: position("WHERE", from).map(|condition| {
+                        between(
+                            condition,
+                            first_of(&["RETURNING", "ORDER", "LIMIT"], condition),
+                        )
+                        .to_string()
+                    }),
+                })
+            });
+            dml(DmlKind::Delete, target)
+        }
+        _ => Err(AppError::InvalidArgument(
+            "only INSERT, UPDATE and DELETE statements can be run in write mode".into(),
+        )),
+    }
+}
+
+/// Alias of the `i`th primary key column converted to text, used to find updated rows again.
+pub fn key_alias(i: usize) -> String {
+    format!("airy_key_{}", i)
+}
+
+/// Removes the first `count` key columns added under `key_alias` from a row, and returns them.
+pub fn take_key(row: &mut Row, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| match row.remove(&key_alias(i)) {
+            Some(serde_json::Value::String(text)) => text,
+            value => value.unwrap_or_default().to_string(),
+        })
+        .collect()
+}
+
+/// Values of the key columns of a row, or the whole row if there are none.
+fn key_of(row: &Row, key_columns: &[String]) -> Row {
+    if key_columns.is_empty() {
+        return row.clone();
+    }
+    key_columns
+        .iter()
+        .filter_map(|column| Some((column.clone(), row.get(column)?.clone())))
+        .collect()
+}
+
+/// Pairs rows before and after a statement by their key.
+/// Updated rows keep only the columns whose values changed, and rows left unchanged are left out.
+pub fn diff(
+    kind: DmlKind,
+    key_columns: &[String],
+    before: Vec<Row>,
+    after: Vec<Row>,
+) -> Vec<RowChange> {
+    match kind {
+        DmlKind::Insert => after
+            .into_iter()
+            .map(|row| RowChange {
+                key: key_of(&row, key_columns),
+                before: None,
+                after: Some(row),
+            })
+            .collect(),
+        DmlKind::Delete => before
+            .into_iter()
+            .map(|row| RowChange {
+                key: key_of(&row, key_columns),
+                before: Some(row),
+                after: None,
+            })
+            .collect(),
+        DmlKind::Update => {
+            let mut after: Vec<(Row, Row)> = after
+                .into_iter()
+                .map(|row| (key_of(&row, key_columns), row))
+                .collect();
+            let mut changes = Vec::new();
+            for row in before {
+                let key = key_of(&row, key_columns);
+                let Some(i) = after.iter().position(|(after_key, _)| *after_key == key) else {
+                    // The key itself was changed.
+                    changes.push(RowChange {
+                        key,
+                        before: Some(row),
+                        after: None,
+                    });
+                    continue;
+                };
+                let (_, new_row) = after.swap_remove(i);
+                let changed: Vec<&String> = new_row
+                    .iter()
+                    .filter(|(column, value)| row.get(*column) != Some(*value))
+                    .map(|(column, _)| column)
+                    .collect();
+                if changed.is_empty() {
+                    continue;
+                }
+                let pick = |row: &Row| -> Row {
+                    changed
+                        .iter()
+                        .map(|column| {
+                            let value = row.get(*column).cloned().unwrap_or_default();
+                            ((*column).clone(), value)
+                        })
+                        .collect()
+                };
+                changes.push(RowChange {
+                    key,
+                    before: Some(pick(&row)),
+                    after: Some(pick(&new_row)),
+                });
+            }
+            changes.extend(after.into_iter().map(|(key, row)| RowChange {
+                key,
+                before: None,
+                after: Some(row),
+            }));
+            changes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn row(value: serde_json::Value) -> Row {
+        let serde_json::Value::Object(row) = value else {
+            unreachable!()
+        };
+        row
+    }
+
+    #[test]
+    fn parses_update_with_alias_and_condition() {
+        let dml = parse(
+            "UPDATE public.employees AS e SET last_name = 'O''Neil; Jr' WHERE e.emp_no IN (SELECT 1) ORDER BY 1 LIMIT 5;",
+        )
+        .unwrap();
+        assert_eq!(dml.kind, DmlKind::Update);
+        let target = dml.target.unwrap();
+        assert_eq!(target.table, "public.employees");
+        assert_eq!(target.qualifier(), "e");
+        assert_eq!(target.condition.as_deref(), Some("e.emp_no IN (SELECT 1)"));
+        assert_eq!(
+            target.select("e.*"),
+            "SELECT e.* FROM public.employees e WHERE e.emp_no IN (SELECT 1)"
+        );
+    }
+
+    #[test]
+    fn only_read_queries_pass() {
+        assert!(check_read("SELECT 1").is_ok());
+        assert!(check_read("WITH t AS (SELECT 1) SELECT * FROM t;").is_ok());
+        assert!(check_read("SET default_transaction_read_only = off").is_err());
+        assert!(check_read("SET SESSION TRANSACTION READ WRITE").is_err());
+        assert!(check_read("UPDATE employees SET last_name = 'x'").is_err());
+        assert!(
+            check_read(
+                "SET default_transaction_read_only = off; UPDATE employees SET last_name = 'x'"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn joins_have_no_target() {
+        let update = parse("UPDATE a SET x = b.x FROM b WHERE a.id = b.id").unwrap();
+        assert_eq!(update.target, None);
+        let delete = parse("DELETE a FROM a JOIN b ON a.id = b.id").unwrap();
+        assert_eq!(delete.target, None);
+        let delete = parse("DELETE FROM a USING b WHERE a.id = b.id RETURNING a.id").unwrap();
+        assert_eq!(delete.target, None);
+        assert!(delete.returning);
+    }
+
+    #[test]
+    fn parses_insert_and_delete_targets() {
+        let insert = parse("INSERT INTO \"Weird \"\"Name\"\"\"(id) VALUES (1)").unwrap();
+        let target = insert.target.unwrap();
+        assert_eq!(target.unquoted_table(), "Weird \"Name\"");
+        let delete = parse("delete from `app`.`users` where id = 3 -- cleanup").unwrap();
+        let target = delete.target.unwrap();
+        assert_eq!(target.unquoted_table(), "app.users");
+        assert_eq!(target.condition.as_deref(), Some("id = 3 -- cleanup"));
+    }
+
+    #[test]
+    fn rejects_other_and_multiple_statements() {
+        assert!(parse("DROP TABLE users").is_err());
+        assert!(parse("SELECT 1").is_err());
+        assert!(parse("DELETE FROM a; DELETE FROM b").is_err());
+        assert!(parse("DELETE FROM a WHERE note = 'x;y';").is_ok());
+    }
+
+    #[test]
+    fn renders_changes() {
+        let update = RowChange {
+            key: row(json!({"id": 1})),
+            before: Some(row(json!({"name": "a"}))),
+            after: Some(row(json!({"name": "b"}))),
+        };
+        assert_eq!(update.to_string(), "~ id = 1: name: \"a\" -> \"b\"");
+        let delete = RowChange {
+            key: row(json!({"id": 2})),
+            before: Some(row(json!({"id": 2, "name": null}))),
+            after: None,
+        };
+        assert_eq!(delete.to_string(), "- id = 2, name = null");
+    }
+
+    #[test]
+    fn diffs_updated_columns_by_key() {
+        let key = vec!["id".to_string()];
+        let changes = diff(
+            DmlKind::Update,
+            &key,
+            vec![
+                row(json!({"id": 1, "name": "a", "age": 30})),
+                row(json!({"id": 2, "name": "b", "age": 40})),
+            ],
+            vec![
+                row(json!({"id": 2, "name": "b", "age": 40})),
+                row(json!({"id": 1, "name": "a", "age": 31})),
+            ],
+        );
+        assert_eq!(
+            changes,
+            vec![RowChange {
+                key: row(json!({"id": 1})),
+                before: Some(row(json!({"age": 30}))),
+                after: Some(row(json!({"age": 31}))),
+            }]
+        );
+    }
+}
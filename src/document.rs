@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    client::{ChatCompletionMessage, ChatCompletionRequest, Client, Content, MessageRole},
+    database::Database,
+    diagram::key_markers,
+    error::AppResult,
+    schema::{DatabaseSchema, TableSchema},
+    tool::Row,
+    utility::{html_escape, html_unescape, json_in_reply, shorten},
+};
+
+/// Max number of characters of sample rows shown to the model for a table.
+const MAX_SAMPLE_CHARS: usize = 4000;
+
+const DRAFT_PROMPT: &str = "You write data dictionaries. \
+Describe the business meaning of database tables and columns in one short sentence each, \
+based on their definitions and sample rows. \
+Reply with JSON only, in the form {\"table\": \"...\", \"columns\": {\"<column>\": \"...\"}}.";
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum DocumentFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+/// Descriptions of the tables and columns of a database, with their definitions and sizes.
+#[derive(Debug, PartialEq)]
+pub struct DataDictionary {
+    pub database: String,
+    pub tables: Vec<TableEntry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TableEntry {
+    pub name: String,
+    pub description: Option<String>,
+    pub row_estimate: Option<i64>,
+    pub columns: Vec<ColumnEntry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ColumnEntry {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub key: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Descriptions read back from a previous version of a document, by table and by table and column.
+#[derive(Debug, Default, PartialEq)]
+pub struct Descriptions {
+    pub tables: HashMap<String, String>,
+    pub columns: HashMap<(String, String), String>,
+}
+
+/// Model reply with drafted descriptions.
+#[derive(Deserialize)]
+struct Draft {
+    table: Option<String>,
+    #[serde(default)]
+    columns: HashMap<String, String>,
+}
+
+/// Drafts descriptions and migrations with a chat model.
+pub struct Drafter<'a> {
+    pub client: &'a Client,
+    pub model: &'a str,
+    pub max_tokens: i64,
+}
+
+impl DataDictionary {
+    /// Starts from the schema, describing tables and columns with their comments.
+    pub fn from_schema(schema: &DatabaseSchema) -> Self {
+        Self {
+            database: schema.database.clone(),
+            tables: schema
+                .tables
+                .iter()
+                .map(|table| TableEntry {
+                    name: schema.qualified_name(table),
+                    description: table.comment.clone(),
+                    row_estimate: table.row_estimate,
+                    columns: table
+                        .columns
+                        .iter()
+                        .map(|column| ColumnEntry {
+                            name: column.name.clone(),
+                            data_type: column.data_type.clone(),
+                            nullable: column.nullable,
+                            key: Some(key_markers(table, column).join(", "))
+                                .filter(|markers| !markers.is_empty()),
+                            description: column.comment.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Takes descriptions from a previous version of the document, which may have been edited by hand,
+    /// for tables and columns without a comment. Comments stay the source of truth where they exist.
+    pub fn merge(&mut self, previous: &Descriptions) {
+        for table in &mut self.tables {
+            if table.description.is_none() {
+                table.description = previous.tables.get(&table.name).cloned();
+            }
+            for column in &mut table.columns {
+                if column.description.is_none() {
+                    column.description = previous
+                        .columns
+                        .get(&(table.name.clone(), column.name.clone()))
+                        .cloned();
+                }
+            }
+        }
+    }
+
+    pub fn render(&self, format: DocumentFormat) -> String {
+        match format {
+            DocumentFormat::Markdown => self.markdown(),
+            DocumentFormat::Html => self.html(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let mut document = format!(
+            "# {}\n\nData dictionary generated by airy. Edit descriptions freely, they're kept when it's regenerated.\n",
+            self.database
+        );
+        for table in &self.tables {
+            document += &format!("\n## {}\n\n", table.name);
+            if let Some(description) = &table.description {
+                document += &format!("{}\n\n", single_line(description));
+            }
+            if let Some(row_estimate) = table.row_estimate {
+                document += &format!("_About {} rows._\n\n", row_estimate);
+            }
+            document += "| Column | Type | Nullable | Key | Description |\n";
+            document += "| --- | --- | --- | --- | --- |\n";
+            for column in &table.columns {
+                document += &format!(
+                    "| {} | {} | {} | {} | {} |\n",
+                    markdown_cell(&column.name),
+                    markdown_cell(&column.data_type),
+                    if column.nullable { "yes" } else { "no" },
+                    markdown_cell(column.key.as_deref().unwrap_or_default()),
+                    markdown_cell(column.description.as_deref().unwrap_or_default())
+                );
+            }
+        }
+        document
+    }
+
+    fn html(&self) -> String {
+        let mut document = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+             <style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+             th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}</style>\n\
+             </head>\n<body>\n<h1>{0}</h1>\n\
+             <p>Data dictionary generated by airy. Edit descriptions freely, they're kept when it's regenerated.</p>\n",
+            html_escape(&self.database)
+        );
+        for table in &self.tables {
+            document += &format!(
+                "<section>\n<h2>{}</h2>\n<p class=\"description\">{}</p>\n",
+                html_escape(&table.name),
+                html_escape(&single_line(
+                    table.description.as_deref().unwrap_or_default()
+                ))
+            );
+            if let Some(row_estimate) = table.row_estimate {
+                document += &format!("<p class=\"rows\">About {} rows.</p>\n", row_estimate);
+            }
+            document += "<table>\n<tr><th>Column</th><th>Type</th><th>Nullable</th><th>Key</th><th>Description</th></tr>\n";
+            for column in &table.columns {
+                document += &format!(
+                    "<tr><td class=\"column\">{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"description\">{}</td></tr>\n",
+                    html_escape(&column.name),
+                    html_escape(&column.data_type),
+                    if column.nullable { "yes" } else { "no" },
+                    html_escape(column.key.as_deref().unwrap_or_default()),
+                    html_escape(&single_line(
+                        column.description.as_deref().unwrap_or_default()
+                    ))
+                );
+            }
+            document += "</table>\n</section>\n";
+        }
+        document += "</body>\n</html>\n";
+        document
+    }
+}
+
+impl TableEntry {
+    fn is_described(&self) -> bool {
+        self.description.is_some()
+            && self
+                .columns
+                .iter()
+                .all(|column| column.description.is_some())
+    }
+}
+
+impl Descriptions {
+    /// Reads descriptions from a document rendered by [`DataDictionary::render`].
+    /// Parsing is line based, so edits have to keep each table row on its own line.
+    pub fn parse(document: &str, format: DocumentFormat) -> Self {
+        match format {
+            DocumentFormat::Markdown => Self::parse_markdown(document),
+            DocumentFormat::Html => Self::parse_html(document),
+        }
+    }
+
+    fn parse_markdown(document: &str) -> Self {
+        let mut descriptions = Self::default();
+        let mut table: Option<String> = None;
+        let mut paragraph: Vec<&str> = Vec::new();
+        for line in document.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("## ") {
+                table = Some(name.trim().to_string());
+                paragraph.clear();
+                continue;
+            }
+            let Some(table) = &table else {
+                continue;
+            };
+            if line.starts_with('|') {
+                if !paragraph.is_empty() {
+                    descriptions
+                        .tables
+                        .insert(table.clone(), paragraph.join(" "));
+                    paragraph.clear();
+                }
+                let cells = markdown_cells(line);
+                if let [name, .., description] = cells.as_slice()
+                    && cells.len() == 5
+                    && name != "Column"
+                    && !name.starts_with("---")
+                    && !description.is_empty()
+                {
+                    descriptions
+                        .columns
+                        .insert((table.clone(), name.clone()), description.clone());
+                }
+            } else if !line.is_empty() && !is_row_estimate(line) {
+                paragraph.push(line);
+            }
+        }
+        descriptions
+    }
+
+    fn parse_html(document: &str) -> Self {
+        let mut descriptions = Self::default();
+        let mut table: Option<String> = None;
+        for line in document.lines().map(str::trim) {
+            if let Some(name) = element_text(line, "<h2>", "</h2>") {
+                table = Some(html_unescape(name));
+                continue;
+            }
+            let Some(table) = &table else {
+                continue;
+            };
+            if let Some(description) = element_text(line, "<p class=\"description\">", "</p>") {
+                if !description.trim().is_empty() {
+                    descriptions
+                        .tables
+                        .insert(table.clone(), html_unescape(description.trim()));
+                }
+            } else if let Some(name) = element_text(line, "<td class=\"column\">", "</td>")
+                && let Some(description) = element_text(line, "<td class=\"description\">", "</td>")
+                && !description.trim().is_empty()
+            {
+                descriptions.columns.insert(
+                    (table.clone(), html_unescape(name)),
+                    html_unescape(description.trim()),
+                );
+            }
+        }
+        descriptions
+    }
+}
+
+impl Drafter<'_> {
+    /// Sends a conversation to the model and returns the text of its reply.
+    pub async fn reply(&self, messages: Vec<ChatCompletionMessage>) -> AppResult<String> {
+        let response = self
+            .client
+            .chat_completion(
+                ChatCompletionRequest::new(self.model.to_string(), messages)
+                    .max_tokens(self.max_tokens),
+            )
+            .await?;
+        Ok(response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .unwrap_or_default())
+    }
+
+    /// Has the model draft the missing descriptions of a table from its definition and sample rows.
+    /// Leaves them missing if the reply can't be parsed.
+    pub async fn draft(
+        &self,
+        table: &TableSchema,
+        sample_rows: &[Row],
+        entry: &mut TableEntry,
+    ) -> AppResult<()> {
+        let samples = shorten(&serde_json::to_string(sample_rows)?, MAX_SAMPLE_CHARS);
+        let missing: Vec<&str> = entry
+            .columns
+            .iter()
+            .filter(|column| column.description.is_none())
+            .map(|column| column.name.as_str())
+            .collect();
+        let mut request = format!("{}\nSample rows: {}\n\nDescribe ", table, samples);
+        if entry.description.is_none() {
+            request += "the table";
+            if !missing.is_empty() {
+                request += " and ";
+            }
+        }
+        if !missing.is_empty() {
+            request += &format!("the columns {}", missing.join(", "));
+        }
+        request += ".";
+
+        let reply = self
+            .reply(vec![
+                message(MessageRole::system, DRAFT_PROMPT),
+                message(MessageRole::user, &request),
+            ])
+            .await?;
+        let Some(draft) = json_in_reply::<Draft>(&reply) else {
+            eprintln!("Couldn't read the descriptions drafted for {}.", entry.name);
+            return Ok(());
+        };
+
+        if entry.description.is_none() {
+            entry.description = draft.table.filter(|description| !description.is_empty());
+        }
+        for column in &mut entry.columns {
+            if column.description.is_none() {
+                column.description = draft
+                    .columns
+                    .get(&column.name)
+                    .filter(|description| !description.is_empty())
+                    .cloned();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the data dictionary of a database.
+/// Descriptions come from comments, then from `previous`, and the rest are drafted from a few sample rows of each table.
+pub async fn generate(
+    database: &Database,
+    previous: &Descriptions,
+    drafter: &Drafter<'_>,
+    sample_rows: usize,
+) -> AppResult<DataDictionary> {
+    let schema = database.manager.get_schema().await?;
+    let mut dictionary = DataDictionary::from_schema(&schema);
+    dictionary.merge(previous);
+
+    for (table, entry) in schema.tables.iter().zip(&mut dictionary.tables) {
+        let details = database
+            .manager
+            .get_table(&table.schema, &table.name)
+            .await?;
+        let details = details.as_ref().unwrap_or(table);
+        entry.row_estimate = details.row_estimate;
+        if entry.is_described() {
+            continue;
+        }
+        eprintln!("Describing {}...", entry.name);
+        let rows = database
+            .manager
+            .sample_rows(details, &[], sample_rows)
+            .await?;
+        drafter.draft(table, &rows, entry).await?;
+    }
+    Ok(dictionary)
+}
+
+pub fn message(role: MessageRole, content: &str) -> ChatCompletionMessage {
+    ChatCompletionMessage {
+        role,
+        content: Content::Text(content.into()),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+/// Whether a line is the `_About N rows._` line of the Markdown format.
+fn is_row_estimate(line: &str) -> bool {
+    line.starts_with("_About ") && line.ends_with(" rows._")
+}
+
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn markdown_cell(text: &str) -> String {
+    single_line(text).replace('|', "\\|")
+}
+
+/// Splits a Markdown table row into cells, keeping escaped pipes.
+fn markdown_cells(line: &str) -> Vec<String> {
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                chars.next();
+                cells.last_mut().unwrap().push('|');
+            }
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Text between `start` and the following `end` in a line.
+fn element_text<'a>(line: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &line[line.find(start)? + start.len()..];
+    Some(&rest[..rest.find(end)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary() -> DataDictionary {
+        DataDictionary {
+            database: "shop".into(),
+            tables: vec![TableEntry {
+                name: "orders".into(),
+                description: Some("Orders placed by customers.".into()),
+                row_estimate: Some(120),
+                columns: vec![
+                    ColumnEntry {
+                        name: "id".into(),
+                        data_type: "integer".into(),
+                        nullable: false,
+                        key: Some("PK".into()),
+                        description: Some("Order number.".into()),
+                    },
+                    ColumnEntry {
+                        name: "status".into(),
+                        data_type: "text".into(),
+                        nullable: true,
+                        key: None,
+                        description: Some("One of <new|paid> & \"shipped\".".into()),
+                    },
+                    ColumnEntry {
+                        name: "note".into(),
+                        data_type: "text".into(),
+                        nullable: true,
+                        key: None,
+                        description: None,
+                    },
+                ],
+            }],
+        }
+    }
+
+    fn expected() -> Descriptions {
+        Descriptions {
+            tables: HashMap::from([("orders".into(), "Orders placed by customers.".into())]),
+            columns: HashMap::from([
+                (("orders".into(), "id".into()), "Order number.".into()),
+                (
+                    ("orders".into(), "status".into()),
+                    "One of <new|paid> & \"shipped\".".into(),
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn reads_back_rendered_descriptions() {
+        for format in [DocumentFormat::Markdown, DocumentFormat::Html] {
+            let document = dictionary().render(format);
+            assert_eq!(Descriptions::parse(&document, format), expected());
+        }
+    }
+
+    #[test]
+    fn merges_hand_edits_below_comments() {
+        let document = dictionary()
+            .render(DocumentFormat::Markdown)
+            .replace(
+                "Orders placed by customers.",
+                "Orders.\nSee the sales wiki.",
+            )
+            .replace("| yes |  |  |", "| yes |  | Free text from checkout. |")
+            .replace("Order number.", "Edited.");
+
+        // The table has lost its comment, while the `id` column keeps its comment.
+        let mut dictionary = dictionary();
+        dictionary.tables[0].description = None;
+        dictionary.merge(&Descriptions::parse(&document, DocumentFormat::Markdown));
+        assert_eq!(
+            dictionary.tables[0].description.as_deref(),
+            Some("Orders. See the sales wiki.")
+        );
+        assert_eq!(
+            dictionary.tables[0].columns[0].description.as_deref(),
+            Some("Order number.")
+        );
+        assert_eq!(
+            dictionary.tables[0].columns[2].description.as_deref(),
+            Some("Free text from checkout.")
+        );
+    }
+
+    #[test]
+    fn parses_drafts_wrapped_in_prose() {
+        let draft: Draft = json_in_reply(
+            "Here you go:\n```json\n{\"table\": \"Orders.\", \"columns\": {\"id\": \"Key.\"}}\n```",
+        )
+        .unwrap();
+        assert_eq!(draft.table.as_deref(), Some("Orders."));
+        assert_eq!(draft.columns["id"], "Key.");
+    }
+}
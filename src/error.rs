@@ -1,13 +1,18 @@
-use rmcp::{Error as McpError, ServiceError};
+use rmcp::{
+    ErrorData as McpError, ServiceError, service::ClientInitializeError,
+    transport::sse_client::SseTransportError,
+};
 use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("internal error: {0}")]
-    Internal(#[from] Box<dyn std::error::Error>),
+    Internal(#[from] Box<dyn std::error::Error + Send + Sync>),
     #[error("service error: {0}")]
     Service(#[from] ServiceError),
+    #[error("SSE transport error: {0}")]
+    SseTransport(#[from] SseTransportError<reqwest::Error>),
     #[error("request error: {0}")]
     Request(#[from] reqwest::Error),
     #[error("sql error: {0}")]
@@ -22,10 +27,22 @@ pub enum AppError {
     MissingApiKey,
     #[error("missing database URL")]
     MissingDatabaseUrl,
+    #[error("resource not found: {0}")]
+    ResourceNotFound(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("config error: {0}")]
+    Config(String),
 }
 
 pub type AppResult<T> = Result<T, AppError>;
 
+impl From<ClientInitializeError> for AppError {
+    fn from(err: ClientInitializeError) -> Self {
+        AppError::Internal(Box::new(err))
+    }
+}
+
 impl Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -48,6 +65,7 @@ impl From<AppError> for McpError {
         match err {
             AppError::Internal(err) => McpError::internal_error(err.to_string(), None),
             AppError::Service(err) => McpError::internal_error(err.to_string(), None),
+            AppError::SseTransport(err) => McpError::internal_error(err.to_string(), None),
             AppError::Request(err) => McpError::invalid_request(err.to_string(), None),
             AppError::Sql(err) => McpError::internal_error(err.to_string(), None),
             AppError::Io(err) => McpError::internal_error(err.to_string(), None),
@@ -57,6 +75,11 @@ impl From<AppError> for McpError {
                 McpError::invalid_request("Missing OpenRouter API key", None)
             }
             AppError::MissingDatabaseUrl => McpError::invalid_request("Missing database URL", None),
+            AppError::ResourceNotFound(uri) => {
+                McpError::resource_not_found(format!("Resource not found: {}", uri), None)
+            }
+            AppError::InvalidArgument(message) => McpError::invalid_params(message, None),
+            AppError::Config(message) => McpError::internal_error(message, None),
         }
     }
 }
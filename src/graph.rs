@@ -0,0 +1,244 @@
+use std::collections::{HashMap, VecDeque};
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::Serialize;
+
+use crate::schema::{DatabaseSchema, ForeignKeySchema, TableSchema};
+
+/// A join to the next table along a foreign key.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Join {
+    pub table: String,
+    /// Join condition, such as `orders.customer_id = customers.id`.
+    pub on: String,
+    pub foreign_key: String,
+}
+
+/// Graph with tables as nodes and foreign keys as edges, followed in either direction.
+pub struct RelationGraph<'a> {
+    schema: &'a DatabaseSchema,
+    positions: HashMap<(&'a str, &'a str), usize>,
+    /// Edges of each table, in the order of `schema.tables`.
+    edges: Vec<Vec<Edge<'a>>>,
+}
+
+struct Edge<'a> {
+    to: usize,
+    /// Table that has the foreign key.
+    referencing: usize,
+    referenced: usize,
+    foreign_key: &'a ForeignKeySchema,
+}
+
+impl<'a> RelationGraph<'a> {
+    pub fn new(schema: &'a DatabaseSchema) -> Self {
+        let positions: HashMap<(&str, &str), usize> = schema
+            .tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| ((table.schema.as_str(), table.name.as_str()), i))
+            .collect();
+
+        let mut edges: Vec<Vec<Edge>> = schema.tables.iter().map(|_| Vec::new()).collect();
+        for (i, table) in schema.tables.iter().enumerate() {
+            for foreign_key in &table.foreign_keys {
+                let Some(&j) = positions.get(&(
+                    foreign_key.referenced_schema.as_str(),
+                    foreign_key.referenced_table.as_str(),
+                )) else {
+                    continue;
+                };
+                // Self-references never shorten a path.
+                if i == j {
+                    continue;
+                }
+                edges[i].push(Edge {
+                    to: j,
+                    referencing: i,
+                    referenced: j,
+                    foreign_key,
+                });
+                edges[j].push(Edge {
+                    to: i,
+                    referencing: i,
+                    referenced: j,
+                    foreign_key,
+                });
+            }
+        }
+
+        Self {
+            schema,
+            positions,
+            edges,
+        }
+    }
+
+    /// Shortest chain of joins from `from` to `to`, or `None` if no foreign keys connect them.
+    /// Breadth-first, so among paths of equal length the one through earlier foreign keys wins.
+    pub fn join_path(&self, from: &TableSchema, to: &TableSchema) -> Option<Vec<Join>> {
+        let start = self.position(from)?;
+        let goal = self.position(to)?;
+
+        let mut previous: Vec<Option<(usize, &Edge)>> = self.edges.iter().map(|_| None).collect();
+        let mut visited = vec![false; self.edges.len()];
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(current) = queue.pop_front() {
+            if current == goal {
+                break;
+            }
+            for edge in &self.edges[current] {
+                if !visited[edge.to] {
+                    visited[edge.to] = true;
+                    previous[edge.to] = Some((current, edge));
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        if !visited[goal] {
+            return None;
+        }
+
+        let mut joins = Vec::new();
+        let mut current = goal;
+        while let Some((from, edge)) = previous[current] {
+            joins.push(self.join(edge));
+            current = from;
+        }
+        joins.reverse();
+        Some(joins)
+    }
+
+    /// Tables within `hops` foreign keys of any of `tables`, in schema order.
+    pub fn neighborhood(&self, tables: &[&TableSchema], hops: usize) -> Vec<&'a TableSchema> {
+        let mut distances: Vec<Option<usize>> = self.edges.iter().map(|_| None).collect();
+        let mut queue = VecDeque::new();
+        for table in tables {
+            if let Some(i) = self.position(table) {
+                distances[i] = Some(0);
+                queue.push_back(i);
+            }
+        }
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[current].unwrap_or_default();
+            if distance == hops {
+                continue;
+            }
+            for edge in &self.edges[current] {
+                if distances[edge.to].is_none() {
+                    distances[edge.to] = Some(distance + 1);
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        self.schema
+            .tables
+            .iter()
+            .zip(distances)
+            .filter(|(_, distance)| distance.is_some())
+            .map(|(table, _)| table)
+            .collect()
+    }
+
+    fn position(&self, table: &TableSchema) -> Option<usize> {
+        self.positions
+            .get(&(table.schema.as_str(), table.name.as_str()))
+            .copied()
+    }
+
+    fn join(&self, edge: &Edge) -> Join {
+        let foreign_key = edge.foreign_key;
+        let referencing = self
+            .schema
+            .qualified_name(&self.schema.tables[edge.referencing]);
+        let referenced = self
+            .schema
+            .qualified_name(&self.schema.tables[edge.referenced]);
+        let on = foreign_key
+            .columns
+            .iter()
+            .zip(&foreign_key.referenced_columns)
+            .map(|(column, referenced_column)| {
+                format!(
+                    "{}.{} = {}.{}",
+                    referencing, column, referenced, referenced_column
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ");
+
+        Join {
+            table: self.schema.qualified_name(&self.schema.tables[edge.to]),
+            on,
+            foreign_key: foreign_key.name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixture::{database, table};
+
+    fn schema() -> DatabaseSchema {
+        database(
+            "shop",
+            vec![
+                table("customers", Vec::new(), &[]),
+                table("orders", Vec::new(), &["customers"]),
+                table("order_items", Vec::new(), &["orders", "products"]),
+                table("products", Vec::new(), &[]),
+                table("warehouses", Vec::new(), &[]),
+            ],
+        )
+    }
+
+    #[test]
+    fn joins_along_foreign_keys_in_both_directions() {
+        let schema = schema();
+        let graph = RelationGraph::new(&schema);
+        let joins = graph
+            .join_path(&schema.tables[0], &schema.tables[3])
+            .unwrap();
+        assert_eq!(
+            joins
+                .iter()
+                .map(|join| format!("JOIN {} ON {}", join.table, join.on))
+                .collect::<Vec<_>>(),
+            [
+                "JOIN orders ON orders.customers_id = customers.id",
+                "JOIN order_items ON order_items.orders_id = orders.id",
+                "JOIN products ON order_items.products_id = products.id",
+            ]
+        );
+    }
+
+    #[test]
+    fn neighborhood_grows_with_hops() {
+        let schema = schema();
+        let graph = RelationGraph::new(&schema);
+        let names = |hops| -> Vec<String> {
+            graph
+                .neighborhood(&[&schema.tables[3]], hops)
+                .iter()
+                .map(|table| table.name.clone())
+                .collect()
+        };
+        assert_eq!(names(0), ["products"]);
+        assert_eq!(names(1), ["order_items", "products"]);
+        assert_eq!(names(2), ["orders", "order_items", "products"]);
+    }
+
+    #[test]
+    fn unconnected_tables_have_no_path() {
+        let schema = schema();
+        let graph = RelationGraph::new(&schema);
+        assert_eq!(graph.join_path(&schema.tables[0], &schema.tables[4]), None);
+        assert_eq!(
+            graph.join_path(&schema.tables[1], &schema.tables[1]),
+            Some(Vec::new())
+        );
+    }
+}
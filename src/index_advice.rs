@@ -0,0 +1,745 @@
+use std::fmt::{self, Display};
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    dml,
+    error::{AppError, AppResult},
+    schema::{DatabaseSchema, TableSchema},
+    utility::shorten,
+};
+
+/// Default and max number of the slowest queries of the statement history that are explained.
+pub const SLOW_QUERIES: usize = 10;
+pub const MAX_SLOW_QUERIES: usize = 50;
+/// Tables estimated to have fewer rows are cheap to scan, so no index is suggested for them.
+pub const MIN_TABLE_ROWS: i64 = 10_000;
+/// Scans keeping a larger share of the rows of a table are cheaper than going through an index.
+pub const MAX_SELECTIVITY: f64 = 0.2;
+/// Max number of columns of a suggested index.
+const MAX_INDEX_COLUMNS: usize = 3;
+/// Longest identifier both MySQL and PostgreSQL accept.
+const MAX_NAME_LEN: usize = 63;
+/// Queries are cut to this many characters in the text of the advice.
+const MAX_QUERY_CHARS: usize = 200;
+
+/// A query that was explained, with its statistics if it comes from the statement history.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct SlowQuery {
+    pub query: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calls: Option<i64>,
+    /// Time spent running it, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_ms: Option<f64>,
+}
+
+impl SlowQuery {
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.trim().trim_end_matches(';').into(),
+            calls: None,
+            total_ms: None,
+            mean_ms: None,
+        }
+    }
+}
+
+/// Planner cost of a query without and with a suggested index, estimated with a hypothetical index.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    /// Number of the query in `queries`, from 1.
+    pub query: usize,
+    pub before: f64,
+    pub after: f64,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct IndexCandidate {
+    pub statement: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    /// Why the index would help, from the first plan it was found in.
+    pub reasoning: String,
+    /// Numbers of the queries in `queries` whose plans scan the table, from 1.
+    pub queries: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub estimates: Vec<CostEstimate>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone, Default)]
+pub struct IndexAdvice {
+    /// Where the queries come from, such as `pg_stat_statements`.
+    pub source: String,
+    pub queries: Vec<SlowQuery>,
+    pub candidates: Vec<IndexCandidate>,
+    /// Why queries couldn't be collected or explained, and why impact isn't estimated.
+    pub notes: Vec<String>,
+}
+
+/// A full scan of a table found in a plan, with the condition filtering its rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    /// Schema of the table, if the plan tells.
+    pub schema: Option<String>,
+    /// Name of the table, or its alias on MySQL.
+    pub table: String,
+    /// Name qualifying the columns of the table in the condition.
+    pub qualifier: String,
+    pub condition: String,
+    /// Estimated number of rows kept by the condition.
+    pub rows: Option<f64>,
+}
+
+/// How a column is compared in a condition, which decides its place in an index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    /// `=` or `IN`, for the leading columns.
+    Equality,
+    /// `<`, `>` or `BETWEEN`, for a single column after them.
+    Range,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    pub column: String,
+    pub comparison: Comparison,
+}
+
+/// Reads a number given as a JSON number or a string, as EXPLAIN output mixes both.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Makes sure a query is a single read statement, as statements after it would run when it's prepared.
+pub fn check_query(query: &str) -> AppResult<()> {
+    match dml::statement_keywords(query).as_slice() {
+        [keyword] if ["SELECT", "WITH", "TABLE", "VALUES"].contains(&keyword.as_str()) => Ok(()),
+        [_] => Err(AppError::InvalidArgument(
+            "only read queries can be explained".into(),
+        )),
+        _ => Err(AppError::InvalidArgument(
+            "only a single query can be explained at a time".into(),
+        )),
+    }
+}
+
+/// Parses EXPLAIN output decoded from a JSON column, or from a text column holding JSON.
+pub fn plan_json(value: Value) -> Option<Value> {
+    match value {
+        Value::String(text) => serde_json::from_str(&text).ok(),
+        Value::Null => None,
+        value => Some(value),
+    }
+}
+
+/// Total cost of a plan from `EXPLAIN (FORMAT JSON)` on PostgreSQL.
+pub fn postgres_cost(plan: &Value) -> Option<f64> {
+    number(&plan[0]["Plan"]["Total Cost"])
+}
+
+/// Sequential scans with a filter in a plan from `EXPLAIN (FORMAT JSON, VERBOSE)` on PostgreSQL.
+pub fn postgres_scans(plan: &Value) -> Vec<Scan> {
+    fn walk(node: &Value, processes: f64, scans: &mut Vec<Scan>) {
+        let mut processes = processes;
+        match node["Node Type"].as_str() {
+            // Workers split the rows of parallel scans with the leader.
+            Some("Gather" | "Gather Merge") => {
+                processes = number(&node["Workers Planned"]).unwrap_or(0.0) + 1.0;
+            }
+            Some("Seq Scan") => {
+                if let (Some(table), Some(condition)) =
+                    (node["Relation Name"].as_str(), node["Filter"].as_str())
+                {
+                    let parallel = node["Parallel Aware"].as_bool() == Some(true);
+                    scans.push(Scan {
+                        schema: node["Schema"].as_str().map(Into::into),
+                        table: table.into(),
+                        qualifier: node["Alias"].as_str().unwrap_or(table).into(),
+                        condition: condition.into(),
+                        rows: number(&node["Plan Rows"])
+                            .map(|rows| if parallel { rows * processes } else { rows }),
+                    });
+                }
+            }
+            _ => {}
+        }
+        for child in node["Plans"].as_array().into_iter().flatten() {
+            walk(child, processes, scans);
+        }
+    }
+
+    let mut scans = Vec::new();
+    walk(&plan[0]["Plan"], 1.0, &mut scans);
+    scans
+}
+
+/// Full table scans with an attached condition in a plan from `EXPLAIN FORMAT=JSON` on MySQL.
+pub fn mysql_scans(plan: &Value) -> Vec<Scan> {
+    fn walk(value: &Value, scans: &mut Vec<Scan>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(table) = object.get("table")
+                    && table["access_type"].as_str() == Some("ALL")
+                    && let (Some(name), Some(condition)) = (
+                        table["table_name"].as_str(),
+                        table["attached_condition"].as_str(),
+                    )
+                {
+                    let filtered = number(&table["filtered"]).unwrap_or(100.0);
+                    scans.push(Scan {
+                        schema: None,
+                        table: name.into(),
+                        qualifier: name.into(),
+                        condition: condition.into(),
+                        rows: number(&table["rows_examined_per_scan"])
+                            .map(|rows| rows * filtered / 100.0),
+                    });
+                }
+                object.values().for_each(|value| walk(value, scans));
+            }
+            Value::Array(values) => values.iter().for_each(|value| walk(value, scans)),
+            _ => {}
+        }
+    }
+
+    let mut scans = Vec::new();
+    walk(plan, &mut scans);
+    scans
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Possibly qualified name, such as `o.status` or `` `shop`.`o`.`status` ``.
+    Name(Vec<String>),
+    Operator(String),
+    Open,
+    Close,
+    Cast,
+    /// Literals, parameters and punctuation that don't matter here.
+    Other,
+}
+
+fn tokens(condition: &str) -> Vec<Token> {
+    let chars: Vec<char> = condition.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' {
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' && chars.get(i + 1) == Some(&'\'') {
+                    i += 2;
+                } else if chars[i] == '\'' {
+                    break;
+                } else {
+                    i += 1;
+                }
+            }
+            i += 1;
+            tokens.push(Token::Other);
+        } else if c == '"' || c == '`' || c.is_alphabetic() || c == '_' {
+            let mut parts = Vec::new();
+            loop {
+                let mut part = String::new();
+                match chars.get(i) {
+                    Some(&quote) if quote == '"' || quote == '`' => {
+                        i += 1;
+                        while i < chars.len() {
+                            if chars[i] == quote && chars.get(i + 1) == Some(&quote) {
+                                part.push(quote);
+                                i += 2;
+                            } else if chars[i] == quote {
+                                i += 1;
+                                break;
+                            } else {
+                                part.push(chars[i]);
+                                i += 1;
+                            }
+                        }
+                    }
+                    _ => {
+                        while i < chars.len()
+                            && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                        {
+                            part.push(chars[i]);
+                            i += 1;
+                        }
+                    }
+                }
+                parts.push(part);
+                if chars.get(i) == Some(&'.')
+                    && chars
+                        .get(i + 1)
+                        .is_some_and(|&c| c == '"' || c == '`' || c.is_alphabetic() || c == '_')
+                {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Name(parts));
+        } else if "=<>!~".contains(c) {
+            let start = i;
+            while i < chars.len() && "=<>!~".contains(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token::Operator(chars[start..i].iter().collect()));
+        } else if c == ':' && chars.get(i + 1) == Some(&':') {
+            i += 2;
+            tokens.push(Token::Cast);
+        } else {
+            tokens.push(match c {
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => Token::Other,
+            });
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// How an operator or keyword next to a column compares it.
+fn comparison(token: &Token) -> Option<Comparison> {
+    match token {
+        Token::Operator(operator) => match operator.as_str() {
+            "=" => Some(Comparison::Equality),
+            "<" | ">" | "<=" | ">=" => Some(Comparison::Range),
+            _ => None,
+        },
+        Token::Name(parts) if parts.len() == 1 => match parts[0].to_lowercase().as_str() {
+            "in" => Some(Comparison::Equality),
+            "between" => Some(Comparison::Range),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Finds the columns qualified with `qualifier` that a condition compares in a way an index can serve.
+/// Columns wrapped in a function call can't use a plain index and are left out.
+pub fn predicates(condition: &str, qualifier: &str) -> Vec<Predicate> {
+    let tokens = tokens(condition);
+    let mut predicates: Vec<Predicate> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Name(parts) = token else {
+            continue;
+        };
+        // Plans qualify every column, and unqualified names are keywords and types.
+        if parts.len() < 2 || parts[parts.len() - 2] != qualifier {
+            continue;
+        }
+        let in_call = i >= 2
+            && tokens[i - 1] == Token::Open
+            && matches!(&tokens[i - 2], Token::Name(name) if name.len() == 1
+                && comparison(&tokens[i - 2]).is_none()
+                && !["and", "or", "not"].contains(&name[0].to_lowercase().as_str()));
+        if in_call {
+            continue;
+        }
+
+        // Skip the closing parentheses and casts after the column, as in `(o.status)::text = ...`.
+        let mut next = i + 1;
+        loop {
+            match tokens.get(next) {
+                Some(Token::Close) => next += 1,
+                Some(Token::Cast) => next += 2,
+                _ => break,
+            }
+        }
+        let found = tokens.get(next).and_then(comparison).or_else(|| {
+            i.checked_sub(1)
+                .and_then(|previous| comparison(&tokens[previous]))
+        });
+        let Some(comparison) = found else {
+            continue;
+        };
+        let column = &parts[parts.len() - 1];
+        match predicates
+            .iter_mut()
+            .find(|predicate| &predicate.column == column)
+        {
+            // A column compared for equality anywhere leads the index.
+            Some(predicate) if comparison == Comparison::Equality => {
+                predicate.comparison = comparison
+            }
+            Some(_) => {}
+            None => predicates.push(Predicate {
+                column: column.clone(),
+                comparison,
+            }),
+        }
+    }
+    predicates
+}
+
+/// Columns of an index serving the predicates on a table: compared columns first, then one range column.
+/// Returns `None` if no column qualifies or an existing index already starts with them.
+pub fn index_columns(table: &TableSchema, predicates: &[Predicate]) -> Option<Vec<String>> {
+    let column_name = |predicate: &Predicate| {
+        table
+            .columns
+            .iter()
+            .find(|column| column.name.eq_ignore_ascii_case(&predicate.column))
+            .map(|column| column.name.clone())
+    };
+    let mut columns: Vec<String> = predicates
+        .iter()
+        .filter(|predicate| predicate.comparison == Comparison::Equality)
+        .filter_map(column_name)
+        .take(MAX_INDEX_COLUMNS)
+        .collect();
+    if columns.len() < MAX_INDEX_COLUMNS
+        && let Some(range) = predicates
+            .iter()
+            .filter(|predicate| predicate.comparison == Comparison::Range)
+            .find_map(column_name)
+    {
+        columns.push(range);
+    }
+    let covered = table.indexes.iter().any(|index| {
+        index.columns.len() >= columns.len()
+            && index.columns.iter().zip(&columns).all(|(a, b)| a == b)
+    });
+    (!columns.is_empty() && !covered).then_some(columns)
+}
+
+/// Finds the table of a scan named by its alias, as MySQL plans do:
+/// the table of that name, or else the only table having every column the condition compares.
+pub fn resolve_table<'a>(schema: &'a DatabaseSchema, scan: &Scan) -> Option<&'a TableSchema> {
+    if let Some(table) = schema
+        .tables
+        .iter()
+        .find(|table| table.name.eq_ignore_ascii_case(&scan.table))
+    {
+        return Some(table);
+    }
+    let predicates = predicates(&scan.condition, &scan.qualifier);
+    let mut tables = schema.tables.iter().filter(|table| {
+        predicates.iter().all(|predicate| {
+            table
+                .columns
+                .iter()
+                .any(|column| column.name.eq_ignore_ascii_case(&predicate.column))
+        })
+    });
+    match (predicates.is_empty(), tables.next(), tables.next()) {
+        (false, Some(table), None) => Some(table),
+        _ => None,
+    }
+}
+
+/// Name of a suggested index, such as `orders_customer_id_idx`.
+fn index_name(table: &str, columns: &[String]) -> String {
+    let name = format!("{}_{}_idx", table, columns.join("_"));
+    name[..name.floor_char_boundary(MAX_NAME_LEN)].into()
+}
+
+impl IndexAdvice {
+    /// Starts advice on queries given by the user.
+    pub fn for_queries(queries: &[String]) -> Self {
+        Self {
+            source: "the given queries".into(),
+            queries: queries.iter().map(|query| SlowQuery::new(query)).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts advice on the slowest queries read from the statement history in `source`.
+    pub fn from_history(source: &str, history: AppResult<Vec<SlowQuery>>) -> Self {
+        let mut advice = Self {
+            source: source.into(),
+            ..Default::default()
+        };
+        match history {
+            Ok(queries) if queries.is_empty() => advice.notes.push(format!(
+                "{} has no read queries of this database yet.",
+                source
+            )),
+            Ok(queries) => advice.queries = queries,
+            Err(err) => advice.notes.push(format!(
+                "{} can't be read, pass the queries to explain instead: {}",
+                source, err
+            )),
+        }
+        advice
+    }
+
+    /// Suggests an index for a scan of `table` in the plan of the query numbered `query`, unless the scan keeps too many rows.
+    /// `label` names the table for people, and `target` is the quoted name the statement creates the index on.
+    pub fn add_scan(
+        &mut self,
+        table: &TableSchema,
+        label: &str,
+        target: &str,
+        quote: fn(&str) -> String,
+        scan: &Scan,
+        query: usize,
+    ) {
+        let Some(row_estimate) = table.row_estimate.filter(|rows| *rows >= MIN_TABLE_ROWS) else {
+            return;
+        };
+        if scan
+            .rows
+            .is_some_and(|rows| rows > row_estimate as f64 * MAX_SELECTIVITY)
+        {
+            return;
+        }
+        let Some(columns) = index_columns(table, &predicates(&scan.condition, &scan.qualifier))
+        else {
+            return;
+        };
+
+        if let Some(candidate) = self
+            .candidates
+            .iter_mut()
+            .find(|candidate| candidate.table == label && candidate.columns == columns)
+        {
+            if !candidate.queries.contains(&query) {
+                candidate.queries.push(query);
+            }
+            return;
+        }
+        let kept = scan
+            .rows
+            .map(|rows| format!(", keeping about {:.0}", rows))
+            .unwrap_or_default();
+        self.candidates.push(IndexCandidate {
+            statement: format!(
+                "CREATE INDEX {} ON {} ({});",
+                quote(&index_name(&table.name, &columns)),
+                target,
+                columns
+                    .iter()
+                    .map(|column| quote(column))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            table: label.into(),
+            columns,
+            reasoning: format!(
+                "Reads all of about {} rows of {} to filter on {}{}.",
+                row_estimate, label, scan.condition, kept
+            ),
+            queries: vec![query],
+            estimates: Vec::new(),
+        });
+    }
+}
+
+impl Display for IndexAdvice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.queries.is_empty() {
+            writeln!(
+                f,
+                "Explained {} queries from {}:",
+                self.queries.len(),
+                self.source
+            )?;
+        }
+        for (i, query) in self.queries.iter().enumerate() {
+            write!(f, "{}. ", i + 1)?;
+            if let (Some(calls), Some(mean_ms)) = (query.calls, query.mean_ms) {
+                write!(f, "{} calls, {:.1} ms on average: ", calls, mean_ms)?;
+            }
+            writeln!(f, "{}", shorten(&query.query, MAX_QUERY_CHARS))?;
+        }
+
+        if self.candidates.is_empty() {
+            writeln!(f, "No index to suggest.")?;
+        }
+        for candidate in &self.candidates {
+            writeln!(f, "\n{}", candidate.statement)?;
+            writeln!(f, "  {}", candidate.reasoning)?;
+            let queries: Vec<String> = candidate.queries.iter().map(|i| i.to_string()).collect();
+            writeln!(f, "  Queries: {}", queries.join(", "))?;
+            for estimate in &candidate.estimates {
+                writeln!(
+                    f,
+                    "  Estimated cost of query {}: {:.1} -> {:.1}",
+                    estimate.query, estimate.before, estimate.after
+                )?;
+            }
+        }
+
+        if !self.notes.is_empty() {
+            writeln!(f, "\nNotes:")?;
+        }
+        for note in &self.notes {
+            writeln!(f, "- {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::{
+        ColumnSchema, IndexSchema,
+        fixture::{column, table},
+    };
+
+    fn predicate(column: &str, comparison: Comparison) -> Predicate {
+        Predicate {
+            column: column.into(),
+            comparison,
+        }
+    }
+
+    fn orders() -> TableSchema {
+        let columns = ["id", "customer_id", "status", "created_at"]
+            .iter()
+            .map(|name| ColumnSchema {
+                nullable: false,
+                ..column(name, "integer")
+            })
+            .collect();
+        TableSchema {
+            indexes: vec![IndexSchema {
+                name: "orders_pkey".into(),
+                columns: vec!["id".into()],
+                unique: true,
+                primary: true,
+            }],
+            row_estimate: Some(100_000),
+            ..table("orders", columns, &[])
+        }
+    }
+
+    #[test]
+    fn finds_compared_columns_of_postgres_filters() {
+        assert_eq!(
+            predicates(
+                "(((o.status)::text = ANY ('{new,paid}'::text[])) AND (o.created_at > $1) AND (lower(o.note) = 'x'::text))",
+                "o"
+            ),
+            vec![
+                predicate("status", Comparison::Equality),
+                predicate("created_at", Comparison::Range),
+            ]
+        );
+        assert_eq!(
+            predicates("(c.id = o.customer_id)", "o"),
+            vec![predicate("customer_id", Comparison::Equality)]
+        );
+    }
+
+    #[test]
+    fn finds_compared_columns_of_mysql_conditions() {
+        assert_eq!(
+            predicates(
+                "((`shop`.`o`.`created_at` between '2020-01-01' and '2021-01-01') and (`shop`.`o`.`status` in ('new','it''s')) and (`shop`.`c`.`id` = 1))",
+                "o"
+            ),
+            vec![
+                predicate("created_at", Comparison::Range),
+                predicate("status", Comparison::Equality),
+            ]
+        );
+    }
+
+    #[test]
+    fn explains_single_read_queries() {
+        assert!(check_query("WITH t AS (SELECT 1) SELECT * FROM t;").is_ok());
+        assert!(check_query("SELECT 1; DROP TABLE orders").is_err());
+        assert!(check_query("DELETE FROM orders").is_err());
+    }
+
+    #[test]
+    fn orders_equality_columns_before_a_range() {
+        let table = orders();
+        assert_eq!(
+            index_columns(
+                &table,
+                &[
+                    predicate("created_at", Comparison::Range),
+                    predicate("status", Comparison::Equality),
+                    predicate("unknown", Comparison::Equality),
+                ]
+            ),
+            Some(vec!["status".to_string(), "created_at".to_string()])
+        );
+        assert_eq!(
+            index_columns(&table, &[predicate("id", Comparison::Equality)]),
+            None
+        );
+    }
+
+    #[test]
+    fn suggests_indexes_for_selective_scans() {
+        let plan = json!([{ "Plan": {
+            "Node Type": "Gather",
+            "Workers Planned": 1,
+            "Plans": [{
+                "Node Type": "Seq Scan",
+                "Parallel Aware": true,
+                "Relation Name": "orders",
+                "Schema": "public",
+                "Alias": "o",
+                "Plan Rows": 50,
+                "Filter": "(o.customer_id = $1)"
+            }, {
+                "Node Type": "Seq Scan",
+                "Relation Name": "orders",
+                "Schema": "public",
+                "Alias": "o2",
+                "Plan Rows": 50000,
+                "Filter": "(o2.status = $1)"
+            }]
+        }}]);
+        let scans = postgres_scans(&plan);
+        assert_eq!(scans[0].rows, Some(100.0));
+
+        let mut advice = IndexAdvice::default();
+        let quote = |name: &str| format!("\"{}\"", name);
+        for scan in &scans {
+            advice.add_scan(&orders(), "orders", "\"orders\"", quote, scan, 1);
+        }
+        assert_eq!(advice.candidates.len(), 1);
+        assert_eq!(
+            advice.candidates[0].statement,
+            "CREATE INDEX \"orders_customer_id_idx\" ON \"orders\" (\"customer_id\");"
+        );
+    }
+
+    #[test]
+    fn finds_full_scans_of_mysql_plans() {
+        let plan = json!({ "query_block": {
+            "nested_loop": [
+                { "table": { "table_name": "c", "access_type": "const" } },
+                { "table": {
+                    "table_name": "o",
+                    "access_type": "ALL",
+                    "rows_examined_per_scan": 10000,
+                    "filtered": "1.00",
+                    "attached_condition": "(`shop`.`o`.`customer_id` = 7)"
+                } }
+            ]
+        }});
+        assert_eq!(
+            mysql_scans(&plan),
+            vec![Scan {
+                schema: None,
+                table: "o".into(),
+                qualifier: "o".into(),
+                condition: "(`shop`.`o`.`customer_id` = 7)".into(),
+                rows: Some(100.0),
+            }]
+        );
+    }
+}
@@ -1,7 +1,22 @@
 pub mod cli;
 pub mod client;
+pub mod config;
+pub mod database;
+pub mod diagram;
+pub mod dml;
+pub mod document;
 pub mod error;
+pub mod graph;
+pub mod index_advice;
+pub mod migration;
 pub mod mysql_utility;
+pub mod profile;
 pub mod repl;
+pub mod schema;
+pub mod search;
+pub mod server;
+pub mod sql_error;
+pub mod template;
 pub mod tool;
 pub mod utility;
+pub mod value_search;
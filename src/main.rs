@@ -1,87 +1,217 @@
 use std::{
-    net::{Ipv4Addr, SocketAddr},
+    net::{SocketAddr, TcpListener},
     sync::Arc,
 };
 
 use clap::Parser;
-use rmcp::{
-    ServiceExt,
-    transport::{sse_server::SseServer, stdio},
-};
-use sqlx::{PgPool, mysql::MySqlPoolOptions};
+use rmcp::{ServiceExt, transport::stdio};
 
 use airy::{
     cli::{Cli, CliCommand},
     client::Client,
+    config::Config,
+    database::{DEFAULT_SYSTEM_PROMPT, DatabaseOptions, Databases},
+    diagram,
+    document::{self, Descriptions, Drafter},
     error::{AppError, AppResult},
+    index_advice, migration,
     repl::ReplSession,
-    tool::{mysql::MySqlManager, postgres::PostgresManager},
+    server::{self, HttpServerOptions, TlsOptions},
+    tool::{
+        external::{ExternalServers, McpServersConfig},
+        handler::DatabaseHandler,
+    },
 };
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
     let args = Cli::parse();
+    let settings = args
+        .profile_overrides()
+        .merge(Config::load()?.profile(args.profile.as_deref())?)
+        .into_settings()?;
 
-    macro_rules! impl_db {
-        (
-            $prompt_file:expr,
-            $manager_type:ty,
-            $pool:expr
-            $(,)?
-        ) => {
-            let mut system_prompt = include_str!($prompt_file).to_string();
-            if let CliCommand::Chat {
-                system_prompt: Some(system_prompt_option),
-            } = &args.command
-            {
-                if system_prompt_option.is_empty() {
-                    println!("{}", system_prompt);
-                    return Ok(());
-                }
-                system_prompt = system_prompt_option.clone();
-            }
+    let mut system_prompt = None;
+    if let CliCommand::Chat {
+        system_prompt: Some(system_prompt_option),
+        ..
+    } = &args.command
+    {
+        if system_prompt_option.is_empty() {
+            println!("{}", DEFAULT_SYSTEM_PROMPT);
+            return Ok(());
+        }
+        system_prompt = Some(system_prompt_option.clone());
+    }
+    if system_prompt.is_none()
+        && let Some(system_prompt_file) = &settings.system_prompt_file
+    {
+        system_prompt = Some(std::fs::read_to_string(system_prompt_file).map_err(|err| {
+            AppError::Config(format!("{}: {}", system_prompt_file.display(), err))
+        })?);
+    }
 
-            let manager = <$manager_type>::new($pool, system_prompt);
+    let databases = Databases::connect(
+        settings.databases.clone(),
+        DatabaseOptions {
+            system_prompt,
+            read_only: settings.read_only,
+            write: settings.write,
+            row_limit: settings.row_limit,
+            schema_token_budget: settings.schema_token_budget,
+        },
+    )
+    .await?;
+    let databases = Arc::new(databases);
 
-            match args.command {
-                CliCommand::Chat { .. } => {
-                    let mut client = Client::create(
-                        args.openrouter_base_url.clone(),
-                        args.openrouter_api_key
-                            .clone()
-                            .ok_or(AppError::MissingApiKey)?,
-                    )?;
-                    client.add_tool(<$manager_type>::get_database_schema_tool_attr());
-                    client.add_tool(<$manager_type>::execute_query_tool_attr());
+    match args.command {
+        CliCommand::Chat { ref mcp_config, .. } => {
+            let handler = DatabaseHandler::for_chat(databases).await?;
+            let mut client = Client::create(
+                settings.base_url.clone(),
+                settings.api_key.clone().ok_or(AppError::MissingApiKey)?,
+            )?;
+            for tool in handler.tools() {
+                client.add_tool(tool);
+            }
 
-                    let mut repl_session = ReplSession::new(client, Arc::new(manager), &args);
-                    repl_session.run().await?;
-                }
-                CliCommand::Mcp { sse, port } => {
-                    if sse {
-                        let ct =
-                            SseServer::serve(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port))
-                                .await?
-                                .with_service(move || manager.clone());
-                        tokio::signal::ctrl_c().await?;
-                        ct.cancel();
-                    } else {
-                        let service = manager.serve(stdio()).await.unwrap();
-                        service.waiting().await.unwrap();
-                    }
-                }
+            let external_servers = if let Some(mcp_config) = mcp_config {
+                ExternalServers::connect(
+                    McpServersConfig::load(mcp_config)?,
+                    &client.tool_names().collect::<Vec<_>>(),
+                )
+                .await?
+            } else {
+                ExternalServers::default()
+            };
+            for tool in external_servers.tools() {
+                client.add_tool(tool.clone());
             }
-        };
-    }
 
-    if let Some(mysql_url) = args.mysql_url.as_ref() {
-        let pool = MySqlPoolOptions::new().connect(mysql_url).await?;
-        impl_db!("mysql_system_prompt.txt", MySqlManager, pool);
-    } else if let Some(postgres_url) = args.postgres_url.as_ref() {
-        let pool = PgPool::connect(postgres_url).await?;
-        impl_db!("postgres_system_prompt.txt", PostgresManager, pool);
-    } else {
-        return Err(AppError::MissingDatabaseUrl);
+            let mut repl_session = ReplSession::new(client, handler, &settings)
+                .with_external_servers(external_servers);
+            repl_session.run().await?;
+        }
+        CliCommand::Mcp {
+            http,
+            host,
+            port,
+            auth_token,
+            allow_unauthenticated,
+            tls_cert,
+            tls_key,
+        } => {
+            let handler = DatabaseHandler::new(databases).await?;
+            if http {
+                let options = HttpServerOptions {
+                    auth_token,
+                    tls: tls_cert
+                        .zip(tls_key)
+                        .map(|(cert_path, key_path)| TlsOptions {
+                            cert_path,
+                            key_path,
+                        }),
+                    allow_unauthenticated,
+                };
+                let listener = TcpListener::bind(SocketAddr::new(host, port))?;
+                server::serve(listener, options, move || handler.session(), async {
+                    let _ = tokio::signal::ctrl_c().await;
+                })
+                .await?;
+            } else {
+                let service = handler.serve(stdio()).await.unwrap();
+                service.waiting().await.unwrap();
+            }
+        }
+        CliCommand::Diagram {
+            format,
+            tables,
+            hops,
+            output,
+            database,
+        } => {
+            let schema = databases
+                .get(database.as_deref())?
+                .manager
+                .get_schema()
+                .await?;
+            let diagram = diagram::render(&schema, &tables, hops, format)?;
+            match output {
+                Some(path) => std::fs::write(path, diagram)?,
+                None => print!("{}", diagram),
+            }
+        }
+        CliCommand::Document {
+            format,
+            output,
+            sample_rows,
+            database,
+        } => {
+            let client = Client::create(
+                settings.base_url.clone(),
+                settings.api_key.clone().ok_or(AppError::MissingApiKey)?,
+            )?;
+            let previous = match &output {
+                Some(path) if path.exists() => {
+                    Descriptions::parse(&std::fs::read_to_string(path)?, format)
+                }
+                _ => Descriptions::default(),
+            };
+            let drafter = Drafter {
+                client: &client,
+                model: &settings.model,
+                max_tokens: settings.max_tokens,
+            };
+            let dictionary = document::generate(
+                databases.get(database.as_deref())?,
+                &previous,
+                &drafter,
+                sample_rows,
+            )
+            .await?;
+            let document = dictionary.render(format);
+            match output {
+                Some(path) => std::fs::write(path, document)?,
+                None => print!("{}", document),
+            }
+        }
+        CliCommand::AdviseIndexes {
+            queries,
+            limit,
+            database,
+        } => {
+            let advice = databases
+                .get(database.as_deref())?
+                .manager
+                .advise_indexes(&queries, limit.clamp(1, index_advice::MAX_SLOW_QUERIES))
+                .await?;
+            print!("{}", advice);
+        }
+        CliCommand::MigrateGen {
+            description,
+            dir,
+            name,
+            database,
+        } => {
+            let client = Client::create(
+                settings.base_url.clone(),
+                settings.api_key.clone().ok_or(AppError::MissingApiKey)?,
+            )?;
+            let drafter = Drafter {
+                client: &client,
+                model: &settings.model,
+                max_tokens: settings.max_tokens,
+            };
+            let migration =
+                migration::generate(databases.get(database.as_deref())?, &drafter, &description)
+                    .await?;
+            let name = name
+                .or(Some(migration.name.clone()))
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or(description);
+            let files = migration::write(&dir, &name, &migration)?;
+            println!("{}\n{}", files.up.display(), files.down.display());
+        }
     }
 
     Ok(())
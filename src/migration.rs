@@ -0,0 +1,292 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    client::MessageRole,
+    database::{Database, schema_summary},
+    dml,
+    document::{Drafter, message},
+    error::{AppError, AppResult},
+    schema::TableSchema,
+    search::{self, SchemaIndex},
+    utility::json_in_reply,
+};
+
+/// Number of drafts the model gets, each after being told what was wrong with the previous one.
+const MAX_ATTEMPTS: usize = 3;
+/// Token budget for the digest of the whole schema in the request.
+const SCHEMA_TOKENS: usize = 4000;
+/// Number of tables relevant to the change whose full definitions are included in the request.
+const RELEVANT_TABLES: usize = 5;
+/// Digits of the version of the first migration in a directory.
+const VERSION_WIDTH: usize = 4;
+/// First keywords of statements that start, end or split a transaction.
+/// `END` commits in PostgreSQL and `PREPARE` covers `PREPARE TRANSACTION`.
+const TRANSACTION_KEYWORDS: &[&str] = &[
+    "BEGIN", "START", "COMMIT", "END", "ROLLBACK", "ABORT", "SAVEPOINT", "RELEASE", "PREPARE",
+];
+
+const MIGRATION_PROMPT: &str = "You write database migrations. \
+Given a schema and a change, write the SQL that makes the change and the SQL that reverts it exactly, \
+for the given database server and version. \
+Don't include transaction control statements such as BEGIN or COMMIT, or USE statements. \
+Reply with JSON only, in the form {\"name\": \"<short_snake_case_name>\", \"up\": \"...\", \"down\": \"...\"}.";
+
+/// Migration drafted by the model.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Migration {
+    #[serde(default)]
+    pub name: String,
+    pub up: String,
+    pub down: String,
+}
+
+/// Paths of the files written for a migration.
+pub struct MigrationFiles {
+    pub up: PathBuf,
+    pub down: PathBuf,
+}
+
+/// Has the model draft a migration for a change, and retries until it applies and reverts cleanly.
+pub async fn generate(
+    database: &Database,
+    drafter: &Drafter<'_>,
+    description: &str,
+) -> AppResult<Migration> {
+    let manager = &database.manager;
+    let schema = manager.get_schema().await?;
+    let tables: Vec<&TableSchema> = SchemaIndex::new(&schema).search(description, RELEVANT_TABLES);
+    let mut request = format!(
+        "Database: {} {}\nSchema:\n{}\n",
+        manager.dialect(),
+        manager.server_version(),
+        schema_summary(&[(&database.name, schema.clone())], Some(SCHEMA_TOKENS)),
+    );
+    if !tables.is_empty() {
+        request += "\nRelevant tables:\n\n";
+        for table in &tables {
+            request += &format!("{}\n", table);
+            for related in search::related_tables(&schema, table) {
+                if !tables.iter().any(|table| std::ptr::eq(*table, related)) {
+                    request += &format!("{}\n", related);
+                }
+            }
+        }
+    }
+    request += &format!("\nChange: {}", description);
+
+    let mut messages = vec![
+        message(MessageRole::system, MIGRATION_PROMPT),
+        message(MessageRole::user, &request),
+    ];
+    let mut problems = Vec::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        eprintln!(
+            "Drafting the migration (attempt {} of {})...",
+            attempt, MAX_ATTEMPTS
+        );
+        let reply = drafter.reply(messages.clone()).await?;
+        messages.push(message(MessageRole::assistant, &reply));
+
+        problems = match json_in_reply::<Migration>(&reply) {
+            Some(migration) => {
+                let mut found = check_statements(&migration);
+                if found.is_empty() {
+                    eprintln!("Trying the migration...");
+                    found = manager
+                        .try_migration(&migration.up, &migration.down)
+                        .await?;
+                }
+                if found.is_empty() {
+                    return Ok(migration);
+                }
+                found
+            }
+            None => vec!["The reply isn't the requested JSON object.".to_string()],
+        };
+        for problem in &problems {
+            eprintln!("  {}", problem);
+        }
+        messages.push(message(
+            MessageRole::user,
+            &format!(
+                "The migration doesn't work:\n{}\nFix it and reply with the whole JSON object again.",
+                problems.join("\n")
+            ),
+        ));
+    }
+    Err(AppError::InvalidArgument(format!(
+        "no working migration after {} attempts: {}",
+        MAX_ATTEMPTS,
+        problems.join(" ")
+    )))
+}
+
+/// Finds statements that can't be tried in a transaction that is rolled back afterwards.
+fn check_statements(migration: &Migration) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (direction, sql) in [("up", &migration.up), ("down", &migration.down)] {
+        let keywords = dml::statement_keywords(sql);
+        if keywords.is_empty() {
+            problems.push(format!("The {} migration is empty.", direction));
+        }
+        for keyword in keywords {
+            if TRANSACTION_KEYWORDS.contains(&keyword.as_str()) {
+                problems.push(format!(
+                    "The {} migration controls the transaction with {}, leave that to the migration tool.",
+                    direction, keyword
+                ));
+            } else if keyword == "USE" {
+                problems.push(format!(
+                    "The {} migration switches the database with USE, it must run in the database it is applied to.",
+                    direction
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Compares the schema objects before a migration was applied and after it was reverted.
+pub fn unrestored(before: &[String], after: &[String]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for object in after.iter().filter(|object| !before.contains(object)) {
+        problems.push(format!("The down migration leaves behind: {}", object));
+    }
+    for object in before.iter().filter(|object| !after.contains(object)) {
+        problems.push(format!("The down migration doesn't restore: {}", object));
+    }
+    problems
+}
+
+/// Turns a name or description into a file name part, such as `add_email_to_users`.
+pub fn slug(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(8)
+        .map(|word| word.to_ascii_lowercase())
+        .collect();
+    if words.is_empty() {
+        "migration".into()
+    } else {
+        words.join("_")
+    }
+}
+
+/// Version and digit count of the next migration, after the highest `NNNN_name.up.sql` or `.down.sql` in a directory.
+fn next_version(dir: &Path) -> AppResult<(u64, usize)> {
+    let mut latest: Option<(u64, usize)> = None;
+    if dir.exists() {
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if !(name.ends_with(".up.sql") || name.ends_with(".down.sql")) {
+                continue;
+            }
+            let digits = name.split('_').next().unwrap_or_default();
+            if let Ok(version) = digits.parse::<u64>()
+                && latest.is_none_or(|(latest, _)| version > latest)
+            {
+                latest = Some((version, digits.len()));
+            }
+        }
+    }
+    Ok(latest.map_or((1, VERSION_WIDTH), |(version, width)| (version + 1, width)))
+}
+
+/// Writes the migration as the next numbered pair of files in a directory, creating it if needed.
+pub fn write(dir: &Path, name: &str, migration: &Migration) -> AppResult<MigrationFiles> {
+    let (version, width) = next_version(dir)?;
+    std::fs::create_dir_all(dir)?;
+    let stem = format!("{:0width$}_{}", version, slug(name), width = width);
+    let files = MigrationFiles {
+        up: dir.join(format!("{}.up.sql", stem)),
+        down: dir.join(format!("{}.down.sql", stem)),
+    };
+    std::fs::write(&files.up, format!("{}\n", migration.up.trim()))?;
+    std::fs::write(&files.down, format!("{}\n", migration.down.trim()))?;
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_transaction_control_outside_of_bodies() {
+        let migration = Migration {
+            name: "f".into(),
+            up: "CREATE FUNCTION f() RETURNS trigger AS $$ BEGIN RETURN NEW; END; $$ LANGUAGE plpgsql;"
+                .into(),
+            down: "BEGIN; DROP FUNCTION f(); COMMIT;".into(),
+        };
+        let problems = check_statements(&migration);
+        assert_eq!(problems.len(), 2);
+        assert!(
+            problems
+                .iter()
+                .all(|problem| problem.starts_with("The down"))
+        );
+    }
+
+    #[test]
+    fn rejects_every_transaction_control_statement() {
+        for statement in [
+            "BEGIN",
+            "START TRANSACTION",
+            "COMMIT",
+            "END",
+            "ROLLBACK",
+            "ABORT",
+            "SAVEPOINT s",
+            "RELEASE SAVEPOINT s",
+            "PREPARE TRANSACTION 't'",
+            "USE `shop`",
+        ] {
+            let migration = Migration {
+                name: "m".into(),
+                up: format!("CREATE TABLE t (id integer); {};", statement),
+                down: "DROP TABLE t;".into(),
+            };
+            assert_eq!(check_statements(&migration).len(), 1, "{}", statement);
+        }
+    }
+
+    #[test]
+    fn reports_objects_not_restored() {
+        let before = vec!["column users.id integer".to_string()];
+        let after = vec![
+            "column users.id bigint".to_string(),
+            "index users_email".to_string(),
+        ];
+        assert_eq!(
+            unrestored(&before, &after),
+            vec![
+                "The down migration leaves behind: column users.id bigint",
+                "The down migration leaves behind: index users_email",
+                "The down migration doesn't restore: column users.id integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_after_the_latest_migration() {
+        let dir = std::env::temp_dir().join(format!("airy-migrations-{}", std::process::id()));
+        let migration = Migration {
+            name: String::new(),
+            up: "CREATE TABLE a (id int);".into(),
+            down: "DROP TABLE a;".into(),
+        };
+        let first = write(&dir, "Add table A!", &migration).unwrap();
+        assert!(first.up.ends_with("0001_add_table_a.up.sql"));
+        std::fs::write(dir.join("0041_other.down.sql"), "").unwrap();
+        let next = write(&dir, "b", &migration).unwrap();
+        assert!(next.down.ends_with("0042_b.down.sql"));
+        assert_eq!(
+            std::fs::read_to_string(next.up).unwrap(),
+            "CREATE TABLE a (id int);\n"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
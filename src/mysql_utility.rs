@@ -236,10 +236,10 @@ mod deserializers {
                     {
                         let temp_json_val: Result<MySqlJson, _> =
                             decode_raw_mysql(raw_value_check.clone());
-                        if let Ok(json_wrapper) = temp_json_val {
-                            if matches!(json_wrapper.0, serde_json::Value::Array(_)) {
-                                return self.deserialize_seq(visitor);
-                            }
+                        if let Ok(json_wrapper) = temp_json_val
+                            && matches!(json_wrapper.0, serde_json::Value::Array(_))
+                        {
+                            return self.deserialize_seq(visitor);
                         }
                     }
                 }
@@ -283,11 +283,11 @@ mod deserializers {
 
             if type_name.eq_ignore_ascii_case("JSON") {
                 let json_val_res: Result<MySqlJson, _> = decode_raw_mysql(raw_value.clone());
-                if let Ok(json_val) = json_val_res {
-                    if matches!(json_val.0, serde_json::Value::Array(_)) {
-                        let seq_access = MySqlArraySeqAccess::new(raw_value)?;
-                        return visitor.visit_seq(seq_access);
-                    }
+                if let Ok(json_val) = json_val_res
+                    && matches!(json_val.0, serde_json::Value::Array(_))
+                {
+                    let seq_access = MySqlArraySeqAccess::new(raw_value)?;
+                    return visitor.visit_seq(seq_access);
                 }
             }
 
@@ -0,0 +1,220 @@
+use std::fmt::{self, Display};
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::Serialize;
+
+/// Max number of rows read to profile a column, or to pick sample rows from.
+pub const ROW_BUDGET: usize = 10_000;
+/// Number of most frequent values reported.
+pub const TOP_VALUES: usize = 5;
+/// Number of buckets of a histogram.
+pub const HISTOGRAM_BUCKETS: usize = 10;
+/// Frequent values and sample values are cut to this many characters.
+pub const MAX_VALUE_CHARS: usize = 100;
+/// Number of leading bytes of a binary value shown in hex.
+pub const BINARY_PREFIX_BYTES: usize = 16;
+
+/// Statistics of the values of a column, possibly computed on a sample.
+#[derive(Serialize, JsonSchema, Debug, Clone, Default)]
+pub struct ColumnProfile {
+    pub column: String,
+    pub data_type: String,
+    /// Number of rows profiled.
+    pub rows: i64,
+    /// Whether the rows were a sample of the table.
+    pub sampled: bool,
+    pub null_ratio: f64,
+    /// Number of distinct values in the profiled rows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distinct_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+    /// Most frequent values, most frequent first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub top_values: Vec<ValueCount>,
+    /// Lengths of strings, in characters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<LengthStats>,
+    /// Equal-width buckets of numbers and dates, with the smallest and largest value in each.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub histogram: Vec<Bucket>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct LengthStats {
+    pub min: i64,
+    pub max: i64,
+    pub average: f64,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Bucket {
+    pub min: String,
+    pub max: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct TableProfile {
+    pub table: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_estimate: Option<i64>,
+    pub columns: Vec<ColumnProfile>,
+}
+
+/// Which statistics make sense for a column, judging by its type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    /// Min, max, histogram.
+    Numeric,
+    /// Min, max, histogram over time.
+    Temporal,
+    /// Min, max, lengths.
+    Text,
+    /// Byte strings, shown as their size and a hex prefix.
+    Binary,
+    /// Only nulls, distinct and frequent values, compared as text.
+    Other,
+}
+
+impl ColumnKind {
+    /// Classifies a type as reported by MySQL or PostgreSQL, such as `varchar(40)` or `timestamp with time zone`.
+    pub fn of(data_type: &str) -> Self {
+        let data_type = data_type.to_lowercase();
+        let base = data_type
+            .split(['(', ' '])
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches('_');
+        if data_type.ends_with("[]") {
+            return ColumnKind::Other;
+        }
+        match base {
+            "smallint" | "integer" | "int" | "bigint" | "tinyint" | "mediumint" | "numeric"
+            | "decimal" | "real" | "double" | "float" => ColumnKind::Numeric,
+            "date" | "timestamp" | "datetime" => ColumnKind::Temporal,
+            "character" | "char" | "varchar" | "text" | "tinytext" | "mediumtext" | "longtext"
+            | "citext" | "enum" | "set" => ColumnKind::Text,
+            "bytea" | "binary" | "varbinary" | "blob" | "tinyblob" | "mediumblob" | "longblob" => {
+                ColumnKind::Binary
+            }
+            _ => ColumnKind::Other,
+        }
+    }
+
+    /// Whether values have an order, so min and max mean something.
+    pub fn is_ordered(self) -> bool {
+        matches!(
+            self,
+            ColumnKind::Numeric | ColumnKind::Temporal | ColumnKind::Text
+        )
+    }
+
+    /// Whether values can be put in a histogram.
+    pub fn has_histogram(self) -> bool {
+        matches!(self, ColumnKind::Numeric | ColumnKind::Temporal)
+    }
+}
+
+/// Fraction of a table to sample so that about `budget` rows are read, or `None` to read it whole.
+/// Samples return uneven counts, so callers still cap rows at `budget`.
+/// Oversampling would bias the capped rows towards the start of the table.
+pub fn sample_fraction(row_estimate: Option<i64>, budget: usize) -> Option<f64> {
+    let rows = row_estimate? as f64;
+    let budget = budget as f64;
+    (rows > budget).then(|| budget / rows)
+}
+
+impl Display for ColumnProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} rows",
+            self.column, self.data_type, self.rows
+        )?;
+        if self.sampled {
+            write!(f, " sampled")?;
+        }
+        writeln!(f, ", {:.1}% null", self.null_ratio * 100.0)?;
+        if let Some(distinct_count) = self.distinct_count {
+            writeln!(f, "  distinct: {}", distinct_count)?;
+        }
+        if let (Some(min), Some(max)) = (&self.min, &self.max) {
+            writeln!(f, "  range: {} .. {}", min, max)?;
+        }
+        if let Some(length) = &self.length {
+            writeln!(
+                f,
+                "  length: {} .. {}, average {:.1}",
+                length.min, length.max, length.average
+            )?;
+        }
+        if !self.top_values.is_empty() {
+            let values: Vec<String> = self
+                .top_values
+                .iter()
+                .map(|value| format!("{} ({})", value.value, value.count))
+                .collect();
+            writeln!(f, "  top values: {}", values.join(", "))?;
+        }
+        if !self.histogram.is_empty() {
+            writeln!(f, "  histogram:")?;
+            for bucket in &self.histogram {
+                writeln!(f, "    {} .. {}: {}", bucket.min, bucket.max, bucket.count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for TableProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Table: {}", self.table)?;
+        if let Some(row_estimate) = self.row_estimate {
+            write!(f, " (~{} rows)", row_estimate)?;
+        }
+        writeln!(f)?;
+        for column in &self.columns {
+            write!(f, "{}", column)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_types_of_both_dialects() {
+        assert_eq!(ColumnKind::of("integer"), ColumnKind::Numeric);
+        assert_eq!(ColumnKind::of("decimal(10,2)"), ColumnKind::Numeric);
+        assert_eq!(ColumnKind::of("int unsigned"), ColumnKind::Numeric);
+        assert_eq!(
+            ColumnKind::of("timestamp with time zone"),
+            ColumnKind::Temporal
+        );
+        assert_eq!(ColumnKind::of("datetime(6)"), ColumnKind::Temporal);
+        assert_eq!(ColumnKind::of("character varying(40)"), ColumnKind::Text);
+        assert_eq!(ColumnKind::of("enum('M','F')"), ColumnKind::Text);
+        assert_eq!(ColumnKind::of("integer[]"), ColumnKind::Other);
+        assert_eq!(ColumnKind::of("varbinary(16)"), ColumnKind::Binary);
+        assert_eq!(ColumnKind::of("jsonb"), ColumnKind::Other);
+        assert_eq!(ColumnKind::of("boolean"), ColumnKind::Other);
+    }
+
+    #[test]
+    fn samples_only_tables_over_budget() {
+        assert_eq!(sample_fraction(None, 100), None);
+        assert_eq!(sample_fraction(Some(100), 100), None);
+        assert_eq!(sample_fraction(Some(1000), 100), Some(0.1));
+    }
+}
@@ -1,31 +1,43 @@
-use std::io::{self, Write};
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use futures::{StreamExt, stream};
 use owo_colors::{OwoColorize, Stream::Stdout};
 use rmcp::model::{self, CallToolResult};
 
 use crate::{
-    cli::Cli,
     client::{
         ChatCompletionMessage, ChatCompletionRequest, Client, Content, MessageRole, ToolChoiceType,
     },
-    error::AppResult,
-    tool::ManagerArc,
+    config::Settings,
+    error::{AppError, AppResult},
+    sql_error::{self, SqlError},
+    tool::{external::ExternalServers, handler::DatabaseHandler},
     utility::fix_tool_call,
 };
 
 pub struct ReplSession {
     client: Client,
-    manager: ManagerArc,
+    handler: DatabaseHandler,
+    external_servers: ExternalServers,
     history: Vec<ChatCompletionMessage>,
     model: String,
     max_tokens: i64,
+    tool_concurrency: usize,
+    /// Statements that failed since the user's last message, to stop the model from retrying endlessly.
+    failed_statements: AtomicUsize,
 }
 
+/// Tools running statements written by the model, which it may fix and run again when they fail.
+const STATEMENT_TOOLS: [&str; 2] = ["executeQuery", "executeWrite"];
+
 impl ReplSession {
-    pub fn new(client: Client, manager: ManagerArc, args: &Cli) -> Self {
+    pub fn new(client: Client, handler: DatabaseHandler, settings: &Settings) -> Self {
         let history = vec![ChatCompletionMessage {
             role: MessageRole::system,
-            content: Content::Text(manager.system_prompt().into()),
+            content: Content::Text(handler.system_prompt().into()),
             name: None,
             tool_calls: None,
             tool_call_id: None,
@@ -33,15 +45,28 @@ impl ReplSession {
 
         Self {
             client,
-            manager,
+            handler,
+            external_servers: ExternalServers::default(),
             history,
-            model: args.model.clone(),
-            max_tokens: args.max_tokens,
+            model: settings.model.clone(),
+            max_tokens: settings.max_tokens,
+            tool_concurrency: settings.tool_concurrency,
+            failed_statements: AtomicUsize::new(0),
         }
     }
 
+    /// Routes tool calls that are not database tools to external MCP servers.
+    pub fn with_external_servers(mut self, external_servers: ExternalServers) -> Self {
+        self.external_servers = external_servers;
+        self
+    }
+
     pub async fn run(&mut self) -> AppResult<()> {
         println!("Database REPL started. Type 'exit' to quit.");
+        let server_names: Vec<_> = self.external_servers.server_names().collect();
+        if !server_names.is_empty() {
+            println!("Connected MCP servers: {}", server_names.join(", "));
+        }
 
         loop {
             print!("User> ");
@@ -57,6 +82,7 @@ impl ReplSession {
                 continue;
             }
 
+            self.failed_statements.store(0, Ordering::Relaxed);
             self.history.push(ChatCompletionMessage {
                 role: MessageRole::user,
                 content: Content::Text(user_input.to_string()),
@@ -95,67 +121,45 @@ impl ReplSession {
                             });
 
                             if let Some(tool_calls) = assistant_message.tool_calls {
-                                for (tool_call_id, function_name, params_json) in
-                                    tool_calls.into_iter().filter_map(|tool| {
+                                let session = &*self;
+                                let tool_results: Vec<_> =
+                                    stream::iter(tool_calls.into_iter().filter_map(|tool| {
                                         let params: Option<serde_json::Value> = tool
                                             .function
                                             .arguments
                                             .and_then(|args| serde_json::from_str(&args).ok());
                                         Some((tool.id, tool.function.name?, params))
+                                    }))
+                                    .map(|(tool_call_id, function_name, params_json)| async move {
+                                        let result_content =
+                                            session.call_tool(&function_name, params_json).await;
+                                        (tool_call_id, function_name, result_content)
                                     })
-                                {
-                                    let tool_result = match function_name.as_str() {
-                                        "mysqlGetDatabaseSchema" => {
-                                            self.manager.get_database_schema().await
-                                        }
-                                        "mysqlExecuteQuery" => {
-                                            if let Some(params_json) = params_json {
-                                                match serde_json::from_value(params_json) {
-                                                    Ok(params) => {
-                                                        self.manager.execute_query(params).await
-                                                    }
-                                                    Err(err) => Err(err.into()),
-                                                }
-                                            } else {
-                                                Ok(CallToolResult::error(vec![
-                                                    model::Content::text(format!(
-                                                        "Missing required parameter for {}",
-                                                        function_name,
-                                                    )),
-                                                ]))
-                                            }
-                                        }
-                                        _ => Ok(CallToolResult::error(vec![model::Content::text(
-                                            format!("Unknown tool: {}", function_name),
-                                        )])),
-                                    };
-
-                                    let result_content = match tool_result {
-                                        Ok(result) => {
-                                            // Assuming CallToolResult content is a Vec<Content>, and the first one is text.
-                                            if let Some(text) = result
-                                                .content
-                                                .first()
-                                                .and_then(|content| content.as_text())
-                                            {
-                                                text.text.clone()
-                                            } else if result.is_error.unwrap_or(false) {
-                                                format!(
-                                                    "Tool error {}: {:?}",
-                                                    function_name, result.content,
-                                                )
-                                            } else {
-                                                "Tool returned non-text or empty content".into()
-                                            }
-                                        }
-                                        Err(err) => {
-                                            format!(
-                                                "Error executing tool {}: {}",
-                                                function_name, err
-                                            )
-                                        }
-                                    };
+                                    .buffered(self.tool_concurrency)
+                                    .collect()
+                                    .await;
+
+                                let changes: Vec<&str> = tool_results
+                                    .iter()
+                                    .filter(|(_, function_name, _)| function_name == "executeWrite")
+                                    .map(|(_, _, result_content)| result_content.as_str())
+                                    .collect();
+                                let outcome = if self.handler.has_open_transaction().await {
+                                    Some(self.confirm_changes(&changes).await?)
+                                } else {
+                                    None
+                                };
 
+                                // The outcome goes with the last result, whichever tools the batch called.
+                                let last = tool_results.len().saturating_sub(1);
+                                for (i, (tool_call_id, function_name, mut result_content)) in
+                                    tool_results.into_iter().enumerate()
+                                {
+                                    if let Some(outcome) = &outcome
+                                        && i == last
+                                    {
+                                        result_content += &format!("\n\n{}", outcome);
+                                    }
                                     self.history.push(ChatCompletionMessage {
                                         role: MessageRole::tool,
                                         tool_call_id: Some(tool_call_id),
@@ -190,4 +194,115 @@ impl ReplSession {
         }
         Ok(())
     }
+
+    /// Shows the changes made by the model and asks the user whether to commit them, or rolls them back.
+    /// Returns the outcome, to let the model know.
+    async fn confirm_changes(&self, changes: &[&str]) -> AppResult<String> {
+        for change in changes {
+            println!("{}", change.if_supports_color(Stdout, |text| text.yellow()));
+        }
+        print!("Commit these changes? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        let commit = matches!(answer.trim().to_lowercase().as_str(), "y" | "yes");
+
+        let tool = if commit {
+            "commitTransaction"
+        } else {
+            "rollbackTransaction"
+        };
+        let result = match self.handler.call(tool, None).await {
+            Ok(result) => result
+                .content
+                .first()
+                .and_then(|content| content.as_text())
+                .map(|text| text.text.clone())
+                .unwrap_or_default(),
+            Err(err) => format!("Error: {}", err),
+        };
+        println!("{}", result);
+        Ok(if commit {
+            format!("The user approved the changes. {}", result)
+        } else {
+            format!("The user rejected the changes. {}", result)
+        })
+    }
+
+    /// Executes a single tool call and renders its result as text for the model.
+    /// Errors are rendered into the result, so they never affect other calls in the same batch.
+    async fn call_tool(
+        &self,
+        function_name: &str,
+        params_json: Option<serde_json::Value>,
+    ) -> String {
+        let statement = ["query", "statement"]
+            .iter()
+            .find_map(|key| params_json.as_ref()?.get(key)?.as_str())
+            .map(String::from);
+        let database = params_json
+            .as_ref()
+            .and_then(|params| params.get("database")?.as_str())
+            .map(String::from);
+        let tool_result = match function_name {
+            _ if STATEMENT_TOOLS.contains(&function_name)
+                && self.failed_statements.load(Ordering::Relaxed) >= sql_error::MAX_ATTEMPTS =>
+            {
+                Ok(CallToolResult::error(vec![model::Content::text(format!(
+                    "{} statements failed already, explain the errors to the user instead of trying again.",
+                    sql_error::MAX_ATTEMPTS
+                ))]))
+            }
+            _ if self.handler.has_tool(function_name) => {
+                self.handler.call(function_name, params_json).await
+            }
+            _ if self.external_servers.has_tool(function_name) => {
+                self.external_servers
+                    .call_tool(function_name, params_json)
+                    .await
+            }
+            _ => Ok(CallToolResult::error(vec![model::Content::text(format!(
+                "Unknown tool: {}",
+                function_name
+            ))])),
+        };
+
+        match tool_result {
+            Ok(result) => {
+                // Assuming CallToolResult content is a Vec<Content>, and the first one is text.
+                if let Some(text) = result.content.first().and_then(|content| content.as_text()) {
+                    text.text.clone()
+                } else if result.is_error.unwrap_or(false) {
+                    format!("Tool error {}: {:?}", function_name, result.content)
+                } else {
+                    "Tool returned non-text or empty content".into()
+                }
+            }
+            Err(AppError::Sql(err)) if STATEMENT_TOOLS.contains(&function_name) => {
+                let mut error = SqlError::new(&err, statement.as_deref());
+                if let Ok(database) = self.handler.databases().get(database.as_deref())
+                    && let Ok(schema) = database.manager.get_schema().await
+                {
+                    error.add_schema_hints(&schema, database.manager.dialect());
+                }
+                let failed = self.failed_statements.fetch_add(1, Ordering::Relaxed) + 1;
+                let next = if !error.class.is_fixable() || failed >= sql_error::MAX_ATTEMPTS {
+                    "Don't try again, explain the error to the user.".to_string()
+                } else {
+                    format!(
+                        "Fix the statement and try again, {} of {} attempts are left.",
+                        sql_error::MAX_ATTEMPTS - failed,
+                        sql_error::MAX_ATTEMPTS
+                    )
+                };
+                format!(
+                    "Error executing tool {}: {}\n{}",
+                    function_name, error, next
+                )
+            }
+            Err(err) => {
+                format!("Error executing tool {}: {}", function_name, err)
+            }
+        }
+    }
 }
@@ -0,0 +1,373 @@
+use std::fmt::{self, Display};
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::Serialize;
+
+#[cfg(test)]
+pub mod fixture;
+
+/// Introspected structure of a database.
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct DatabaseSchema {
+    pub database: String,
+    pub tables: Vec<TableSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub views: Vec<ViewSchema>,
+    /// Stored functions and procedures.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routines: Vec<RoutineSchema>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct TableSchema {
+    pub schema: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub columns: Vec<ColumnSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<IndexSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub foreign_keys: Vec<ForeignKeySchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<CheckSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<TriggerSchema>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_estimate: Option<i64>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra: Option<String>,
+    /// Expression of a generated column.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct IndexSchema {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    pub primary: bool,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct ForeignKeySchema {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct CheckSchema {
+    pub name: String,
+    pub expression: String,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct TriggerSchema {
+    pub name: String,
+    /// `BEFORE`, `AFTER` or `INSTEAD OF`.
+    pub timing: String,
+    /// `INSERT`, `UPDATE` or `DELETE`.
+    pub events: Vec<String>,
+    pub action: String,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct ViewSchema {
+    pub schema: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub materialized: bool,
+    pub columns: Vec<ColumnSchema>,
+    /// The `SELECT` the view is defined as, if the user can see it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub definition: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct RoutineSchema {
+    pub schema: String,
+    pub name: String,
+    /// `FUNCTION` or `PROCEDURE`.
+    pub kind: String,
+    pub arguments: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl DatabaseSchema {
+    pub fn find_table(&self, schema: &str, name: &str) -> Option<&TableSchema> {
+        self.tables
+            .iter()
+            .find(|table| table.schema == schema && table.name == name)
+    }
+
+    /// Finds a table by `schema.table` or by table name alone, ignoring case.
+    pub fn resolve_table(&self, name: &str) -> Option<&TableSchema> {
+        let name = name.trim();
+        if let Some((schema, table_name)) = name.split_once('.')
+            && let Some(table) = self.tables.iter().find(|table| {
+                table.schema.eq_ignore_ascii_case(schema)
+                    && table.name.eq_ignore_ascii_case(table_name)
+            })
+        {
+            return Some(table);
+        }
+        self.tables
+            .iter()
+            .find(|table| table.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Compact listing of tables with column types, primary keys and foreign keys, one table per line.
+    /// Views and routines follow with their columns and signatures.
+    pub fn digest(&self) -> String {
+        if self.tables.is_empty() && self.views.is_empty() && self.routines.is_empty() {
+            return "none\n".into();
+        }
+        let qualified = self.has_multiple_schemas();
+        let mut digest = String::new();
+        for table in &self.tables {
+            if qualified {
+                digest += &format!("{}.", table.schema);
+            }
+            digest += &table.name;
+            digest += "(";
+            for (i, column) in table.columns.iter().enumerate() {
+                if i > 0 {
+                    digest += ", ";
+                }
+                digest += &format!("{} {}", column.name, column.data_type);
+                if column.key.as_deref() == Some("PRI") {
+                    digest += " PK";
+                }
+                for foreign_key in &table.foreign_keys {
+                    if let Some(position) = foreign_key
+                        .columns
+                        .iter()
+                        .position(|name| *name == column.name)
+                    {
+                        digest += &format!(" FK->{}", foreign_key.referenced_table);
+                        if let Some(referenced_column) =
+                            foreign_key.referenced_columns.get(position)
+                        {
+                            digest += &format!(".{}", referenced_column);
+                        }
+                    }
+                }
+            }
+            digest += ")\n";
+        }
+        for view in &self.views {
+            digest += "view ";
+            if qualified {
+                digest += &format!("{}.", view.schema);
+            }
+            let columns: Vec<String> = view
+                .columns
+                .iter()
+                .map(|column| format!("{} {}", column.name, column.data_type))
+                .collect();
+            digest += &format!("{}({})\n", view.name, columns.join(", "));
+        }
+        for routine in &self.routines {
+            digest += &format!("{} ", routine.kind.to_lowercase());
+            if qualified {
+                digest += &format!("{}.", routine.schema);
+            }
+            digest += &format!("{}({})", routine.name, routine.arguments);
+            if let Some(returns) = &routine.returns {
+                digest += &format!(" -> {}", returns);
+            }
+            digest += "\n";
+        }
+        digest
+    }
+
+    fn has_multiple_schemas(&self) -> bool {
+        self.tables
+            .iter()
+            .any(|table| table.schema != self.tables[0].schema)
+    }
+
+    /// Name of a table, qualified with its schema if there are several schemas.
+    pub fn qualified_name(&self, table: &TableSchema) -> String {
+        if self.has_multiple_schemas() {
+            format!("{}.{}", table.schema, table.name)
+        } else {
+            table.name.clone()
+        }
+    }
+
+    /// Comma-separated table names, qualified with their schema if there are several schemas.
+    pub fn summary(&self) -> String {
+        if self.tables.is_empty() {
+            return "none".into();
+        }
+        self.tables
+            .iter()
+            .map(|table| self.qualified_name(table))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Display for DatabaseSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tables.is_empty() && self.views.is_empty() && self.routines.is_empty() {
+            return write!(f, "No tables found in database '{}'.", self.database);
+        }
+
+        writeln!(f, "Schema for database '{}':\n", self.database)?;
+        for table in &self.tables {
+            writeln!(f, "{}\n", table)?;
+        }
+        for view in &self.views {
+            writeln!(f, "{}\n", view)?;
+        }
+        for routine in &self.routines {
+            writeln!(f, "{}", routine)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TableSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Qualified, as tables of different schemas can share a name and this is shown on its own too.
+        write!(f, "Table: {}.{}", self.schema, self.name)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " -- {}", comment)?;
+        }
+        writeln!(f)?;
+        for column in &self.columns {
+            writeln!(f, "{}", column)?;
+        }
+        for index in &self.indexes {
+            write!(f, "  * INDEX {} ({})", index.name, index.columns.join(", "))?;
+            if index.primary {
+                write!(f, " PRIMARY")?;
+            } else if index.unique {
+                write!(f, " UNIQUE")?;
+            }
+            writeln!(f)?;
+        }
+        for foreign_key in &self.foreign_keys {
+            writeln!(f, "  * {}", foreign_key)?;
+        }
+        for check in &self.checks {
+            writeln!(f, "  * CHECK {}: {}", check.name, check.expression)?;
+        }
+        for trigger in &self.triggers {
+            writeln!(
+                f,
+                "  * TRIGGER {} {} {}: {}",
+                trigger.name,
+                trigger.timing,
+                trigger.events.join(" OR "),
+                trigger.action
+            )?;
+        }
+        if let Some(row_estimate) = self.row_estimate {
+            writeln!(f, "  ~{} rows", row_estimate)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ColumnSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  - {}: {} ", self.name, self.data_type)?;
+        if !self.nullable {
+            write!(f, "| NOT NULL")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, "| KEY: {}", key)?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, "| DEFAULT: {}", default)?;
+        }
+        if let Some(extra) = &self.extra {
+            write!(f, "| EXTRA: {}", extra)?;
+        }
+        if let Some(generated) = &self.generated {
+            write!(f, "| GENERATED: {}", generated)?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, "| COMMENT: {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ViewSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.materialized {
+            "Materialized view"
+        } else {
+            "View"
+        };
+        write!(f, "{}: {}", kind, self.name)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " -- {}", comment)?;
+        }
+        writeln!(f)?;
+        for column in &self.columns {
+            writeln!(f, "{}", column)?;
+        }
+        if let Some(definition) = &self.definition {
+            writeln!(f, "  AS {}", definition.trim())?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for RoutineSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.kind == "PROCEDURE" {
+            "Procedure"
+        } else {
+            "Function"
+        };
+        write!(f, "{}: {}({})", kind, self.name, self.arguments)?;
+        if let Some(returns) = &self.returns {
+            write!(f, " RETURNS {}", returns)?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, " -- {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ForeignKeySchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FOREIGN KEY {} ({}) REFERENCES {}.{} ({})",
+            self.name,
+            self.columns.join(", "),
+            self.referenced_schema,
+            self.referenced_table,
+            self.referenced_columns.join(", ")
+        )
+    }
+}
@@ -0,0 +1,70 @@
+//! Schemas built for tests.
+
+use super::{ColumnSchema, DatabaseSchema, ForeignKeySchema, TableSchema};
+
+/// A nullable column without a key.
+pub fn column(name: &str, data_type: &str) -> ColumnSchema {
+    ColumnSchema {
+        name: name.into(),
+        data_type: data_type.into(),
+        nullable: true,
+        key: None,
+        default: None,
+        extra: None,
+        generated: None,
+        comment: None,
+    }
+}
+
+/// A column that isn't nullable, with a key such as `PRI` or `MUL`.
+pub fn key_column(name: &str, data_type: &str, key: &str) -> ColumnSchema {
+    ColumnSchema {
+        nullable: false,
+        key: Some(key.into()),
+        ..column(name, data_type)
+    }
+}
+
+/// A foreign key of `table` from `column` to the `id` of `referenced`.
+pub fn foreign_key(table: &str, column: &str, referenced: &str) -> ForeignKeySchema {
+    ForeignKeySchema {
+        name: format!("{}_{}_fkey", table, referenced),
+        columns: vec![column.into()],
+        referenced_schema: "public".into(),
+        referenced_table: referenced.into(),
+        referenced_columns: vec!["id".into()],
+    }
+}
+
+/// A table in `public` with a foreign key from `<referenced>_id` to each of `references`.
+pub fn table(name: &str, columns: Vec<ColumnSchema>, references: &[&str]) -> TableSchema {
+    TableSchema {
+        schema: "public".into(),
+        name: name.into(),
+        comment: None,
+        columns,
+        indexes: Vec::new(),
+        foreign_keys: references
+            .iter()
+            .map(|referenced| foreign_key(name, &format!("{}_id", referenced), referenced))
+            .collect(),
+        checks: Vec::new(),
+        triggers: Vec::new(),
+        row_estimate: None,
+    }
+}
+
+/// Nullable columns without keys, all of one type.
+pub fn columns(names: &[&str], data_type: &str) -> Vec<ColumnSchema> {
+    names.iter().map(|name| column(name, data_type)).collect()
+}
+
+/// A database with tables only.
+pub fn database(name: &str, tables: Vec<TableSchema>) -> DatabaseSchema {
+    DatabaseSchema {
+        database: name.into(),
+        tables,
+        views: Vec::new(),
+        routines: Vec::new(),
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::schema::{DatabaseSchema, TableSchema};
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document length normalization.
+const B: f64 = 0.75;
+/// Table names count this many times, so a table named like the query beats one that only has such a column.
+const TABLE_NAME_WEIGHT: usize = 3;
+
+/// BM25 index over the tables of a schema.
+/// A table's document is its name, comment, columns with their comments,
+/// and the names of tables it's connected to by foreign keys.
+pub struct SchemaIndex<'a> {
+    schema: &'a DatabaseSchema,
+    documents: Vec<HashMap<String, usize>>,
+    lengths: Vec<usize>,
+    average_length: f64,
+    document_frequencies: HashMap<String, usize>,
+}
+
+impl<'a> SchemaIndex<'a> {
+    pub fn new(schema: &'a DatabaseSchema) -> Self {
+        let mut documents = Vec::with_capacity(schema.tables.len());
+        let mut lengths = Vec::with_capacity(schema.tables.len());
+        let mut document_frequencies: HashMap<String, usize> = HashMap::new();
+
+        for table in &schema.tables {
+            let mut terms = Vec::new();
+            let name_terms = tokenize(&table.name);
+            for _ in 0..TABLE_NAME_WEIGHT {
+                terms.extend(name_terms.iter().cloned());
+                terms.push(exact_name_term(&name_terms));
+            }
+            terms.extend(table.comment.iter().flat_map(|comment| tokenize(comment)));
+            for column in &table.columns {
+                terms.extend(tokenize(&column.name));
+                terms.extend(column.comment.iter().flat_map(|comment| tokenize(comment)));
+            }
+            for related in related_tables(schema, table) {
+                terms.extend(tokenize(&related.name));
+            }
+
+            let mut frequencies: HashMap<String, usize> = HashMap::new();
+            for term in &terms {
+                *frequencies.entry(term.clone()).or_default() += 1;
+            }
+            for term in frequencies.keys() {
+                *document_frequencies.entry(term.clone()).or_default() += 1;
+            }
+            lengths.push(terms.len());
+            documents.push(frequencies);
+        }
+
+        let average_length = if lengths.is_empty() {
+            0.0
+        } else {
+            lengths.iter().sum::<usize>() as f64 / lengths.len() as f64
+        };
+
+        Self {
+            schema,
+            documents,
+            lengths,
+            average_length,
+            document_frequencies,
+        }
+    }
+
+    /// Returns up to `limit` tables that match the query, best first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&'a TableSchema> {
+        // Whole query and single words can also match a table name exactly.
+        let query_terms = tokenize(query);
+        let mut terms: HashSet<String> = query_terms
+            .iter()
+            .map(|term| exact_name_term(std::slice::from_ref(term)))
+            .collect();
+        terms.insert(exact_name_term(&query_terms));
+        terms.extend(query_terms);
+        let document_count = self.documents.len() as f64;
+
+        let mut scores: Vec<(usize, f64)> = self
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(i, frequencies)| {
+                let length_ratio = self.lengths[i] as f64 / self.average_length.max(1.0);
+                let score = terms
+                    .iter()
+                    .filter_map(|term| {
+                        let frequency = *frequencies.get(term)? as f64;
+                        let document_frequency = self.document_frequencies[term] as f64;
+                        let idf = (1.0
+                            + (document_count - document_frequency + 0.5)
+                                / (document_frequency + 0.5))
+                            .ln();
+                        Some(
+                            idf * frequency * (K1 + 1.0)
+                                / (frequency + K1 * (1.0 - B + B * length_ratio)),
+                        )
+                    })
+                    .sum::<f64>();
+                (i, score)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        scores
+            .into_iter()
+            .take(limit)
+            .map(|(i, _)| &self.schema.tables[i])
+            .collect()
+    }
+}
+
+/// Tables connected to `table` by a foreign key, in either direction.
+pub fn related_tables<'a>(schema: &'a DatabaseSchema, table: &TableSchema) -> Vec<&'a TableSchema> {
+    schema
+        .tables
+        .iter()
+        .filter(|other| !(other.schema == table.schema && other.name == table.name))
+        .filter(|other| {
+            table.foreign_keys.iter().any(|foreign_key| {
+                foreign_key.referenced_schema == other.schema
+                    && foreign_key.referenced_table == other.name
+            }) || other.foreign_keys.iter().any(|foreign_key| {
+                foreign_key.referenced_schema == table.schema
+                    && foreign_key.referenced_table == table.name
+            })
+        })
+        .collect()
+}
+
+/// Term for a complete table name, which ranks "orders" above "order_items" for a query about orders.
+fn exact_name_term(terms: &[String]) -> String {
+    format!("={}", terms.join("_"))
+}
+
+/// Splits identifiers and text into lowercase terms, breaking `snake_case` and `camelCase`
+/// and folding simple plurals, so "OrderItems" matches "order_item".
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut previous_lowercase = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            push_term(&mut terms, &mut current);
+            previous_lowercase = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lowercase {
+            push_term(&mut terms, &mut current);
+        }
+        previous_lowercase = c.is_lowercase() || c.is_numeric();
+        current.extend(c.to_lowercase());
+    }
+    push_term(&mut terms, &mut current);
+    terms
+}
+
+fn push_term(terms: &mut Vec<String>, current: &mut String) {
+    if current.is_empty() {
+        return;
+    }
+    let term = std::mem::take(current);
+    let term = if let Some(stem) = term.strip_suffix("ies")
+        && stem.len() > 2
+    {
+        format!("{}y", stem)
+    } else if term.len() > 3 && term.ends_with('s') && !term.ends_with("ss") {
+        term[..term.len() - 1].to_string()
+    } else {
+        term
+    };
+    terms.push(term);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixture::{columns, database, table};
+
+    fn schema() -> DatabaseSchema {
+        database(
+            "shop",
+            vec![
+                table(
+                    "customers",
+                    columns(&["id", "email", "created_at"], "integer"),
+                    &[],
+                ),
+                table(
+                    "orders",
+                    columns(&["id", "customers_id", "total"], "integer"),
+                    &["customers"],
+                ),
+                table(
+                    "order_items",
+                    columns(&["id", "orders_id", "sku", "quantity"], "integer"),
+                    &["orders"],
+                ),
+                table("warehouses", columns(&["id", "city"], "integer"), &[]),
+            ],
+        )
+    }
+
+    fn names(tables: &[&TableSchema]) -> Vec<String> {
+        tables.iter().map(|table| table.name.clone()).collect()
+    }
+
+    #[test]
+    fn tokenizes_identifiers() {
+        assert_eq!(tokenize("OrderItems"), ["order", "item"]);
+        assert_eq!(
+            tokenize("order_items.created_at"),
+            ["order", "item", "created", "at"]
+        );
+        assert_eq!(tokenize("categories address"), ["category", "address"]);
+    }
+
+    #[test]
+    fn ranks_table_names_above_columns() {
+        let schema = schema();
+        let index = SchemaIndex::new(&schema);
+        assert_eq!(names(&index.search("orders", 2)), ["orders", "order_items"]);
+        assert_eq!(names(&index.search("order items", 1)), ["order_items"]);
+        assert_eq!(names(&index.search("email address", 5)), ["customers"]);
+        assert!(index.search("invoice", 5).is_empty());
+    }
+
+    #[test]
+    fn finds_related_tables_in_both_directions() {
+        let schema = schema();
+        assert_eq!(
+            names(&related_tables(&schema, &schema.tables[1])),
+            ["customers", "order_items"]
+        );
+        assert!(related_tables(&schema, &schema.tables[3]).is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+use rmcp::{
+    ServerHandler,
+    transport::{
+        SseServer, StreamableHttpService, sse_server::SseServerConfig,
+        streamable_http_server::session::local::LocalSessionManager,
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{AppError, AppResult};
+
+/// Path of the streamable HTTP endpoint.
+pub const STREAMABLE_HTTP_PATH: &str = "/mcp";
+/// Paths of the legacy SSE endpoints.
+pub const SSE_PATH: &str = "/sse";
+pub const SSE_POST_PATH: &str = "/message";
+
+/// How long open connections are given to finish after shutdown is requested.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpServerOptions {
+    /// Token clients must send as `Authorization: Bearer <token>` or `X-API-Key: <token>`.
+    pub auth_token: Option<String>,
+    pub tls: Option<TlsOptions>,
+    /// Serve without a token on an address other than loopback.
+    pub allow_unauthenticated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// Builds routes for both the streamable HTTP and the legacy SSE transport.
+/// Every session gets its own service from `service_factory`.
+pub fn router<S, F>(
+    service_factory: F,
+    auth_token: Option<String>,
+    ct: CancellationToken,
+    bind: SocketAddr,
+) -> Router
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + Clone + 'static,
+{
+    let (sse_server, sse_router) = SseServer::new(SseServerConfig {
+        bind,
+        sse_path: SSE_PATH.into(),
+        post_path: SSE_POST_PATH.into(),
+        ct,
+        sse_keep_alive: None,
+    });
+    sse_server.with_service(service_factory.clone());
+
+    let streamable_http_service = StreamableHttpService::new(
+        move || Ok(service_factory()),
+        LocalSessionManager::default().into(),
+        Default::default(),
+    );
+
+    let router = Router::new()
+        .nest_service(STREAMABLE_HTTP_PATH, streamable_http_service)
+        .merge(sse_router);
+
+    match auth_token {
+        Some(token) => router.layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            authorize,
+        )),
+        None => router,
+    }
+}
+
+/// Serves MCP over HTTP, or HTTPS if TLS is configured, until `shutdown` completes.
+/// Refuses to serve other hosts than this one without a token, unless explicitly allowed.
+pub async fn serve<S, F>(
+    listener: TcpListener,
+    options: HttpServerOptions,
+    service_factory: F,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> AppResult<()>
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + Clone + 'static,
+{
+    let addr = listener.local_addr()?;
+    if options.auth_token.is_none() && !options.allow_unauthenticated && !addr.ip().is_loopback() {
+        return Err(AppError::InvalidArgument(format!(
+            "refusing to serve {} without an auth token, set --auth-token or pass --allow-unauthenticated",
+            addr.ip()
+        )));
+    }
+
+    listener.set_nonblocking(true)?;
+    let ct = CancellationToken::new();
+    let app = router(service_factory, options.auth_token, ct.clone(), addr).into_make_service();
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        let ct = ct.clone();
+        async move {
+            shutdown.await;
+            ct.cancel();
+            handle.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+        }
+    });
+
+    if let Some(tls) = options.tls {
+        // Several crypto providers may be compiled in through dependencies, so pick one explicitly.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = RustlsConfig::from_pem_file(tls.cert_path, tls.key_path).await?;
+        axum_server::from_tcp_rustls(listener, config)
+            .handle(handle)
+            .serve(app)
+            .await?;
+    } else {
+        axum_server::from_tcp(listener)
+            .handle(handle)
+            .serve(app)
+            .await?;
+    }
+
+    ct.cancel();
+    Ok(())
+}
+
+async fn authorize(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    if is_authorized(request.headers(), &token) {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let api_key = headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok());
+
+    [bearer, api_key]
+        .into_iter()
+        .flatten()
+        .any(|candidate| constant_time_eq(candidate.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Compares secrets without short-circuiting on the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,441 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+};
+
+use sqlx::{mysql::MySqlDatabaseError, postgres::PgDatabaseError};
+
+use crate::schema::{DatabaseSchema, TableSchema};
+
+/// Number of failed statements the model may fix in one turn before it has to explain the errors instead.
+pub const MAX_ATTEMPTS: usize = 3;
+/// Number of closest identifiers suggested for an unknown one.
+const SUGGESTIONS: usize = 3;
+/// Characters of the statement shown from the position of a syntax error.
+const NEAR_CHARS: usize = 40;
+
+/// What went wrong with a statement, which tells the model how to fix it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorClass {
+    UnknownColumn,
+    UnknownTable,
+    SyntaxError,
+    AmbiguousColumn,
+    TypeMismatch,
+    PermissionDenied,
+    Timeout,
+    Other,
+}
+
+impl ErrorClass {
+    /// Classifies an error by its SQLSTATE on PostgreSQL or its error number on MySQL.
+    fn of(sqlstate: Option<&str>, mysql_number: Option<u16>) -> Self {
+        if let Some(number) = mysql_number {
+            return match number {
+                1054 => Self::UnknownColumn,
+                1146 | 1109 => Self::UnknownTable,
+                1064 | 1149 => Self::SyntaxError,
+                1052 => Self::AmbiguousColumn,
+                1267 | 1292 | 1366 | 1411 | 3140 => Self::TypeMismatch,
+                1044 | 1142 | 1143 | 1227 | 1792 => Self::PermissionDenied,
+                3024 => Self::Timeout,
+                _ => Self::Other,
+            };
+        }
+        match sqlstate.unwrap_or_default() {
+            "42703" => Self::UnknownColumn,
+            "42P01" => Self::UnknownTable,
+            "42601" => Self::SyntaxError,
+            "42702" => Self::AmbiguousColumn,
+            "42804" | "42883" | "42846" | "22P02" | "22007" | "22008" => Self::TypeMismatch,
+            "42501" | "25006" => Self::PermissionDenied,
+            "57014" => Self::Timeout,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::UnknownColumn => "unknown column",
+            Self::UnknownTable => "unknown table",
+            Self::SyntaxError => "syntax error",
+            Self::AmbiguousColumn => "ambiguous column",
+            Self::TypeMismatch => "type mismatch",
+            Self::PermissionDenied => "permission denied",
+            Self::Timeout => "timeout",
+            Self::Other => "error",
+        }
+    }
+
+    /// Whether rewriting the statement can fix the error.
+    pub fn is_fixable(self) -> bool {
+        !matches!(self, Self::PermissionDenied | Self::Other)
+    }
+}
+
+/// A failed statement, with hints on how to fix it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlError {
+    pub class: ErrorClass,
+    pub message: String,
+    /// Identifier the error is about, such as an unknown column, as the server quoted it.
+    pub name: Option<String>,
+    pub hints: Vec<String>,
+    pub statement: Option<String>,
+}
+
+impl SqlError {
+    /// Classifies an error of `statement`, keeping the server's own hint.
+    pub fn new(err: &sqlx::Error, statement: Option<&str>) -> Self {
+        let Some(database_error) = err.as_database_error() else {
+            let class = match err {
+                sqlx::Error::PoolTimedOut => ErrorClass::Timeout,
+                _ => ErrorClass::Other,
+            };
+            return Self {
+                class,
+                message: err.to_string(),
+                name: None,
+                hints: Vec::new(),
+                statement: statement.map(Into::into),
+            };
+        };
+
+        let message = database_error.message().to_string();
+        let mysql_number = database_error
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .map(|err| err.number());
+        let class = ErrorClass::of(database_error.code().as_deref(), mysql_number);
+        let mut error = Self {
+            class,
+            name: error_name(&message),
+            message,
+            hints: Vec::new(),
+            statement: statement.map(Into::into),
+        };
+        if let Some(err) = database_error.try_downcast_ref::<PgDatabaseError>() {
+            if let (Some(sqlx::postgres::PgErrorPosition::Original(position)), Some(statement)) =
+                (err.position(), statement)
+            {
+                let near: String = statement
+                    .trim()
+                    .chars()
+                    .skip(position.saturating_sub(1))
+                    .take(NEAR_CHARS)
+                    .collect();
+                if !near.is_empty() {
+                    error.hints.push(format!("The error is at: {}", near));
+                }
+            }
+            if let Some(hint) = err.hint() {
+                error.hints.push(hint.to_string());
+            }
+        }
+        error
+    }
+
+    /// Adds hints from the schema, such as the identifiers closest to an unknown one.
+    pub fn add_schema_hints(&mut self, schema: &DatabaseSchema, dialect: &str) {
+        let name = self
+            .name
+            .as_deref()
+            .and_then(|name| name.rsplit('.').next())
+            .unwrap_or_default();
+        // Columns are looked for in the tables the statement names first.
+        let words: HashSet<String> = self
+            .statement
+            .iter()
+            .flat_map(|statement| statement.split(|c: char| !(c.is_alphanumeric() || c == '_')))
+            .map(str::to_lowercase)
+            .collect();
+        let named: Vec<&TableSchema> = schema
+            .tables
+            .iter()
+            .filter(|table| words.contains(&table.name.to_lowercase()))
+            .collect();
+        let all: Vec<&TableSchema> = schema.tables.iter().collect();
+        let columns_of = |tables: &[&TableSchema]| -> Vec<(String, String)> {
+            tables
+                .iter()
+                .flat_map(|table| {
+                    let table_name = schema.qualified_name(table);
+                    table
+                        .columns
+                        .iter()
+                        .map(move |column| (column.name.clone(), table_name.clone()))
+                })
+                .collect()
+        };
+        match self.class {
+            ErrorClass::UnknownColumn => {
+                let mut found = closest(name, columns_of(&named).into_iter());
+                if found.is_empty() {
+                    found = closest(name, columns_of(&all).into_iter());
+                }
+                let closest: Vec<String> = found
+                    .into_iter()
+                    .map(|(column, table)| format!("{}.{}", table, column))
+                    .collect();
+                self.hints.push(if closest.is_empty() {
+                    "No column has a similar name, look up the columns of the tables with searchSchema.".into()
+                } else {
+                    format!("Closest columns: {}", closest.join(", "))
+                });
+            }
+            ErrorClass::UnknownTable => {
+                let relations = schema
+                    .tables
+                    .iter()
+                    .map(|table| (table.name.clone(), schema.qualified_name(table)))
+                    .chain(
+                        schema
+                            .views
+                            .iter()
+                            .map(|view| (view.name.clone(), view.name.clone())),
+                    );
+                let closest: Vec<String> = closest(name, relations)
+                    .into_iter()
+                    .map(|(_, qualified)| qualified)
+                    .collect();
+                self.hints.push(if closest.is_empty() {
+                    "No table has a similar name, find the right one with searchSchema.".into()
+                } else {
+                    format!("Closest tables: {}", closest.join(", "))
+                });
+            }
+            ErrorClass::AmbiguousColumn => {
+                let tables: Vec<String> = if named.is_empty() { &all } else { &named }
+                    .iter()
+                    .filter(|table| {
+                        table
+                            .columns
+                            .iter()
+                            .any(|column| column.name.eq_ignore_ascii_case(name))
+                    })
+                    .map(|table| schema.qualified_name(table))
+                    .collect();
+                let owners = if tables.is_empty() {
+                    String::new()
+                } else {
+                    format!(" It's a column of {}.", tables.join(", "))
+                };
+                self.hints.push(format!(
+                    "Qualify `{}` with the table name or alias it should come from.{}",
+                    name, owners
+                ));
+            }
+            ErrorClass::SyntaxError => self.hints.push(format!(
+                "Check the statement against {} syntax, such as quoting, commas and keyword order.",
+                dialect
+            )),
+            ErrorClass::TypeMismatch => self.hints.push(
+                "Compare and insert values of the column's type: cast explicitly, or quote or unquote literals."
+                    .into(),
+            ),
+            ErrorClass::PermissionDenied => self.hints.push(
+                "The connection isn't allowed to do this, as it's read-only or lacks the privilege. Rewriting the statement won't help."
+                    .into(),
+            ),
+            ErrorClass::Timeout => self.hints.push(
+                "The statement took too long. Filter on indexed columns, aggregate less or add a LIMIT rather than running it again."
+                    .into(),
+            ),
+            ErrorClass::Other => {}
+        }
+    }
+}
+
+impl Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.class.label(), self.message)?;
+        for hint in &self.hints {
+            write!(f, "\n- {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// First quoted name in an error message, or the word after "column" as in PostgreSQL's `column e.nme does not exist`.
+fn error_name(message: &str) -> Option<String> {
+    if let Some(start) = message.find(['"', '\'']) {
+        let quote = message[start..].chars().next()?;
+        let rest = &message[start + 1..];
+        return rest.find(quote).map(|end| rest[..end].to_string());
+    }
+    message
+        .strip_prefix("column ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(Into::into)
+}
+
+/// Levenshtein distance between two names, ignoring case.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Candidates whose name is closest to `name`, closest first, leaving out those clearly further than the best ones.
+/// Each candidate is a name and what to show for it.
+fn closest<T>(name: &str, candidates: impl Iterator<Item = (String, T)>) -> Vec<(String, T)> {
+    let lower = name.to_lowercase();
+    let mut scored: Vec<(usize, String, T)> = candidates
+        .filter_map(|(candidate, shown)| {
+            let candidate_lower = candidate.to_lowercase();
+            let distance = distance(&lower, &candidate_lower);
+            let similar = distance <= (lower.chars().count() / 2).max(2)
+                || (lower.len() >= 3
+                    && (candidate_lower.contains(&lower) || lower.contains(&candidate_lower)));
+            similar.then_some((distance, candidate, shown))
+        })
+        .collect();
+    scored.sort_by_key(|(distance, _, _)| *distance);
+    let best = scored.first().map_or(0, |(distance, _, _)| *distance);
+    scored
+        .into_iter()
+        .take_while(|(distance, _, _)| *distance <= best + 1)
+        .take(SUGGESTIONS)
+        .map(|(_, candidate, shown)| (candidate, shown))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{
+        ColumnSchema,
+        fixture::{column, database, table},
+    };
+
+    fn schema() -> DatabaseSchema {
+        let table = |name: &str, names: &[&str]| {
+            let columns = names
+                .iter()
+                .map(|name| ColumnSchema {
+                    nullable: false,
+                    ..column(name, "integer")
+                })
+                .collect();
+            table(name, columns, &[])
+        };
+        database(
+            "employees",
+            vec![
+                table(
+                    "employees",
+                    &["emp_no", "first_name", "last_name", "hire_date"],
+                ),
+                table("salaries", &["emp_no", "salary", "from_date"]),
+            ],
+        )
+    }
+
+    fn error(class: ErrorClass, message: &str) -> SqlError {
+        SqlError {
+            class,
+            name: error_name(message),
+            message: message.into(),
+            hints: Vec::new(),
+            statement: None,
+        }
+    }
+
+    #[test]
+    fn classifies_by_sqlstate_and_error_number() {
+        assert_eq!(
+            ErrorClass::of(Some("42703"), None),
+            ErrorClass::UnknownColumn
+        );
+        assert_eq!(
+            ErrorClass::of(Some("22P02"), None),
+            ErrorClass::TypeMismatch
+        );
+        // MySQL shares SQLSTATE 42000 between syntax errors and missing privileges.
+        assert_eq!(
+            ErrorClass::of(Some("42000"), Some(1064)),
+            ErrorClass::SyntaxError
+        );
+        assert_eq!(
+            ErrorClass::of(Some("42000"), Some(1142)),
+            ErrorClass::PermissionDenied
+        );
+        assert_eq!(
+            ErrorClass::of(Some("HY000"), Some(3024)),
+            ErrorClass::Timeout
+        );
+    }
+
+    #[test]
+    fn finds_the_name_in_messages() {
+        assert_eq!(
+            error_name("Unknown column 'e.frist_name' in 'field list'").as_deref(),
+            Some("e.frist_name")
+        );
+        assert_eq!(
+            error_name("column e.frist_name does not exist").as_deref(),
+            Some("e.frist_name")
+        );
+        assert_eq!(
+            error_name("relation \"salary\" does not exist").as_deref(),
+            Some("salary")
+        );
+    }
+
+    #[test]
+    fn looks_for_columns_in_the_named_tables_first() {
+        let mut column = error(
+            ErrorClass::UnknownColumn,
+            "Unknown column 'date' in 'where clause'",
+        );
+        column.statement = Some("SELECT * FROM salaries WHERE date > '2000-01-01'".into());
+        column.add_schema_hints(&schema(), "MySQL");
+        assert_eq!(column.hints, vec!["Closest columns: salaries.from_date"]);
+
+        column.statement = Some("SELECT * FROM employees WHERE salary > 1000".into());
+        column.name = Some("salary".into());
+        column.hints.clear();
+        column.add_schema_hints(&schema(), "MySQL");
+        assert_eq!(column.hints, vec!["Closest columns: salaries.salary"]);
+    }
+
+    #[test]
+    fn suggests_closest_identifiers() {
+        let mut column = error(
+            ErrorClass::UnknownColumn,
+            "column e.frist_name does not exist",
+        );
+        column.add_schema_hints(&schema(), "PostgreSQL");
+        assert_eq!(
+            column.hints,
+            vec!["Closest columns: employees.first_name, employees.last_name"]
+        );
+
+        let mut table = error(
+            ErrorClass::UnknownTable,
+            "Table 'employees.salary' doesn't exist",
+        );
+        table.add_schema_hints(&schema(), "MySQL");
+        assert_eq!(table.hints, vec!["Closest tables: salaries"]);
+
+        let mut ambiguous = error(
+            ErrorClass::AmbiguousColumn,
+            "column reference \"emp_no\" is ambiguous",
+        );
+        ambiguous.statement = Some("SELECT emp_no FROM employees, salaries".into());
+        ambiguous.add_schema_hints(&schema(), "PostgreSQL");
+        assert_eq!(
+            ambiguous.to_string(),
+            "ambiguous column: column reference \"emp_no\" is ambiguous\n\
+             - Qualify `emp_no` with the table name or alias it should come from. It's a column of employees, salaries."
+        );
+    }
+}
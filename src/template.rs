@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+/// Replaces `{{name}}` placeholders with their values.
+/// Placeholders without a value are left as they are, so literal braces in prompts survive.
+pub fn render(template: &str, variables: &HashMap<&str, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let placeholder = &rest[start..start + end + 2];
+        let name = placeholder[2..placeholder.len() - 2].trim();
+        match variables.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(placeholder),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_known_variables() {
+        let variables = HashMap::from([
+            ("dialect", "MySQL".to_string()),
+            ("row_limit", "100".into()),
+        ]);
+        assert_eq!(
+            render(
+                "Write {{dialect}} with at most {{ row_limit }} rows.",
+                &variables
+            ),
+            "Write MySQL with at most 100 rows."
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_and_unclosed_placeholders() {
+        let variables = HashMap::from([("dialect", "MySQL".to_string())]);
+        assert_eq!(
+            render("{{unknown}} {{dialect}} {{dialect", &variables),
+            "{{unknown}} MySQL {{dialect"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use rmcp::{
+    RoleClient, ServiceExt,
+    model::{CallToolRequestParam, CallToolResult, Tool as McpTool},
+    service::RunningService,
+    transport::{SseClientTransport, TokioChildProcess},
+};
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::error::AppResult;
+
+/// External MCP servers configuration.
+/// Uses the same `mcpServers` layout as other MCP clients, so existing config files can be reused.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServersConfig {
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum McpServerConfig {
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+    Sse {
+        url: String,
+    },
+}
+
+impl McpServersConfig {
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+struct ExternalServer {
+    name: String,
+    service: RunningService<RoleClient, ()>,
+}
+
+struct ExternalTool {
+    server_index: usize,
+    name: String,
+    tool: McpTool,
+}
+
+/// Name a tool is exposed as, prefixed with its server if it would clash with the database tools or other servers,
+/// and numbered if the prefixed name clashes too.
+fn exposed_name(server: &str, tool: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(tool) {
+        return tool.to_string();
+    }
+    let prefixed = format!("{}_{}", server, tool);
+    if !taken(&prefixed) {
+        return prefixed;
+    }
+    (2..)
+        .map(|number| format!("{}_{}", prefixed, number))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or(prefixed)
+}
+
+/// Connections to external MCP servers, whose tools are exposed next to the database tools.
+#[derive(Default)]
+pub struct ExternalServers {
+    servers: Vec<ExternalServer>,
+    tools: Vec<ExternalTool>,
+}
+
+impl ExternalServers {
+    pub async fn connect(config: McpServersConfig, reserved_names: &[&str]) -> AppResult<Self> {
+        let mut external = Self::default();
+
+        for (name, server_config) in config.mcp_servers {
+            let service = match server_config {
+                McpServerConfig::Stdio { command, args, env } => {
+                    let mut command = Command::new(command);
+                    command.args(args).envs(env);
+                    ().serve(TokioChildProcess::new(command)?).await?
+                }
+                McpServerConfig::Sse { url } => {
+                    ().serve(SseClientTransport::start(url).await?).await?
+                }
+            };
+
+            let server_index = external.servers.len();
+            for tool in service.list_all_tools().await? {
+                let exposed_name = exposed_name(&name, &tool.name, |candidate| {
+                    reserved_names.contains(&candidate) || external.find_tool(candidate).is_some()
+                });
+                external.tools.push(ExternalTool {
+                    server_index,
+                    name: tool.name.to_string(),
+                    tool: McpTool {
+                        name: exposed_name.into(),
+                        ..tool
+                    },
+                });
+            }
+            external.servers.push(ExternalServer { name, service });
+        }
+
+        Ok(external)
+    }
+
+    /// Tools of all connected servers, under the names they are exposed as.
+    pub fn tools(&self) -> impl Iterator<Item = &McpTool> {
+        self.tools.iter().map(|external_tool| &external_tool.tool)
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.find_tool(name).is_some()
+    }
+
+    pub async fn call_tool(
+        &self,
+        name: &str,
+        params_json: Option<serde_json::Value>,
+    ) -> AppResult<CallToolResult> {
+        let Some(external_tool) = self.find_tool(name) else {
+            return Ok(CallToolResult::error(vec![rmcp::model::Content::text(
+                format!("Unknown tool: {}", name),
+            )]));
+        };
+        let server = &self.servers[external_tool.server_index];
+
+        Ok(server
+            .service
+            .call_tool(CallToolRequestParam {
+                name: external_tool.name.clone().into(),
+                arguments: params_json.and_then(|params| params.as_object().cloned()),
+            })
+            .await?)
+    }
+
+    /// Names of the connected servers.
+    pub fn server_names(&self) -> impl Iterator<Item = &str> {
+        self.servers.iter().map(|server| server.name.as_str())
+    }
+
+    fn find_tool(&self, name: &str) -> Option<&ExternalTool> {
+        self.tools
+            .iter()
+            .find(|external_tool| external_tool.tool.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_prefixed_names_that_clash_too() {
+        let taken = ["query", "docs_query", "docs_query_2"];
+        let taken = |name: &str| taken.contains(&name);
+        assert_eq!(exposed_name("docs", "search", taken), "search");
+        assert_eq!(exposed_name("docs", "query", taken), "docs_query_3");
+        assert_eq!(exposed_name("web", "query", taken), "web_query");
+    }
+}
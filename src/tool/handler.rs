@@ -0,0 +1,1384 @@
+use std::{sync::Arc, time::Instant};
+
+use rmcp::{
+    ErrorData as McpError, RoleServer, ServerHandler,
+    handler::server::{
+        common::cached_schema_for_type, router::tool::ToolRouter, wrapper::Parameters,
+    },
+    model::{
+        CallToolResult, Content, GetPromptRequestParam, GetPromptResult, Implementation,
+        ListPromptsResult, ListResourcesResult, PaginatedRequestParam, ProtocolVersion,
+        ReadResourceRequestParam, ReadResourceResult, ServerCapabilities, ServerInfo,
+        SetLevelRequestParam, SubscribeRequestParam, Tool as McpTool, UnsubscribeRequestParam,
+    },
+    schemars::{self, JsonSchema},
+    service::{NotificationContext, RequestContext},
+    tool, tool_handler, tool_router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{DatabaseInfo, Databases},
+    diagram::{self, DiagramFormat},
+    dml::{self, DmlKind, RowChange},
+    error::{AppError, AppResult},
+    graph::{Join, RelationGraph},
+    index_advice::{self, IndexAdvice},
+    profile::{self, ColumnProfile, TableProfile},
+    schema::{DatabaseSchema, TableSchema},
+    search::{self, SchemaIndex},
+    tool::{
+        Row,
+        logging::{self, LogLevel},
+        prompt,
+        resource::{self, ResourceSubscriptions},
+        write::{self, OpenTransaction},
+    },
+    value_search::{self, RowMatch, TableSearch},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct DatabaseParams {
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExecuteQueryParams {
+    #[schemars(description = "The SQL SELECT query to execute.")]
+    query: String,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SearchSchemaParams {
+    #[schemars(description = "Words describing the data, such as \"customer orders by city\".")]
+    query: String,
+    #[schemars(description = "Max number of matching tables. Defaults to 5.")]
+    #[serde(default)]
+    limit: Option<usize>,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct FindJoinPathParams {
+    #[schemars(description = "Table to start from, optionally qualified with its schema.")]
+    from_table: String,
+    #[schemars(description = "Table to reach, optionally qualified with its schema.")]
+    to_table: String,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RenderDiagramParams {
+    #[schemars(description = "Output format: mermaid (default) or dot.")]
+    #[serde(default)]
+    format: DiagramFormat,
+    #[schemars(description = "Tables to draw. Draws every table if omitted.")]
+    #[serde(default)]
+    tables: Vec<String>,
+    #[schemars(
+        description = "Also draw tables up to this many foreign keys away from the given tables. Defaults to 1."
+    )]
+    #[serde(default)]
+    hops: Option<usize>,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ProfileColumnParams {
+    #[schemars(description = "Table of the column, optionally qualified with its schema.")]
+    table: String,
+    #[schemars(description = "Column to profile.")]
+    column: String,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ProfileTableParams {
+    #[schemars(description = "Table to profile, optionally qualified with its schema.")]
+    table: String,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SampleRowsParams {
+    #[schemars(description = "Table to sample, optionally qualified with its schema.")]
+    table: String,
+    #[schemars(description = "Number of rows. Defaults to 10, at most 100.")]
+    #[serde(default)]
+    n: Option<usize>,
+    #[schemars(description = "Columns to include. Includes every column if omitted.")]
+    #[serde(default)]
+    columns: Vec<String>,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SearchValueParams {
+    #[schemars(
+        description = "Value to look for, such as a name or an email address. Rows have to contain every word of it."
+    )]
+    text: String,
+    #[schemars(description = "Tables to search. Searches every table if omitted.")]
+    #[serde(default)]
+    tables: Vec<String>,
+    #[schemars(description = "Max number of matching rows. Defaults to 20.")]
+    #[serde(default)]
+    limit: Option<usize>,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AdviseIndexesParams {
+    #[schemars(
+        description = "Queries to explain. Explains the slowest queries recorded by the server if omitted."
+    )]
+    #[serde(default)]
+    queries: Vec<String>,
+    #[schemars(description = "Number of recorded queries to explain. Defaults to 10, at most 50.")]
+    #[serde(default)]
+    limit: Option<usize>,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExecuteWriteParams {
+    #[schemars(description = "A single INSERT, UPDATE or DELETE statement.")]
+    statement: String,
+    #[schemars(description = "Name of the database. Defaults to the first one.")]
+    #[serde(default)]
+    database: Option<String>,
+}
+
+/// Structured result of a query.
+#[derive(Serialize, JsonSchema)]
+pub struct QueryOutput {
+    pub rows: Vec<Row>,
+    pub row_count: usize,
+    /// Whether rows beyond the row limit were left out.
+    pub truncated: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SchemaSearchOutput {
+    /// Best matching tables first.
+    pub tables: Vec<TableSchema>,
+    /// Tables connected to the matching tables by foreign keys.
+    pub related_tables: Vec<TableSchema>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct JoinPathOutput {
+    pub from_table: String,
+    /// Joins in order, each to a table connected by a foreign key to one before it.
+    pub joins: Vec<Join>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SampleRowsOutput {
+    pub table: String,
+    /// Rows picked at random, with long values shortened and binary values summarized.
+    pub rows: Vec<Row>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ValueSearchOutput {
+    pub matches: Vec<TableMatches>,
+    /// Tables that could not be searched, such as large tables without a text index.
+    pub skipped_tables: Vec<SkippedTable>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct TableMatches {
+    pub table: String,
+    pub rows: Vec<RowMatch>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SkippedTable {
+    pub table: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct WriteOutput {
+    pub rows_affected: u64,
+    /// Changed rows with their values before and after the statement, only the changed columns of updated rows.
+    pub changes: Vec<RowChange>,
+    /// Whether some affected rows are not listed, because there were too many or they couldn't be read.
+    pub partial: bool,
+    /// Number of statements run in the open transaction, this one included.
+    pub statements: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct DatabaseList {
+    pub databases: Vec<DatabaseInfo>,
+}
+
+/// Default number of foreign keys followed from the tables of a diagram.
+const DIAGRAM_HOPS: usize = 1;
+
+/// Default and max number of rows returned by `sampleRows`.
+const SAMPLE_ROWS: usize = 10;
+const MAX_SAMPLE_ROWS: usize = 100;
+
+/// Default and max number of rows returned by `searchValue`.
+const VALUE_SEARCH_LIMIT: usize = 20;
+const MAX_VALUE_SEARCH_LIMIT: usize = 100;
+
+/// Max number of changed rows shown for a data-modifying statement.
+const CHANGED_ROWS: usize = 20;
+
+/// Tools that change data, offered only in write mode.
+const WRITE_TOOLS: [&str; 3] = ["executeWrite", "commitTransaction", "rollbackTransaction"];
+
+/// Default and max number of tables returned by a schema search.
+const SEARCH_LIMIT: usize = 5;
+const MAX_SEARCH_LIMIT: usize = 20;
+
+/// Returns the result as text for the model, and as structured content for MCP clients.
+fn structured_result(text: String, structured: impl Serialize) -> AppResult<CallToolResult> {
+    Ok(CallToolResult {
+        structured_content: Some(serde_json::to_value(structured)?),
+        ..CallToolResult::success(vec![Content::text(text)])
+    })
+}
+
+/// Lists tools with their arguments for the system prompt, required arguments first.
+fn describe_tools(tools: &[McpTool]) -> String {
+    tools
+        .iter()
+        .map(|tool| {
+            let required: Vec<&str> = tool
+                .input_schema
+                .get("required")
+                .and_then(|required| required.as_array())
+                .map(|required| required.iter().filter_map(|name| name.as_str()).collect())
+                .unwrap_or_default();
+            let mut arguments: Vec<(bool, String)> = tool
+                .input_schema
+                .get("properties")
+                .and_then(|properties| properties.as_object())
+                .into_iter()
+                .flatten()
+                .map(|(name, property)| {
+                    let data_type = property
+                        .get("type")
+                        .and_then(|data_type| data_type.as_str())
+                        .unwrap_or("value");
+                    let optional = !required.contains(&name.as_str());
+                    if optional {
+                        (optional, format!("`{}` ({}, optional)", name, data_type))
+                    } else {
+                        (optional, format!("`{}` ({})", name, data_type))
+                    }
+                })
+                .collect();
+            arguments.sort_by_key(|(optional, _)| *optional);
+
+            let description = tool.description.as_deref().unwrap_or_default();
+            if arguments.is_empty() {
+                format!("- `{}`: {} Takes no arguments.", tool.name, description)
+            } else {
+                let arguments: Vec<_> = arguments
+                    .into_iter()
+                    .map(|(_, argument)| argument)
+                    .collect();
+                format!(
+                    "- `{}`: {} Takes {}.",
+                    tool.name,
+                    description,
+                    arguments.join(", ")
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Serves tools, resources and prompts of all connected databases.
+/// The MCP server uses one handler per session, and the chat REPL calls its tools directly.
+#[derive(Clone)]
+pub struct DatabaseHandler {
+    databases: Arc<Databases>,
+    system_prompt: Arc<str>,
+    resource_subscriptions: ResourceSubscriptions,
+    log_level: LogLevel,
+    open_transaction: OpenTransaction,
+    tool_router: ToolRouter<Self>,
+}
+
+impl DatabaseHandler {
+    /// Renders the system prompt for the tools before their descriptions name the connected servers.
+    pub async fn new(databases: Arc<Databases>) -> AppResult<Self> {
+        Self::with_tools(databases, false).await
+    }
+
+    /// Creates the handler for the chat REPL, where the user confirms changes rather than the model committing them.
+    pub async fn for_chat(databases: Arc<Databases>) -> AppResult<Self> {
+        Self::with_tools(databases, true).await
+    }
+
+    async fn with_tools(databases: Arc<Databases>, user_commits: bool) -> AppResult<Self> {
+        let mut tool_router = Self::tool_router();
+        if !databases.is_multiple() {
+            tool_router.remove_route("listDatabases");
+        }
+        if !databases.write() {
+            for name in WRITE_TOOLS {
+                tool_router.remove_route(name);
+            }
+        } else {
+            if user_commits {
+                tool_router.remove_route("commitTransaction");
+            }
+            if let Some(route) = tool_router.map.get_mut("executeWrite") {
+                let description = route.attr.description.as_deref().unwrap_or_default();
+                let committer = if user_commits {
+                    "the user confirms the changes"
+                } else {
+                    "commitTransaction is called"
+                };
+                route.attr.description = Some(
+                    format!(
+                        "{} Nothing is committed until {}, and the transaction is rolled back if it isn't committed within {} minutes.",
+                        description,
+                        committer,
+                        write::COMMIT_TIMEOUT.as_secs() / 60
+                    )
+                    .into(),
+                );
+            }
+        }
+        let mut tools = tool_router.list_all();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        let system_prompt = databases.system_prompt(&describe_tools(&tools)).await?;
+        // Only read queries are run, in read-only transactions, so the query tool is safe to call.
+        if (databases.read_only() || databases.write())
+            && let Some(route) = tool_router.map.get_mut("executeQuery")
+            && let Some(annotations) = route.attr.annotations.as_mut()
+        {
+            annotations.read_only_hint = Some(true);
+            annotations.destructive_hint = Some(false);
+        }
+
+        // Mention the connected servers, so the model writes SQL for the right dialect and version.
+        let connected = databases.describe();
+        for route in tool_router.map.values_mut() {
+            let description = route.attr.description.as_deref().unwrap_or_default();
+            route.attr.description =
+                Some(format!("{} Connected to {}.", description, connected).into());
+        }
+
+        Ok(Self {
+            databases,
+            system_prompt: system_prompt.into(),
+            resource_subscriptions: Default::default(),
+            log_level: Default::default(),
+            open_transaction: Default::default(),
+            tool_router,
+        })
+    }
+
+    /// Creates a copy of the handler for a new MCP session, with its own resource subscriptions, log level and transaction.
+    pub fn session(&self) -> Self {
+        Self {
+            resource_subscriptions: Default::default(),
+            log_level: Default::default(),
+            open_transaction: Default::default(),
+            ..self.clone()
+        }
+    }
+
+    pub fn databases(&self) -> &Databases {
+        &self.databases
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    pub fn tools(&self) -> Vec<McpTool> {
+        let mut tools = self.tool_router.list_all();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tool_router.has_route(name)
+    }
+
+    /// Whether changes wait to be committed or rolled back.
+    pub async fn has_open_transaction(&self) -> bool {
+        self.open_transaction.lock().await.is_some()
+    }
+
+    /// Calls a tool outside of an MCP session, as the chat REPL does.
+    pub async fn call(
+        &self,
+        name: &str,
+        arguments: Option<serde_json::Value>,
+    ) -> AppResult<CallToolResult> {
+        let arguments = arguments.unwrap_or_else(|| serde_json::json!({}));
+        match name {
+            "listDatabases" => self.database_list(),
+            "getDatabaseSchema" => {
+                self.database_schema(serde_json::from_value(arguments)?)
+                    .await
+            }
+            "searchSchema" => self.search_schema(serde_json::from_value(arguments)?).await,
+            "searchValue" => self.value_search(serde_json::from_value(arguments)?).await,
+            "findJoinPath" => self.join_path(serde_json::from_value(arguments)?).await,
+            "renderDiagram" => self.diagram(serde_json::from_value(arguments)?).await,
+            "profileColumn" => {
+                self.column_profile(serde_json::from_value(arguments)?)
+                    .await
+            }
+            "profileTable" => self.table_profile(serde_json::from_value(arguments)?).await,
+            "sampleRows" => self.row_sample(serde_json::from_value(arguments)?).await,
+            "adviseIndexes" => self.index_advice(serde_json::from_value(arguments)?).await,
+            "refreshSchema" => self.reload_schema(serde_json::from_value(arguments)?).await,
+            "executeQuery" => {
+                self.run_query(serde_json::from_value(arguments)?, None)
+                    .await
+            }
+            "executeWrite" => {
+                self.run_write(serde_json::from_value(arguments)?, None)
+                    .await
+            }
+            "commitTransaction" => self.finish_transaction(true).await,
+            "rollbackTransaction" => self.finish_transaction(false).await,
+            _ => Ok(CallToolResult::error(vec![Content::text(format!(
+                "Unknown tool: {}",
+                name
+            ))])),
+        }
+    }
+
+    fn database_list(&self) -> AppResult<CallToolResult> {
+        let list = DatabaseList {
+            databases: self.databases.infos(),
+        };
+        structured_result(serde_json::to_string_pretty(&list)?, list)
+    }
+
+    async fn database_schema(&self, params: DatabaseParams) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        let schema = database.manager.get_schema().await?;
+        structured_result(schema.to_string(), schema)
+    }
+
+    async fn reload_schema(&self, params: DatabaseParams) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        let schema = database.manager.refresh_schema().await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Reloaded the schema of `{}`: {} tables.",
+            database.name,
+            schema.tables.len()
+        ))]))
+    }
+
+    async fn search_schema(&self, params: SearchSchemaParams) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        let schema = database.manager.get_schema().await?;
+        let limit = params
+            .limit
+            .unwrap_or(SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+
+        let tables = SchemaIndex::new(&schema).search(&params.query, limit);
+        let mut related_tables: Vec<&TableSchema> = Vec::new();
+        for table in &tables {
+            for related in search::related_tables(&schema, table) {
+                if related_tables.len() < limit
+                    && !tables.iter().any(|table| std::ptr::eq(*table, related))
+                    && !related_tables
+                        .iter()
+                        .any(|table| std::ptr::eq(*table, related))
+                {
+                    related_tables.push(related);
+                }
+            }
+        }
+
+        let text = if tables.is_empty() {
+            format!(
+                "No tables match '{}'. Try other words, or list the tables with getDatabaseSchema.",
+                params.query
+            )
+        } else {
+            let mut text = String::from("Matching tables:\n\n");
+            for table in &tables {
+                text += &format!("{}\n", table);
+            }
+            if !related_tables.is_empty() {
+                text += "Related tables (foreign keys):\n\n";
+                for table in &related_tables {
+                    text += &format!("{}\n", table);
+                }
+            }
+            text
+        };
+        structured_result(
+            text,
+            SchemaSearchOutput {
+                tables: tables.into_iter().cloned().collect(),
+                related_tables: related_tables.into_iter().cloned().collect(),
+            },
+        )
+    }
+
+    async fn value_search(&self, params: SearchValueParams) -> AppResult<CallToolResult> {
+        let words = value_search::words(&params.text);
+        if words.is_empty() {
+            return Ok(CallToolResult::error(vec![Content::text(
+                "Nothing to search for.",
+            )]));
+        }
+        let database = self.databases.get(params.database.as_deref())?;
+        let schema = database.manager.get_schema().await?;
+        let tables: Vec<&TableSchema> = if params.tables.is_empty() {
+            schema.tables.iter().collect()
+        } else {
+            let mut tables = Vec::with_capacity(params.tables.len());
+            for name in &params.tables {
+                match schema.resolve_table(name) {
+                    Some(table) => tables.push(table),
+                    None => {
+                        return Ok(CallToolResult::error(vec![Content::text(format!(
+                            "Unknown table: {}",
+                            name
+                        ))]));
+                    }
+                }
+            }
+            tables
+        };
+
+        let deadline = Instant::now() + value_search::SEARCH_TIME;
+        let mut remaining = params
+            .limit
+            .unwrap_or(VALUE_SEARCH_LIMIT)
+            .clamp(1, MAX_VALUE_SEARCH_LIMIT);
+        let mut output = ValueSearchOutput {
+            matches: Vec::new(),
+            skipped_tables: Vec::new(),
+        };
+        for table in tables {
+            if remaining == 0 {
+                break;
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let search = if timeout.is_zero() {
+                TableSearch::TimedOut
+            } else {
+                database
+                    .manager
+                    .search_value(table, &words, remaining, timeout)
+                    .await?
+            };
+            let reason = match search {
+                TableSearch::Searched(rows) => {
+                    if !rows.is_empty() {
+                        remaining -= rows.len();
+                        output.matches.push(TableMatches {
+                            table: schema.qualified_name(table),
+                            rows,
+                        });
+                    }
+                    continue;
+                }
+                TableSearch::NoTextColumns => continue,
+                TableSearch::TooLarge => "too large to scan, and no text index",
+                TableSearch::TimedOut => "out of time",
+            };
+            output.skipped_tables.push(SkippedTable {
+                table: schema.qualified_name(table),
+                reason: reason.into(),
+            });
+        }
+
+        let mut text = if output.matches.is_empty() {
+            format!("No rows contain '{}'.\n", params.text)
+        } else {
+            let mut text = format!("Rows containing '{}':\n", params.text);
+            for table in &output.matches {
+                text += &format!("{}\n", table.table);
+                let mut keyless = 0;
+                for row in &table.rows {
+                    if row.key.is_empty() {
+                        keyless += 1;
+                        continue;
+                    }
+                    let key: Vec<String> = row
+                        .key
+                        .iter()
+                        .map(|(column, value)| format!("{} = {}", column, value))
+                        .collect();
+                    text += &format!("  {} in {}\n", key.join(", "), row.columns.join(", "));
+                }
+                if keyless > 0 {
+                    text += &format!("  {} rows without a primary key\n", keyless);
+                }
+            }
+            text
+        };
+        if !output.skipped_tables.is_empty() {
+            let skipped: Vec<String> = output
+                .skipped_tables
+                .iter()
+                .map(|table| format!("{} ({})", table.table, table.reason))
+                .collect();
+            text += &format!("Not searched: {}\n", skipped.join(", "));
+        }
+        structured_result(text, output)
+    }
+
+    async fn join_path(&self, params: FindJoinPathParams) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        let schema = database.manager.get_schema().await?;
+        let mut tables = Vec::new();
+        for name in [&params.from_table, &params.to_table] {
+            match schema.resolve_table(name) {
+                Some(table) => tables.push(table),
+                None => {
+                    return Ok(CallToolResult::error(vec![Content::text(format!(
+                        "Unknown table: {}",
+                        name
+                    ))]));
+                }
+            }
+        }
+
+        let from_table = schema.qualified_name(tables[0]);
+        let Some(joins) = RelationGraph::new(&schema).join_path(tables[0], tables[1]) else {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "No chain of foreign keys connects {} and {}.",
+                from_table,
+                schema.qualified_name(tables[1])
+            ))]));
+        };
+
+        let mut text = format!("FROM {}", from_table);
+        for join in &joins {
+            text += &format!("\nJOIN {} ON {}", join.table, join.on);
+        }
+        structured_result(text, JoinPathOutput { from_table, joins })
+    }
+
+    async fn diagram(&self, params: RenderDiagramParams) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        let schema = database.manager.get_schema().await?;
+        let diagram = diagram::render(
+            &schema,
+            &params.tables,
+            params.hops.unwrap_or(DIAGRAM_HOPS),
+            params.format,
+        )?;
+        Ok(CallToolResult::success(vec![Content::text(diagram)]))
+    }
+
+    /// Looks up a table in detail, with its row estimate.
+    /// Returns its qualified name along with it.
+    async fn table_details(
+        &self,
+        database: Option<&str>,
+        name: &str,
+    ) -> AppResult<Option<(String, TableSchema)>> {
+        let manager = &self.databases.get(database)?.manager;
+        let schema = manager.get_schema().await?;
+        let Some(table) = schema.resolve_table(name) else {
+            return Ok(None);
+        };
+        let name = schema.qualified_name(table);
+        Ok(manager
+            .get_table(&table.schema, &table.name)
+            .await?
+            .map(|table| (name, table)))
+    }
+
+    async fn column_profile(&self, params: ProfileColumnParams) -> AppResult<CallToolResult> {
+        let Some((name, table)) = self
+            .table_details(params.database.as_deref(), &params.table)
+            .await?
+        else {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "Unknown table: {}",
+                params.table
+            ))]));
+        };
+        let Some(column) = table
+            .columns
+            .iter()
+            .find(|column| column.name == params.column)
+        else {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "Unknown column {} in table {}",
+                params.column, name
+            ))]));
+        };
+
+        let profile = self
+            .databases
+            .get(params.database.as_deref())?
+            .manager
+            .profile_column(&table, column, profile::ROW_BUDGET)
+            .await?;
+        structured_result(profile.to_string(), profile)
+    }
+
+    async fn table_profile(&self, params: ProfileTableParams) -> AppResult<CallToolResult> {
+        let Some((name, table)) = self
+            .table_details(params.database.as_deref(), &params.table)
+            .await?
+        else {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "Unknown table: {}",
+                params.table
+            ))]));
+        };
+
+        let manager = &self.databases.get(params.database.as_deref())?.manager;
+        let mut columns = Vec::with_capacity(table.columns.len());
+        for column in &table.columns {
+            columns.push(
+                manager
+                    .profile_column(&table, column, profile::ROW_BUDGET)
+                    .await?,
+            );
+        }
+        let profile = TableProfile {
+            table: name,
+            row_estimate: table.row_estimate,
+            columns,
+        };
+        structured_result(profile.to_string(), profile)
+    }
+
+    async fn row_sample(&self, params: SampleRowsParams) -> AppResult<CallToolResult> {
+        let Some((name, table)) = self
+            .table_details(params.database.as_deref(), &params.table)
+            .await?
+        else {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "Unknown table: {}",
+                params.table
+            ))]));
+        };
+        let mut columns = Vec::with_capacity(params.columns.len());
+        for column_name in &params.columns {
+            match table
+                .columns
+                .iter()
+                .find(|column| column.name == *column_name)
+            {
+                Some(column) => columns.push(column),
+                None => {
+                    return Ok(CallToolResult::error(vec![Content::text(format!(
+                        "Unknown column {} in table {}",
+                        column_name, name
+                    ))]));
+                }
+            }
+        }
+
+        let limit = params.n.unwrap_or(SAMPLE_ROWS).clamp(1, MAX_SAMPLE_ROWS);
+        let rows = self
+            .databases
+            .get(params.database.as_deref())?
+            .manager
+            .sample_rows(&table, &columns, limit)
+            .await?;
+        structured_result(
+            serde_json::to_string_pretty(&rows)?,
+            SampleRowsOutput { table: name, rows },
+        )
+    }
+
+    async fn index_advice(&self, params: AdviseIndexesParams) -> AppResult<CallToolResult> {
+        let limit = params
+            .limit
+            .unwrap_or(index_advice::SLOW_QUERIES)
+            .clamp(1, index_advice::MAX_SLOW_QUERIES);
+        let advice = self
+            .databases
+            .get(params.database.as_deref())?
+            .manager
+            .advise_indexes(&params.queries, limit)
+            .await?;
+        structured_result(advice.to_string(), advice)
+    }
+
+    /// Serves a tool call, and logs the statements it ran to the MCP client that requested it.
+    async fn logged(
+        &self,
+        database: Option<&str>,
+        context: &RequestContext<RoleServer>,
+        call: impl Future<Output = AppResult<CallToolResult>>,
+    ) -> AppResult<CallToolResult> {
+        let database = &self.databases.get(database)?.name;
+        logging::log_executed(&context.peer, &self.log_level, database, call).await
+    }
+
+    /// Runs a query and logs it to the MCP client that requested it.
+    async fn run_query(
+        &self,
+        params: ExecuteQueryParams,
+        context: Option<&RequestContext<RoleServer>>,
+    ) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        // Changes go through executeWrite, which holds them for review rather than committing them right away.
+        if self.databases.read_only() || self.databases.write() {
+            dml::check_read(&params.query).map_err(|err| match err {
+                AppError::InvalidArgument(message) if self.databases.write() => {
+                    AppError::InvalidArgument(format!(
+                        "{}, use executeWrite to change data",
+                        message
+                    ))
+                }
+                err => err,
+            })?;
+        }
+        let started = Instant::now();
+        // One extra row tells whether the result was truncated.
+        let row_limit = self.databases.row_limit();
+        let rows = database
+            .manager
+            .fetch_rows(&params.query, row_limit.map(|limit| limit + 1), context)
+            .await;
+        if let Some(context) = context {
+            logging::log_query(
+                &context.peer,
+                &self.log_level,
+                &database.name,
+                &params.query,
+                started.elapsed(),
+                &rows,
+            )
+            .await;
+        }
+
+        match rows? {
+            Some(mut rows) => {
+                let truncated = row_limit.is_some_and(|limit| rows.len() > limit);
+                let mut text = String::new();
+                if let Some(limit) = row_limit.filter(|_| truncated) {
+                    rows.truncate(limit);
+                    text = format!("Only the first {} rows are shown.\n", limit);
+                }
+                text += &serde_json::to_string_pretty(&rows)?;
+                structured_result(
+                    text,
+                    QueryOutput {
+                        row_count: rows.len(),
+                        rows,
+                        truncated,
+                    },
+                )
+            }
+            None => Ok(CallToolResult::error(vec![Content::text(
+                "Query was cancelled.",
+            )])),
+        }
+    }
+
+    /// Runs a data-modifying statement in the open transaction, and logs it to the MCP client that requested it.
+    async fn run_write(
+        &self,
+        params: ExecuteWriteParams,
+        context: Option<&RequestContext<RoleServer>>,
+    ) -> AppResult<CallToolResult> {
+        let database = self.databases.get(params.database.as_deref())?;
+        let dml = dml::parse(&params.statement)?;
+        let schema = database.manager.get_schema().await?;
+        let table = dml
+            .target
+            .as_ref()
+            .and_then(|target| schema.resolve_table(&target.unquoted_table()));
+
+        let started = Instant::now();
+        let result = write::execute(
+            &self.open_transaction,
+            database,
+            &params.statement,
+            &dml,
+            table,
+            CHANGED_ROWS,
+        )
+        .await;
+        if let Some(context) = context {
+            logging::log_write(
+                &context.peer,
+                &self.log_level,
+                &database.name,
+                &params.statement,
+                started.elapsed(),
+                result.as_ref().map(|(touched, _)| touched.rows_affected),
+            )
+            .await;
+        }
+        let (touched, statements) = result?;
+
+        let key_columns: Vec<String> = table
+            .into_iter()
+            .flat_map(|table| &table.columns)
+            .filter(|column| column.key.as_deref() == Some("PRI"))
+            .map(|column| column.name.clone())
+            .collect();
+        // Updated rows can only be found again by their primary key.
+        let (changes, read) = if dml.kind == DmlKind::Update && key_columns.is_empty() {
+            (Vec::new(), 0)
+        } else {
+            let read = touched.before.len().max(touched.after.len());
+            let changes = dml::diff(dml.kind, &key_columns, touched.before, touched.after);
+            (changes, read)
+        };
+        let output = WriteOutput {
+            rows_affected: touched.rows_affected,
+            partial: touched.rows_affected > read as u64,
+            changes,
+            statements,
+        };
+
+        let mut text = format!("{} rows affected.\n", output.rows_affected);
+        for change in &output.changes {
+            text += &format!("{}\n", change);
+        }
+        if output.partial {
+            if read == CHANGED_ROWS {
+                text += &format!("Only the first {} rows are shown.\n", CHANGED_ROWS);
+            } else {
+                text += "The affected rows couldn't be read.\n";
+            }
+        }
+        text += &format!(
+            "Nothing is committed yet. The transaction has {} statements, and it is rolled back if the changes aren't committed within {} minutes.",
+            output.statements,
+            write::COMMIT_TIMEOUT.as_secs() / 60
+        );
+        structured_result(text, output)
+    }
+
+    /// Commits or rolls back the open transaction.
+    async fn finish_transaction(&self, commit: bool) -> AppResult<CallToolResult> {
+        let Some(pending) = self.open_transaction.lock().await.take() else {
+            return Ok(CallToolResult::error(vec![Content::text(
+                "No transaction is open. It may have been rolled back after its timeout.",
+            )]));
+        };
+        let summary = format!(
+            "{} statements on `{}`, {} rows affected",
+            pending.statements, pending.database, pending.rows_affected
+        );
+        if commit {
+            pending.commit().await?;
+            Ok(CallToolResult::success(vec![Content::text(format!(
+                "Committed {}.",
+                summary
+            ))]))
+        } else {
+            pending.rollback().await?;
+            Ok(CallToolResult::success(vec![Content::text(format!(
+                "Rolled back {}.",
+                summary
+            ))]))
+        }
+    }
+}
+
+#[tool_router]
+impl DatabaseHandler {
+    #[tool(
+        name = "listDatabases",
+        description = "Lists the connected databases with their SQL dialect and server version.",
+        output_schema = cached_schema_for_type::<DatabaseList>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn list_databases(&self) -> Result<CallToolResult, McpError> {
+        Ok(self.database_list()?)
+    }
+
+    #[tool(
+        name = "getDatabaseSchema",
+        description = "Retrieves the schema: tables with their columns, comments, keys, constraints and triggers, views, and stored functions and procedures.",
+        output_schema = cached_schema_for_type::<DatabaseSchema>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn get_database_schema(
+        &self,
+        Parameters(params): Parameters<DatabaseParams>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.database_schema(params).await?)
+    }
+
+    #[tool(
+        name = "searchSchema",
+        description = "Finds the tables most relevant to a query by their names, columns, comments and foreign keys, and returns their definitions with the tables they're connected to. Use it instead of getDatabaseSchema on large databases.",
+        output_schema = cached_schema_for_type::<SchemaSearchOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn search_schema_tool(
+        &self,
+        Parameters(params): Parameters<SearchSchemaParams>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.search_schema(params).await?)
+    }
+
+    #[tool(
+        name = "searchValue",
+        description = "Finds which tables, columns and rows contain a value, such as a person's name, by searching text columns across tables. Returns primary keys of the matching rows, to filter on them in queries.",
+        output_schema = cached_schema_for_type::<ValueSearchOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn search_value(
+        &self,
+        Parameters(params): Parameters<SearchValueParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let database = params.database.clone();
+        Ok(self
+            .logged(database.as_deref(), &context, self.value_search(params))
+            .await?)
+    }
+
+    #[tool(
+        name = "findJoinPath",
+        description = "Finds the shortest chain of foreign keys between two tables and returns it as JOIN clauses with their ON conditions.",
+        output_schema = cached_schema_for_type::<JoinPathOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn find_join_path(
+        &self,
+        Parameters(params): Parameters<FindJoinPathParams>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.join_path(params).await?)
+    }
+
+    #[tool(
+        name = "renderDiagram",
+        description = "Draws an entity-relationship diagram of tables, their columns and foreign keys as Mermaid erDiagram or Graphviz DOT source.",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn render_diagram(
+        &self,
+        Parameters(params): Parameters<RenderDiagramParams>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.diagram(params).await?)
+    }
+
+    #[tool(
+        name = "sampleRows",
+        description = "Shows rows picked at random from a table, to see what its data looks like. Long values are shortened and binary values are shown as their size and first bytes. Prefer this to SELECT * ... LIMIT.",
+        output_schema = cached_schema_for_type::<SampleRowsOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    pub async fn sample_rows(
+        &self,
+        Parameters(params): Parameters<SampleRowsParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let database = params.database.clone();
+        Ok(self
+            .logged(database.as_deref(), &context, self.row_sample(params))
+            .await?)
+    }
+
+    #[tool(
+        name = "adviseIndexes",
+        description = "Suggests missing indexes. Explains the slowest queries recorded by the server, or the given queries, and proposes CREATE INDEX statements for the full table scans in their plans, with the reasoning and, where hypothetical indexes are available, the estimated cost before and after.",
+        output_schema = cached_schema_for_type::<IndexAdvice>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn advise_indexes(
+        &self,
+        Parameters(params): Parameters<AdviseIndexesParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let database = params.database.clone();
+        Ok(self
+            .logged(database.as_deref(), &context, self.index_advice(params))
+            .await?)
+    }
+
+    #[tool(
+        name = "profileColumn",
+        description = "Profiles the values of a column: share of nulls, distinct count, min and max, most frequent values, string lengths and a histogram of numbers and dates. Large tables are sampled.",
+        output_schema = cached_schema_for_type::<ColumnProfile>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn profile_column(
+        &self,
+        Parameters(params): Parameters<ProfileColumnParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let database = params.database.clone();
+        Ok(self
+            .logged(database.as_deref(), &context, self.column_profile(params))
+            .await?)
+    }
+
+    #[tool(
+        name = "profileTable",
+        description = "Profiles the values of every column of a table, as profileColumn does. Large tables are sampled.",
+        output_schema = cached_schema_for_type::<TableProfile>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn profile_table(
+        &self,
+        Parameters(params): Parameters<ProfileTableParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let database = params.database.clone();
+        Ok(self
+            .logged(database.as_deref(), &context, self.table_profile(params))
+            .await?)
+    }
+
+    #[tool(
+        name = "refreshSchema",
+        description = "Reloads the schema. The schema is cached and reloaded when tables or columns change, so this is only needed if it looks out of date.",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn refresh_schema(
+        &self,
+        Parameters(params): Parameters<DatabaseParams>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.reload_schema(params).await?)
+    }
+
+    // No statement restriction is enforced, so the query tool can't be advertised as read-only.
+    #[tool(
+        name = "executeQuery",
+        description = "Executes a SQL SELECT query and returns the results.",
+        output_schema = cached_schema_for_type::<QueryOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    pub async fn execute_query(
+        &self,
+        Parameters(params): Parameters<ExecuteQueryParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.run_query(params, Some(&context)).await?)
+    }
+
+    #[tool(
+        name = "executeWrite",
+        description = "Runs a single INSERT, UPDATE or DELETE statement in a transaction that stays open, and shows the number of affected rows with the changed rows before and after.",
+        output_schema = cached_schema_for_type::<WriteOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    pub async fn execute_write(
+        &self,
+        Parameters(params): Parameters<ExecuteWriteParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(self.run_write(params, Some(&context)).await?)
+    }
+
+    #[tool(
+        name = "commitTransaction",
+        description = "Commits the changes made by executeWrite. Only call it once the user has reviewed and approved them.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    pub async fn commit_transaction(&self) -> Result<CallToolResult, McpError> {
+        Ok(self.finish_transaction(true).await?)
+    }
+
+    #[tool(
+        name = "rollbackTransaction",
+        description = "Discards the changes made by executeWrite.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    pub async fn rollback_transaction(&self) -> Result<CallToolResult, McpError> {
+        Ok(self.finish_transaction(false).await?)
+    }
+}
+
+#[tool_handler]
+impl ServerHandler for DatabaseHandler {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2025_06_18,
+            capabilities: ServerCapabilities::builder()
+                .enable_prompts()
+                .enable_resources()
+                .enable_resources_list_changed()
+                .enable_resources_subscribe()
+                .enable_tools()
+                .enable_logging()
+                .build(),
+            server_info: Implementation::from_build_env(),
+            instructions: Some(self.system_prompt.to_string()),
+        }
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        resource::watch_schema(
+            self.databases.clone(),
+            context.peer,
+            self.resource_subscriptions.clone(),
+        );
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        logging::set_level(&self.log_level, request.level);
+        Ok(())
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        Ok(prompt::list_prompts(self.databases.is_multiple()))
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        let database = self.databases.get(
+            request
+                .arguments
+                .as_ref()
+                .and_then(|arguments| arguments.get("database"))
+                .and_then(|database| database.as_str()),
+        )?;
+        Ok(prompt::get_prompt(database.manager.as_ref(), &request.name, request.arguments).await?)
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        Ok(resource::list_resources(&self.databases).await?)
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        Ok(resource::read_resource(&self.databases, &request.uri).await?)
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        resource::subscribe(&self.resource_subscriptions, request.uri);
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        resource::unsubscribe(&self.resource_subscriptions, &request.uri);
+        Ok(())
+    }
+}
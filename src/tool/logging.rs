@@ -0,0 +1,160 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rmcp::{
+    Peer, RoleServer,
+    model::{LoggingLevel, LoggingMessageNotificationParam},
+};
+use serde_json::json;
+
+use crate::error::{AppError, AppResult};
+
+const LOGGER: &str = "airy";
+
+/// Minimum level of log messages a client has asked for with `logging/setLevel`.
+/// Clients that haven't set a level receive `info` and above.
+pub type LogLevel = Arc<Mutex<Option<LoggingLevel>>>;
+
+/// A statement a manager ran while serving a tool call, with its duration and error.
+struct Executed {
+    query: String,
+    duration: Duration,
+    error: Option<String>,
+}
+
+tokio::task_local! {
+    /// Statements run during the tool call being served, collected by `log_executed`.
+    static EXECUTED: RefCell<Vec<Executed>>;
+}
+
+pub fn set_level(log_level: &LogLevel, level: LoggingLevel) {
+    *log_level.lock().unwrap() = Some(level);
+}
+
+/// Sends an executed statement, the database it ran on, its duration and its row count or error to the client.
+pub async fn log_query<T>(
+    peer: &Peer<RoleServer>,
+    log_level: &LogLevel,
+    database: &str,
+    query: &str,
+    duration: Duration,
+    result: &AppResult<Option<Vec<T>>>,
+) {
+    let duration_ms = duration.as_millis() as u64;
+    let (level, data) = match result {
+        Ok(Some(rows)) => (
+            LoggingLevel::Info,
+            json!({ "database": database, "query": query, "durationMs": duration_ms, "rows": rows.len() }),
+        ),
+        Ok(None) => (
+            LoggingLevel::Warning,
+            json!({ "database": database, "query": query, "durationMs": duration_ms, "cancelled": true }),
+        ),
+        Err(err) => (
+            LoggingLevel::Error,
+            json!({ "database": database, "query": query, "durationMs": duration_ms, "error": err.to_string() }),
+        ),
+    };
+    log(peer, log_level, level, data).await;
+}
+
+/// Sends a data-modifying statement, the database it ran on, its duration and its affected row count or error to the client.
+pub async fn log_write(
+    peer: &Peer<RoleServer>,
+    log_level: &LogLevel,
+    database: &str,
+    statement: &str,
+    duration: Duration,
+    result: Result<u64, &AppError>,
+) {
+    let duration_ms = duration.as_millis() as u64;
+    let (level, data) = match result {
+        Ok(rows_affected) => (
+            LoggingLevel::Info,
+            json!({ "database": database, "query": statement, "durationMs": duration_ms, "rowsAffected": rows_affected }),
+        ),
+        Err(err) => (
+            LoggingLevel::Error,
+            json!({ "database": database, "query": statement, "durationMs": duration_ms, "error": err.to_string() }),
+        ),
+    };
+    log(peer, log_level, level, data).await;
+}
+
+/// Runs a statement, and records it if a tool call is collecting the statements it runs.
+pub async fn record<T, E: Display>(
+    query: &str,
+    statement: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let started = Instant::now();
+    let result = statement.await;
+    let _ = EXECUTED.try_with(|executed| {
+        executed.borrow_mut().push(Executed {
+            query: query.into(),
+            duration: started.elapsed(),
+            error: result.as_ref().err().map(ToString::to_string),
+        })
+    });
+    result
+}
+
+/// Serves a tool call, and sends the statements that managers recorded for it to the client.
+pub async fn log_executed<T>(
+    peer: &Peer<RoleServer>,
+    log_level: &LogLevel,
+    database: &str,
+    call: impl Future<Output = T>,
+) -> T {
+    let (result, executed) = EXECUTED
+        .scope(RefCell::default(), async {
+            let result = call.await;
+            (result, EXECUTED.with(RefCell::take))
+        })
+        .await;
+    for Executed {
+        query,
+        duration,
+        error,
+    } in executed
+    {
+        let duration_ms = duration.as_millis() as u64;
+        let (level, data) = match error {
+            None => (
+                LoggingLevel::Info,
+                json!({ "database": database, "query": query, "durationMs": duration_ms }),
+            ),
+            Some(error) => (
+                LoggingLevel::Error,
+                json!({ "database": database, "query": query, "durationMs": duration_ms, "error": error }),
+            ),
+        };
+        log(peer, log_level, level, data).await;
+    }
+    result
+}
+
+pub async fn log(
+    peer: &Peer<RoleServer>,
+    log_level: &LogLevel,
+    level: LoggingLevel,
+    data: serde_json::Value,
+) {
+    let min_level = log_level.lock().unwrap().unwrap_or(LoggingLevel::Info);
+    // Levels are declared from least to most severe.
+    if (level as u8) < (min_level as u8) {
+        return;
+    }
+
+    // Logging is best-effort, a client that went away is noticed elsewhere.
+    let _ = peer
+        .notify_logging_message(LoggingMessageNotificationParam {
+            level,
+            logger: Some(LOGGER.into()),
+            data,
+        })
+        .await;
+}
@@ -1,30 +1,124 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
-use rmcp::{
-    model::CallToolResult,
-    schemars::{self, JsonSchema},
-};
-use serde::{Deserialize, Serialize};
+use rmcp::{RoleServer, service::RequestContext};
 
-use crate::error::AppResult;
+use crate::{
+    dml::Dml,
+    error::AppResult,
+    index_advice::IndexAdvice,
+    profile::ColumnProfile,
+    schema::{ColumnSchema, DatabaseSchema, TableSchema},
+    value_search::TableSearch,
+};
 
+pub mod external;
+pub mod handler;
+pub mod logging;
 pub mod mysql;
 pub mod postgres;
+pub mod progress;
+pub mod prompt;
+pub mod resource;
+pub mod schema_cache;
+pub mod write;
 
-#[derive(Serialize, Deserialize, JsonSchema)]
-pub struct ExecuteQueryParams {
-    #[schemars(description = "The SQL SELECT query to execute.")]
-    query: String,
-}
+/// A result row, keyed by column name.
+pub type Row = serde_json::Map<String, serde_json::Value>;
 
 #[async_trait]
 pub trait Manager {
-    fn system_prompt(&self) -> &str;
+    /// Name of the SQL dialect, such as "MySQL".
+    fn dialect(&self) -> &str;
+
+    /// Version reported by the connected server.
+    fn server_version(&self) -> &str;
+
+    /// Introspects tables with their columns and foreign keys.
+    /// The schema is cached until the database reports a change.
+    async fn get_schema(&self) -> AppResult<DatabaseSchema>;
+
+    /// Reloads the schema, bypassing the cache.
+    async fn refresh_schema(&self) -> AppResult<DatabaseSchema>;
+
+    /// Introspects a single table in detail, including indexes and a row count estimate.
+    async fn get_table(&self, schema: &str, name: &str) -> AppResult<Option<TableSchema>>;
+
+    /// Fetches up to `limit` rows picked at random, with only `columns` unless it is empty.
+    /// Long values are shortened and binary values are replaced by their size and a hex prefix.
+    /// Tables without a row estimate are not sampled, and their first rows are returned.
+    async fn sample_rows(
+        &self,
+        table: &TableSchema,
+        columns: &[&ColumnSchema],
+        limit: usize,
+    ) -> AppResult<Vec<Row>>;
+
+    /// Computes statistics of a column over at most `budget` rows, sampled if the table is larger.
+    async fn profile_column(
+        &self,
+        table: &TableSchema,
+        column: &ColumnSchema,
+        budget: usize,
+    ) -> AppResult<ColumnProfile>;
+
+    /// Finds at most `limit` rows of a table whose text columns contain all of `words`, giving up after `timeout`.
+    /// Tables estimated to have more than `SCAN_ROWS` rows are searched only in columns with a text index.
+    async fn search_value(
+        &self,
+        table: &TableSchema,
+        words: &[String],
+        limit: usize,
+        timeout: Duration,
+    ) -> AppResult<TableSearch>;
 
-    async fn get_database_schema(&self) -> AppResult<CallToolResult>;
+    /// Explains `queries`, or the `limit` slowest queries of the statement history if none are given,
+    /// and suggests indexes for the full scans in their plans, estimating their impact with hypothetical indexes where possible.
+    async fn advise_indexes(&self, queries: &[String], limit: usize) -> AppResult<IndexAdvice>;
 
-    async fn execute_query(&self, params: ExecuteQueryParams) -> AppResult<CallToolResult>;
+    /// Applies a migration and reverts it where the database isn't changed,
+    /// a transaction that is rolled back on PostgreSQL and an empty copy of the schema in a scratch database on MySQL.
+    /// Returns what went wrong: a failed statement or schema objects the down migration doesn't restore.
+    async fn try_migration(&self, up: &str, down: &str) -> AppResult<Vec<String>>;
+
+    /// Starts a transaction that stays open until it is committed or rolled back.
+    async fn begin_write(&self) -> AppResult<Box<dyn WriteTransaction>>;
+
+    /// Runs a query, fetching at most `limit` rows.
+    /// When it serves an MCP request, reports progress and aborts the query once the request is cancelled.
+    /// Returns `None` if the request was cancelled.
+    async fn fetch_rows(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        context: Option<&RequestContext<RoleServer>>,
+    ) -> AppResult<Option<Vec<Row>>>;
 }
 
 pub type ManagerArc = Arc<dyn Manager + Send + Sync>;
+
+/// Outcome of a data-modifying statement, with the rows it touched as they were before and after it.
+pub struct Touched {
+    pub rows_affected: u64,
+    pub before: Vec<Row>,
+    pub after: Vec<Row>,
+}
+
+/// A transaction held open across tool calls, so that changes can be reviewed before they're committed.
+#[async_trait]
+pub trait WriteTransaction: Send {
+    /// Runs a data-modifying statement, fetching at most `limit` of the rows it touches before and after it.
+    /// Rows are fetched only if `table` is the single table it changes, and after an update only if that has a primary key.
+    /// A failed statement is undone without undoing earlier statements of the transaction.
+    async fn execute(
+        &mut self,
+        statement: &str,
+        dml: &Dml,
+        table: Option<&TableSchema>,
+        limit: usize,
+    ) -> AppResult<Touched>;
+
+    async fn commit(self: Box<Self>) -> AppResult<()>;
+
+    async fn rollback(self: Box<Self>) -> AppResult<()>;
+}
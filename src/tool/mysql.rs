@@ -1,177 +1,1429 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::{BuildHasher, RandomState},
+    sync::Arc,
+    time::Duration,
+};
+
 use async_trait::async_trait;
-use rmcp::{
-    Error as McpError, ServerHandler,
-    model::{
-        CallToolResult, Content, Implementation, ProtocolVersion, ServerCapabilities, ServerInfo,
-    },
-    tool,
+use futures::StreamExt;
+use rmcp::{RoleServer, service::RequestContext};
+use sqlx::{
+    Executor, Row as _, Transaction,
+    mysql::{MySql, MySqlConnection, MySqlDatabaseError, MySqlPool, MySqlRow},
 };
-use sqlx::mysql::MySqlPool;
 
 use crate::{
-    error::AppResult,
+    dml::{self, Dml, DmlKind},
+    error::{AppError, AppResult},
+    index_advice::{self, IndexAdvice, SlowQuery},
+    migration,
     mysql_utility::from_row,
-    tool::{ExecuteQueryParams, Manager},
+    profile::{
+        BINARY_PREFIX_BYTES, Bucket, ColumnKind, ColumnProfile, HISTOGRAM_BUCKETS, LengthStats,
+        MAX_VALUE_CHARS, ROW_BUDGET, TOP_VALUES, ValueCount, sample_fraction,
+    },
+    schema::{
+        CheckSchema, ColumnSchema, DatabaseSchema, ForeignKeySchema, IndexSchema, RoutineSchema,
+        TableSchema, TriggerSchema, ViewSchema,
+    },
+    tool::{Manager, Row, Touched, WriteTransaction, logging, progress, schema_cache::SchemaCache},
+    utility::{shorten, shorten_json},
+    value_search::{self, SCAN_ROWS, TableSearch},
 };
 
 #[derive(Clone)]
 pub struct MySqlManager {
-    system_prompt: String,
     pool: MySqlPool,
+    server_version: String,
+    schema_cache: Arc<SchemaCache>,
+    /// Runs fetched queries in read-only transactions, so that they can't write even by changing settings.
+    read_only: bool,
 }
 
 impl MySqlManager {
-    pub fn new(pool: MySqlPool, system_prompt: String) -> Self {
-        Self {
+    pub async fn new(pool: MySqlPool, read_only: bool) -> AppResult<Self> {
+        let (server_version,): (String,) =
+            sqlx::query_as("SELECT VERSION()").fetch_one(&pool).await?;
+
+        Ok(Self {
             pool,
-            system_prompt,
+            server_version,
+            schema_cache: Default::default(),
+            read_only,
+        })
+    }
+
+    /// Loads all tables and views of a schema, or a single table, with their columns, constraints and triggers.
+    /// Takes a fixed number of queries however many tables there are.
+    async fn load_relations(
+        &self,
+        schema: &str,
+        table: Option<&str>,
+    ) -> AppResult<(Vec<TableSchema>, Vec<ViewSchema>)> {
+        let column_rows: Vec<ColumnRow> = sqlx::query_as(
+            "
+                SELECT
+                    t.table_name, t.table_type, t.table_comment, v.view_definition,
+                    c.column_name, c.column_type, c.is_nullable, c.column_key, c.column_default,
+                    c.extra, c.generation_expression, c.column_comment
+                FROM information_schema.tables t
+                JOIN information_schema.columns c
+                    ON c.table_schema = t.table_schema AND c.table_name = t.table_name
+                LEFT JOIN information_schema.views v
+                    ON v.table_schema = t.table_schema AND v.table_name = t.table_name
+                WHERE t.table_schema = ? AND t.table_type IN ('BASE TABLE', 'VIEW')
+                    AND (? IS NULL OR (t.table_name = ? AND t.table_type = 'BASE TABLE'))
+                ORDER BY t.table_name, c.ordinal_position
+            ",
+        )
+        .bind(schema)
+        .bind(table)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tables: Vec<TableSchema> = Vec::new();
+        let mut views: Vec<ViewSchema> = Vec::new();
+        for (
+            relation_name,
+            relation_type,
+            relation_comment,
+            definition,
+            column_name,
+            column_type,
+            is_nullable,
+            column_key,
+            column_default,
+            extra,
+            generation_expression,
+            column_comment,
+        ) in column_rows
+        {
+            let Ok(relation_name) = String::from_utf8(relation_name) else {
+                continue;
+            };
+            let is_view = relation_type == b"VIEW";
+            if is_view && views.last().is_none_or(|view| view.name != relation_name) {
+                views.push(ViewSchema {
+                    schema: schema.into(),
+                    name: relation_name,
+                    // MySQL puts "VIEW" in the comment of views.
+                    comment: non_empty(&relation_comment).filter(|comment| comment != "VIEW"),
+                    materialized: false,
+                    columns: Vec::new(),
+                    definition: definition.as_deref().and_then(non_empty),
+                });
+            } else if !is_view
+                && tables
+                    .last()
+                    .is_none_or(|table| table.name != relation_name)
+            {
+                tables.push(TableSchema {
+                    schema: schema.into(),
+                    name: relation_name,
+                    comment: non_empty(&relation_comment),
+                    columns: Vec::new(),
+                    indexes: Vec::new(),
+                    foreign_keys: Vec::new(),
+                    checks: Vec::new(),
+                    triggers: Vec::new(),
+                    row_estimate: None,
+                });
+            }
+            let column = ColumnSchema {
+                name: column_name,
+                data_type: String::from_utf8_lossy(&column_type).into_owned(),
+                nullable: is_nullable != "NO",
+                key: non_empty(&column_key),
+                default: column_default.filter(|s| !s.is_empty()),
+                extra: extra.filter(|s| !s.is_empty()),
+                generated: generation_expression.as_deref().and_then(non_empty),
+                comment: non_empty(&column_comment),
+            };
+            let columns = if is_view {
+                views.last_mut().map(|view| &mut view.columns)
+            } else {
+                tables.last_mut().map(|table| &mut table.columns)
+            };
+            if let Some(columns) = columns {
+                columns.push(column);
+            }
         }
+
+        let foreign_key_rows: Vec<ForeignKeyRow> = sqlx::query_as(
+            "
+                SELECT
+                    table_name,
+                    constraint_name,
+                    column_name,
+                    referenced_table_schema,
+                    referenced_table_name,
+                    referenced_column_name
+                FROM information_schema.key_column_usage
+                WHERE table_schema = ? AND referenced_table_name IS NOT NULL
+                    AND (? IS NULL OR table_name = ?)
+                ORDER BY table_name, constraint_name, ordinal_position
+            ",
+        )
+        .bind(schema)
+        .bind(table)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let positions: HashMap<String, usize> = tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| (table.name.clone(), i))
+            .collect();
+        for (table_name, name, column, referenced_schema, referenced_table, referenced_column) in
+            foreign_key_rows
+        {
+            let Some(&i) = positions.get(String::from_utf8_lossy(&table_name).as_ref()) else {
+                continue;
+            };
+            let name = String::from_utf8_lossy(&name).into_owned();
+            let column = String::from_utf8_lossy(&column).into_owned();
+            let referenced_column = String::from_utf8_lossy(&referenced_column).into_owned();
+            let foreign_keys = &mut tables[i].foreign_keys;
+            match foreign_keys.last_mut() {
+                Some(foreign_key) if foreign_key.name == name => {
+                    foreign_key.columns.push(column);
+                    foreign_key.referenced_columns.push(referenced_column);
+                }
+                _ => foreign_keys.push(ForeignKeySchema {
+                    name,
+                    columns: vec![column],
+                    referenced_schema: String::from_utf8_lossy(&referenced_schema).into_owned(),
+                    referenced_table: String::from_utf8_lossy(&referenced_table).into_owned(),
+                    referenced_columns: vec![referenced_column],
+                }),
+            }
+        }
+
+        let check_rows: AppResult<Vec<CheckRow>> = sqlx::query_as(
+            "
+                SELECT tc.table_name, cc.constraint_name, cc.check_clause
+                FROM information_schema.table_constraints tc
+                JOIN information_schema.check_constraints cc
+                    ON cc.constraint_schema = tc.constraint_schema
+                    AND cc.constraint_name = tc.constraint_name
+                WHERE tc.table_schema = ? AND tc.constraint_type = 'CHECK'
+                    AND (? IS NULL OR tc.table_name = ?)
+                ORDER BY tc.table_name, cc.constraint_name
+            ",
+        )
+        .bind(schema)
+        .bind(table)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into);
+        let check_rows = match check_rows {
+            // MySQL before 8.0.16 has no CHECK constraints, nor a table listing them.
+            Err(AppError::Sql(sqlx::Error::Database(err)))
+                if err.code().as_deref() == Some(NO_SUCH_TABLE) =>
+            {
+                Vec::new()
+            }
+            check_rows => check_rows?,
+        };
+        for (table_name, name, expression) in check_rows {
+            if let Some(&i) = positions.get(String::from_utf8_lossy(&table_name).as_ref()) {
+                tables[i].checks.push(CheckSchema {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    expression: String::from_utf8_lossy(&expression).into_owned(),
+                });
+            }
+        }
+
+        let trigger_rows: Vec<TriggerRow> = sqlx::query_as(
+            "
+                SELECT event_object_table, trigger_name, action_timing, event_manipulation, action_statement
+                FROM information_schema.triggers
+                WHERE event_object_schema = ? AND (? IS NULL OR event_object_table = ?)
+                ORDER BY event_object_table, action_timing, event_manipulation, action_order
+            ",
+        )
+        .bind(schema)
+        .bind(table)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await?;
+        for (table_name, name, timing, event, action) in trigger_rows {
+            if let Some(&i) = positions.get(String::from_utf8_lossy(&table_name).as_ref()) {
+                tables[i].triggers.push(TriggerSchema {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    timing: String::from_utf8_lossy(&timing).into_owned(),
+                    events: vec![String::from_utf8_lossy(&event).into_owned()],
+                    action: String::from_utf8_lossy(&action).into_owned(),
+                });
+            }
+        }
+
+        Ok((tables, views))
+    }
+
+    /// Loads the signatures of stored functions and procedures.
+    async fn load_routines(&self, schema: &str) -> AppResult<Vec<RoutineSchema>> {
+        let routine_rows: Vec<RoutineRow> = sqlx::query_as(
+            "
+                SELECT
+                    r.routine_name,
+                    r.routine_type,
+                    (
+                        SELECT GROUP_CONCAT(
+                            CONCAT_WS(' ', p.parameter_mode, p.parameter_name, p.dtd_identifier)
+                            ORDER BY p.ordinal_position SEPARATOR ', '
+                        )
+                        FROM information_schema.parameters p
+                        WHERE p.specific_schema = r.routine_schema
+                            AND p.specific_name = r.specific_name
+                            AND p.ordinal_position > 0
+                    ),
+                    CASE WHEN r.routine_type = 'FUNCTION' THEN r.dtd_identifier END,
+                    r.routine_comment
+                FROM information_schema.routines r
+                WHERE r.routine_schema = ?
+                ORDER BY r.routine_name
+            ",
+        )
+        .bind(schema)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(routine_rows
+            .into_iter()
+            .map(|(name, kind, arguments, returns, comment)| RoutineSchema {
+                schema: schema.into(),
+                name: String::from_utf8_lossy(&name).into_owned(),
+                kind: String::from_utf8_lossy(&kind).into_owned(),
+                arguments: arguments
+                    .map(|arguments| String::from_utf8_lossy(&arguments).into_owned())
+                    .unwrap_or_default(),
+                returns: returns.as_deref().and_then(non_empty),
+                comment: non_empty(&comment),
+            })
+            .collect())
+    }
+
+    /// Digest of table, view, column, constraint, trigger and routine definitions,
+    /// to tell whether the cached schema is current.
+    async fn fingerprint(&self) -> AppResult<String> {
+        let (fingerprint,): (String,) = sqlx::query_as(
+            "
+                SELECT CAST(CONCAT_WS(
+                    ':',
+                    (
+                        SELECT CONCAT_WS(
+                            ',', COUNT(*), MAX(create_time), MAX(update_time),
+                            SUM(CRC32(CONCAT_WS(',', table_name, table_comment)))
+                        )
+                        FROM information_schema.tables
+                        WHERE table_schema = DATABASE()
+                    ),
+                    (
+                        SELECT CONCAT_WS(
+                            ',', COUNT(*),
+                            SUM(CRC32(CONCAT_WS(
+                                ',', table_name, column_name, ordinal_position, column_type,
+                                is_nullable, column_key, column_default, extra, column_comment
+                            )))
+                        )
+                        FROM information_schema.columns
+                        WHERE table_schema = DATABASE()
+                    ),
+                    (
+                        SELECT CONCAT_WS(',', COUNT(*), SUM(CRC32(CONCAT_WS(',', table_name, constraint_name))))
+                        FROM information_schema.table_constraints
+                        WHERE table_schema = DATABASE()
+                    ),
+                    (
+                        SELECT CONCAT_WS(',', COUNT(*), SUM(CRC32(CONCAT_WS(',', table_name, view_definition))))
+                        FROM information_schema.views
+                        WHERE table_schema = DATABASE()
+                    ),
+                    (
+                        SELECT CONCAT_WS(',', COUNT(*), SUM(CRC32(CONCAT_WS(',', trigger_name, created))))
+                        FROM information_schema.triggers
+                        WHERE trigger_schema = DATABASE()
+                    ),
+                    (
+                        SELECT CONCAT_WS(
+                            ',', COUNT(*), MAX(last_altered),
+                            SUM(CRC32(CONCAT_WS(',', routine_name, created, routine_comment)))
+                        )
+                        FROM information_schema.routines
+                        WHERE routine_schema = DATABASE()
+                    )
+                ) AS CHAR)
+            ",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(fingerprint)
+    }
+
+    /// Reads the read queries of the current database that took the most time from `performance_schema`,
+    /// with a sample of each that has actual values in place of placeholders.
+    async fn slow_queries(&self, limit: usize) -> AppResult<Vec<SlowQuery>> {
+        let query = "
+                SELECT QUERY_SAMPLE_TEXT, CAST(COUNT_STAR AS SIGNED), SUM_TIMER_WAIT / 1e9, AVG_TIMER_WAIT / 1e9
+                FROM performance_schema.events_statements_summary_by_digest
+                WHERE SCHEMA_NAME = DATABASE()
+                    AND (DIGEST_TEXT LIKE 'SELECT%' OR DIGEST_TEXT LIKE 'WITH%')
+                    AND DIGEST_TEXT NOT LIKE '%information_schema%'
+                    AND DIGEST_TEXT NOT LIKE '%performance_schema%'
+                    AND DIGEST_TEXT LIKE '%FROM%'
+                ORDER BY SUM_TIMER_WAIT DESC
+                LIMIT ?
+            ";
+        let rows: Vec<(Option<Vec<u8>>, i64, f64, f64)> = logging::record(
+            query,
+            sqlx::query_as(query)
+                .bind(limit as u64)
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(query, calls, total_ms, mean_ms)| {
+                Some(SlowQuery {
+                    calls: Some(calls),
+                    total_ms: Some(total_ms),
+                    mean_ms: Some(mean_ms),
+                    ..SlowQuery::new(&String::from_utf8_lossy(&query?))
+                })
+            })
+            .collect())
+    }
+
+    async fn explain(&self, query: &str) -> AppResult<serde_json::Value> {
+        index_advice::check_query(query)?;
+        let explain = format!("EXPLAIN FORMAT=JSON {}", query);
+        let row: Row = from_row(
+            logging::record(&explain, sqlx::query(&explain).fetch_one(&self.pool)).await?,
+        )?;
+        row.into_iter()
+            .next()
+            .and_then(|(_, plan)| index_advice::plan_json(plan))
+            .ok_or_else(|| AppError::InvalidArgument("EXPLAIN returned no plan".into()))
     }
 }
 
-#[tool(tool_box)]
-impl MySqlManager {
-    #[tool(
-        name = "mysqlGetDatabaseSchema",
-        description = "Retrieves the schema (tables and columns)."
-    )]
-    pub async fn get_database_schema(&self) -> Result<CallToolResult, McpError> {
-        Ok(Manager::get_database_schema(self).await?)
-    }
-
-    #[tool(
-        name = "mysqlExecuteQuery",
-        description = "Executes a SQL SELECT query and returns the results."
-    )]
-    pub async fn execute_query(
-        &self,
-        #[tool(aggr)] params: ExecuteQueryParams,
-    ) -> Result<CallToolResult, McpError> {
-        Ok(Manager::execute_query(self, params).await?)
+/// Relation name, type, comment and view definition,
+/// followed by column name, type, nullability, key, default, extra, generation expression and comment.
+type ColumnRow = (
+    Vec<u8>,
+    Vec<u8>,
+    Vec<u8>,
+    Option<Vec<u8>>,
+    String,
+    Vec<u8>,
+    String,
+    Vec<u8>,
+    Option<String>,
+    Option<String>,
+    Option<Vec<u8>>,
+    Vec<u8>,
+);
+
+/// Table name, constraint name and check clause.
+type CheckRow = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// Table name, trigger name, timing, event and action.
+type TriggerRow = (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// Routine name, type, parameters, return type and comment.
+type RoutineRow = (Vec<u8>, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>, Vec<u8>);
+
+/// SQLSTATE of a query on a table that doesn't exist.
+const NO_SUCH_TABLE: &str = "42S02";
+
+/// Lists columns, indexes, constraints, views, triggers and routines of the current database by name and definition,
+/// to tell whether a migration that was reverted left the schema as it was.
+const SCHEMA_OBJECTS: &str = "
+    SELECT CONCAT_WS(
+        ' ', 'column', CONCAT(table_name, '.', column_name), column_type, is_nullable, column_default, extra
+    )
+    FROM information_schema.columns
+    WHERE table_schema = DATABASE()
+    UNION ALL
+    SELECT CONCAT_WS(
+        ' ', 'index', CONCAT(table_name, '.', index_name), IF(non_unique = 0, 'unique', NULL),
+        GROUP_CONCAT(column_name ORDER BY seq_in_index)
+    )
+    FROM information_schema.statistics
+    WHERE table_schema = DATABASE()
+    GROUP BY table_name, index_name, non_unique
+    UNION ALL
+    SELECT CONCAT_WS(' ', 'constraint', CONCAT(table_name, '.', constraint_name), constraint_type)
+    FROM information_schema.table_constraints
+    WHERE table_schema = DATABASE()
+    UNION ALL
+    SELECT CONCAT_WS(' ', 'view', table_name, MD5(view_definition))
+    FROM information_schema.views
+    WHERE table_schema = DATABASE()
+    UNION ALL
+    SELECT CONCAT_WS(' ', 'trigger', CONCAT(event_object_table, '.', trigger_name), MD5(action_statement))
+    FROM information_schema.triggers
+    WHERE trigger_schema = DATABASE()
+    UNION ALL
+    SELECT CONCAT_WS(' ', LOWER(routine_type), routine_name, MD5(routine_definition))
+    FROM information_schema.routines
+    WHERE routine_schema = DATABASE()
+    ORDER BY 1
+";
+
+/// Error number of a statement interrupted by `MAX_EXECUTION_TIME`.
+const QUERY_TIMEOUT: u16 = 3024;
+
+fn non_empty(value: &[u8]) -> Option<String> {
+    Some(String::from_utf8_lossy(value).into_owned()).filter(|s| !s.is_empty())
+}
+
+/// Table name, constraint name, column, referenced schema, table and column.
+type ForeignKeyRow = (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// Rows, non-null values, distinct values, min, max, min, max and average length,
+/// and the range of the numbers a histogram is built over.
+type ProfileRow = (
+    i64,
+    i64,
+    i64,
+    Option<Vec<u8>>,
+    Option<Vec<u8>>,
+    Option<i64>,
+    Option<i64>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+);
+
+/// Integer column types, whose values random key lookups can be made for.
+const INTEGER_TYPES: [&str; 6] = [
+    "tinyint",
+    "smallint",
+    "mediumint",
+    "int",
+    "integer",
+    "bigint",
+];
+
+/// The primary key column, if the primary key is a single integer column.
+fn integer_key(table: &TableSchema) -> Option<&ColumnSchema> {
+    let mut keys = table
+        .columns
+        .iter()
+        .filter(|column| column.key.as_deref() == Some("PRI"));
+    let key = keys.next()?;
+    // Column types carry a display width and attributes, such as `int(11) unsigned`.
+    let data_type = key.data_type.to_lowercase();
+    let base = data_type.split(['(', ' ']).next().unwrap_or_default();
+    let integer = INTEGER_TYPES.contains(&base);
+    (integer && keys.next().is_none()).then_some(key)
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("`{}`", identifier.replace('`', "``"))
+}
+
+/// Select list of the given columns, with binary values summarized as their size and first bytes.
+fn select_list(columns: &[&ColumnSchema], qualifier: Option<&str>) -> String {
+    columns
+        .iter()
+        .map(|column| {
+            let alias = quote_identifier(&column.name);
+            let name = match qualifier {
+                Some(qualifier) => format!("{}.{}", qualifier, alias),
+                None => alias.clone(),
+            };
+            if ColumnKind::of(&column.data_type) == ColumnKind::Binary {
+                format!(
+                    "CONCAT('0x', LOWER(HEX(LEFT({name}, {prefix}))), \
+                     IF(LENGTH({name}) > {prefix}, '...', ''), ' (', LENGTH({name}), ' bytes)') AS {alias}",
+                    prefix = BINARY_PREFIX_BYTES
+                )
+            } else if qualifier.is_some() {
+                format!("{} AS {}", name, alias)
+            } else {
+                name
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Decodes a row, shortening long values.
+fn shortened_row(row: MySqlRow) -> AppResult<Row> {
+    let mut row: Row = from_row(row)?;
+    row.values_mut()
+        .for_each(|value| shorten_json(value, MAX_VALUE_CHARS));
+    Ok(row)
+}
+
+/// A transaction held open on its own connection.
+/// Each statement runs under a savepoint, so a failed one is undone as a whole.
+/// MySQL has no `RETURNING`, so inserted rows are only counted.
+pub struct MySqlTransaction {
+    transaction: Transaction<'static, MySql>,
+}
+
+impl MySqlTransaction {
+    async fn touch(
+        &mut self,
+        statement: &str,
+        dml: &Dml,
+        table: Option<&TableSchema>,
+        limit: usize,
+    ) -> AppResult<Touched> {
+        let columns: Vec<&ColumnSchema> = table
+            .map(|table| table.columns.iter().collect())
+            .unwrap_or_default();
+        let key_columns: Vec<&ColumnSchema> = columns
+            .iter()
+            .copied()
+            .filter(|column| column.key.as_deref() == Some("PRI"))
+            .collect();
+
+        // Rows are read before the statement, with their keys as text to find them again afterwards.
+        let mut before = Vec::new();
+        let mut keys: Vec<Vec<String>> = Vec::new();
+        if let Some(target) = &dml.target
+            && table.is_some()
+            && dml.kind != DmlKind::Insert
+        {
+            let mut select = select_list(&columns, Some(target.qualifier()));
+            for (i, column) in key_columns.iter().enumerate() {
+                select += &format!(
+                    ", CAST({}.{} AS CHAR) AS {}",
+                    target.qualifier(),
+                    quote_identifier(&column.name),
+                    dml::key_alias(i)
+                );
+            }
+            let query = format!("{}\nLIMIT {}", target.select(&select), limit);
+            for row in sqlx::query(&query)
+                .fetch_all(&mut *self.transaction)
+                .await?
+            {
+                let mut row = shortened_row(row)?;
+                keys.push(dml::take_key(&mut row, key_columns.len()));
+                before.push(row);
+            }
+        }
+
+        let rows_affected = sqlx::query(statement)
+            .execute(&mut *self.transaction)
+            .await?
+            .rows_affected();
+
+        let mut after = Vec::new();
+        if let Some(table) = table
+            && dml.kind == DmlKind::Update
+            && !key_columns.is_empty()
+            && !keys.is_empty()
+        {
+            let tuple = format!("({})", vec!["?"; key_columns.len()].join(", "));
+            let query = format!(
+                "SELECT {} FROM {}.{} WHERE ({}) IN ({})",
+                select_list(&columns, None),
+                quote_identifier(&table.schema),
+                quote_identifier(&table.name),
+                key_columns
+                    .iter()
+                    .map(|column| format!("CAST({} AS CHAR)", quote_identifier(&column.name)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                vec![tuple; keys.len()].join(", "),
+            );
+            let mut query = sqlx::query(&query);
+            for text in keys.into_iter().flatten() {
+                query = query.bind(text);
+            }
+            for row in query.fetch_all(&mut *self.transaction).await? {
+                after.push(shortened_row(row)?);
+            }
+        }
+
+        Ok(Touched {
+            rows_affected,
+            before,
+            after,
+        })
     }
 }
 
+#[async_trait]
+impl WriteTransaction for MySqlTransaction {
+    async fn execute(
+        &mut self,
+        statement: &str,
+        dml: &Dml,
+        table: Option<&TableSchema>,
+        limit: usize,
+    ) -> AppResult<Touched> {
+        sqlx::query("SAVEPOINT airy_write")
+            .execute(&mut *self.transaction)
+            .await?;
+        match self.touch(statement, dml, table, limit).await {
+            Ok(touched) => {
+                sqlx::query("RELEASE SAVEPOINT airy_write")
+                    .execute(&mut *self.transaction)
+                    .await?;
+                Ok(touched)
+            }
+            Err(err) => {
+                sqlx::query("ROLLBACK TO SAVEPOINT airy_write")
+                    .execute(&mut *self.transaction)
+                    .await?;
+                Err(err)
+            }
+        }
+    }
+
+    async fn commit(self: Box<Self>) -> AppResult<()> {
+        Ok(self.transaction.commit().await?)
+    }
+
+    async fn rollback(self: Box<Self>) -> AppResult<()> {
+        Ok(self.transaction.rollback().await?)
+    }
+}
+
+/// Copies the schema into the scratch database, switches to it and applies and reverts a migration there.
+async fn try_in_scratch(
+    connection: &mut MySqlConnection,
+    scratch: &str,
+    schema: &DatabaseSchema,
+    up: &str,
+    down: &str,
+) -> AppResult<Vec<String>> {
+    connection
+        .execute(format!("USE {}", quote_identifier(scratch)).as_str())
+        .await?;
+    copy_schema(connection, schema).await?;
+    let before: Vec<(Vec<u8>,)> = sqlx::query_as(SCHEMA_OBJECTS)
+        .fetch_all(&mut *connection)
+        .await?;
+    for (direction, sql) in [("up", up), ("down", down)] {
+        in_scratch(connection, scratch).await?;
+        if let Err(err) = logging::record(sql, connection.execute(sql)).await {
+            return Ok(vec![format!("The {} migration fails: {}", direction, err)]);
+        }
+        in_scratch(connection, scratch).await?;
+    }
+    let after: Vec<(Vec<u8>,)> = sqlx::query_as(SCHEMA_OBJECTS)
+        .fetch_all(&mut *connection)
+        .await?;
+    let objects = |rows: Vec<(Vec<u8>,)>| -> Vec<String> {
+        rows.into_iter()
+            .map(|(object,)| String::from_utf8_lossy(&object).into_owned())
+            .collect()
+    };
+    Ok(migration::unrestored(&objects(before), &objects(after)))
+}
+
+/// Makes sure the connection still uses the scratch database, so that nothing is applied to the database itself.
+async fn in_scratch(connection: &mut MySqlConnection, scratch: &str) -> AppResult<()> {
+    let (database,): (Option<Vec<u8>>,) = sqlx::query_as("SELECT DATABASE()")
+        .fetch_one(&mut *connection)
+        .await?;
+    let database = database.map(|database| String::from_utf8_lossy(&database).into_owned());
+    if database.as_deref() != Some(scratch) {
+        return Err(AppError::InvalidArgument(format!(
+            "the migration was tried in {} rather than the scratch database {}",
+            database.as_deref().unwrap_or("no database"),
+            scratch
+        )));
+    }
+    Ok(())
+}
+
+/// Creates the tables with their foreign keys, the routines, the views and the triggers of a database
+/// in the database the connection uses, so that migrations touching any of them can be tried.
+/// Fails if an object can't be copied, as the migration couldn't be verified then.
+async fn copy_schema(connection: &mut MySqlConnection, schema: &DatabaseSchema) -> AppResult<()> {
+    let source = quote_identifier(&schema.database);
+    let qualified = |name: &str| format!("{}.{}", source, quote_identifier(name));
+
+    // Foreign key checks are off while the tables are created, so that they can reference tables created later.
+    connection.execute("SET FOREIGN_KEY_CHECKS = 0").await?;
+    for table in &schema.tables {
+        let show = format!("SHOW CREATE TABLE {}", qualified(&table.name));
+        copy_object(connection, &schema.database, &show, "Create Table").await?;
+    }
+    connection.execute("SET FOREIGN_KEY_CHECKS = 1").await?;
+
+    for routine in &schema.routines {
+        let show = format!("SHOW CREATE {} {}", routine.kind, qualified(&routine.name));
+        let column = format!("Create {}", capitalized(&routine.kind));
+        copy_object(connection, &schema.database, &show, &column).await?;
+    }
+
+    // Views may select from other views, so they're retried until the ones they depend on exist.
+    let mut views: Vec<&ViewSchema> = schema.views.iter().collect();
+    while !views.is_empty() {
+        let mut failed = Vec::new();
+        let mut last_error = None;
+        for view in &views {
+            let show = format!("SHOW CREATE VIEW {}", qualified(&view.name));
+            if let Err(err) = copy_object(connection, &schema.database, &show, "Create View").await
+            {
+                failed.push(*view);
+                last_error = Some(err);
+            }
+        }
+        if let Some(err) = last_error.filter(|_| failed.len() == views.len()) {
+            return Err(err);
+        }
+        views = failed;
+    }
+
+    for trigger in schema.tables.iter().flat_map(|table| &table.triggers) {
+        let show = format!("SHOW CREATE TRIGGER {}", qualified(&trigger.name));
+        copy_object(
+            connection,
+            &schema.database,
+            &show,
+            "SQL Original Statement",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Reads the definition of an object with a `SHOW CREATE` statement,
+/// and creates it without its definer and its qualification with `database`.
+async fn copy_object(
+    connection: &mut MySqlConnection,
+    database: &str,
+    show: &str,
+    column: &str,
+) -> AppResult<()> {
+    let copy_error = |err: &dyn Display| {
+        AppError::InvalidArgument(format!(
+            "the migration can't be verified, as `{}` can't be copied to a scratch database: {}",
+            show, err
+        ))
+    };
+    // `SHOW CREATE TRIGGER` can't be prepared, so the statement is sent as text.
+    let row = connection
+        .fetch_one(show)
+        .await
+        .map_err(|err| copy_error(&err))?;
+    let definition: Option<Vec<u8>> = row.try_get(column).map_err(|err| copy_error(&err))?;
+    let definition = definition.ok_or_else(|| copy_error(&"its definition isn't visible"))?;
+    let definition = unqualified(
+        &without_definer(&String::from_utf8_lossy(&definition)),
+        database,
+    );
+    connection
+        .execute(definition.as_str())
+        .await
+        .map_err(|err| copy_error(&err))?;
+    Ok(())
+}
+
+/// Removes qualifications of names with `database`, quoted or not.
+fn unqualified(sql: &str, database: &str) -> String {
+    // ASCII lowercase keeps byte offsets the same.
+    let lower = sql.to_ascii_lowercase();
+    let prefixes = [
+        format!("{}.", quote_identifier(database)),
+        format!("{}.", database),
+    ]
+    .map(|prefix| prefix.to_ascii_lowercase());
+    let mut result = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        let at = sql.len() - rest.len();
+        let after_name = sql[..at].ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '$');
+        if !after_name
+            && let Some(prefix) = prefixes
+                .iter()
+                .find(|prefix| lower[at..].starts_with(prefix.as_str()))
+        {
+            rest = &rest[prefix.len()..];
+            continue;
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+/// Removes the `DEFINER` clause of a `SHOW CREATE` statement, as setting another definer takes a privilege.
+fn without_definer(sql: &str) -> String {
+    const DEFINER: &str = " DEFINER=";
+    let Some(start) = sql.find(DEFINER) else {
+        return sql.into();
+    };
+    let rest = &sql[start + DEFINER.len()..];
+    // The definer is `user`@`host`, and quoted parts may hold spaces.
+    let mut quoted = false;
+    let end = rest
+        .find(|c: char| {
+            if c == '`' {
+                quoted = !quoted;
+            }
+            c == ' ' && !quoted
+        })
+        .unwrap_or(rest.len());
+    format!("{}{}", &sql[..start], &rest[end..])
+}
+
+/// `FUNCTION` as `Function`, as `SHOW CREATE FUNCTION` names its column.
+fn capitalized(word: &str) -> String {
+    let lower = word.to_lowercase();
+    let mut chars = lower.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
 #[async_trait]
 impl Manager for MySqlManager {
-    fn system_prompt(&self) -> &str {
-        &self.system_prompt
+    fn dialect(&self) -> &str {
+        "MySQL"
     }
 
-    async fn get_database_schema(&self) -> AppResult<CallToolResult> {
-        let (database_name,): (String,) = sqlx::query_as("SELECT DATABASE()")
-            .fetch_one(&self.pool)
-            .await?;
+    fn server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    async fn get_schema(&self) -> AppResult<DatabaseSchema> {
+        self.schema_cache
+            .get(self.fingerprint(), async {
+                let (database,): (String,) = sqlx::query_as("SELECT DATABASE()")
+                    .fetch_one(&self.pool)
+                    .await?;
+                let (tables, views) = self.load_relations(&database, None).await?;
+                let routines = self.load_routines(&database).await?;
+                Ok(DatabaseSchema {
+                    database,
+                    tables,
+                    views,
+                    routines,
+                })
+            })
+            .await
+    }
+
+    async fn refresh_schema(&self) -> AppResult<DatabaseSchema> {
+        self.schema_cache.invalidate().await;
+        self.get_schema().await
+    }
 
-        let tables: Vec<(Vec<u8>,)> = sqlx::query_as(
+    async fn get_table(&self, schema: &str, name: &str) -> AppResult<Option<TableSchema>> {
+        let row_estimate: Option<(Option<i64>,)> = sqlx::query_as(
             "
-                SELECT table_name
+                SELECT CAST(table_rows AS SIGNED)
                 FROM information_schema.tables
-                WHERE table_schema = ? AND table_type = 'BASE TABLE'
+                WHERE table_schema = ? AND table_name = ? AND table_type = 'BASE TABLE'
+            ",
+        )
+        .bind(schema)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some((row_estimate,)) = row_estimate else {
+            return Ok(None);
+        };
+        let Some(mut table) = self.load_relations(schema, Some(name)).await?.0.pop() else {
+            return Ok(None);
+        };
+
+        let index_rows: Vec<(Vec<u8>, i64, Vec<u8>)> = sqlx::query_as(
+            "
+                SELECT index_name, CAST(non_unique AS SIGNED), column_name
+                FROM information_schema.statistics
+                WHERE table_schema = ? AND table_name = ?
+                ORDER BY index_name, seq_in_index
             ",
         )
-        .bind(&database_name)
+        .bind(schema)
+        .bind(name)
         .fetch_all(&self.pool)
         .await?;
 
-        if tables.is_empty() {
-            return Ok(CallToolResult::success(vec![Content::text(format!(
-                "No tables found in database '{}'.",
-                database_name,
-            ))]));
+        let mut indexes: Vec<IndexSchema> = Vec::new();
+        for (index_name, non_unique, column_name) in index_rows {
+            let index_name = String::from_utf8_lossy(&index_name).into_owned();
+            let column_name = String::from_utf8_lossy(&column_name).into_owned();
+            match indexes.last_mut() {
+                Some(index) if index.name == index_name => index.columns.push(column_name),
+                _ => indexes.push(IndexSchema {
+                    primary: index_name == "PRIMARY",
+                    name: index_name,
+                    columns: vec![column_name],
+                    unique: non_unique == 0,
+                }),
+            }
         }
 
-        let mut schema_desc = format!("Schema for database '{}':\n\n", database_name);
+        table.indexes = indexes;
+        table.row_estimate = row_estimate;
+        Ok(Some(table))
+    }
+
+    async fn sample_rows(
+        &self,
+        table: &TableSchema,
+        columns: &[&ColumnSchema],
+        limit: usize,
+    ) -> AppResult<Vec<Row>> {
+        let columns: Vec<&ColumnSchema> = if columns.is_empty() {
+            table.columns.iter().collect()
+        } else {
+            columns.to_vec()
+        };
+        let select = format!(
+            "SELECT {} FROM {}.{}",
+            select_list(&columns, None),
+            quote_identifier(&table.schema),
+            quote_identifier(&table.name),
+        );
+
+        // MySQL has no TABLESAMPLE, and sorting a large table at random scans all of it.
+        // Large tables are sampled by looking up random values of an integer primary key instead,
+        // or by picking rows at random while scanning when there is none.
+        let query = match table.row_estimate {
+            None => format!("{} LIMIT {}", select, limit),
+            Some(rows) if rows <= ROW_BUDGET as i64 => {
+                format!("{} ORDER BY RAND() LIMIT {}", select, limit)
+            }
+            Some(rows) => match integer_key(table) {
+                Some(key) => {
+                    let key = quote_identifier(&key.name);
+                    let range = format!(
+                        "SELECT CAST(MIN({key}) AS SIGNED), CAST(MAX({key}) AS SIGNED) FROM {}.{}",
+                        quote_identifier(&table.schema),
+                        quote_identifier(&table.name),
+                    );
+                    let (low, high): (Option<i64>, Option<i64>) =
+                        logging::record(&range, sqlx::query_as(&range).fetch_one(&self.pool))
+                            .await?;
+                    let (Some(low), Some(high)) = (low, high) else {
+                        return Ok(Vec::new());
+                    };
+                    let span = high.abs_diff(low).saturating_add(1);
+                    let random = RandomState::new();
+                    (0..limit)
+                        .map(|i| {
+                            let value = low.wrapping_add((random.hash_one(i) % span) as i64);
+                            format!("({select} WHERE {key} >= {value} ORDER BY {key} LIMIT 1)")
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" UNION ALL ")
+                }
+                None => format!(
+                    "{} WHERE RAND() < {} LIMIT {}",
+                    select,
+                    sample_fraction(Some(rows), limit).unwrap_or(1.0),
+                    limit
+                ),
+            },
+        };
+
+        let mut rows: Vec<Row> = Vec::new();
+        for row in logging::record(&query, sqlx::query(&query).fetch_all(&self.pool)).await? {
+            let row = shortened_row(row)?;
+            // Random keys in the same gap between keys land on the same row.
+            if !rows.contains(&row) {
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
 
-        for (table_name_bin,) in tables {
-            let table_name = if let Ok(table_name) = String::from_utf8(table_name_bin) {
-                table_name
+    async fn profile_column(
+        &self,
+        table: &TableSchema,
+        column: &ColumnSchema,
+        budget: usize,
+    ) -> AppResult<ColumnProfile> {
+        let kind = ColumnKind::of(&column.data_type);
+        // MySQL has no TABLESAMPLE, so rows are picked at random while scanning.
+        let sampling = sample_fraction(table.row_estimate, budget)
+            .map(|fraction| format!(" WHERE RAND() < {}", fraction))
+            .unwrap_or_default();
+        let sample = format!(
+            "(SELECT {} AS v FROM {}.{}{} LIMIT {}) s",
+            quote_identifier(&column.name),
+            quote_identifier(&table.schema),
+            quote_identifier(&table.name),
+            sampling,
+            budget
+        );
+        // Adding a float zero turns numbers into doubles.
+        let number = match kind {
+            ColumnKind::Numeric => "v + 0E0",
+            ColumnKind::Temporal => "TO_SECONDS(v) + 0E0",
+            _ => "NULL",
+        };
+        let query = format!(
+            "SELECT COUNT(*), COUNT(v), COUNT(DISTINCT v), {}, {}, MIN({number}), MAX({number}) FROM {}",
+            if kind.is_ordered() {
+                "CAST(MIN(v) AS CHAR), CAST(MAX(v) AS CHAR)"
             } else {
-                continue;
-            };
+                "NULL, NULL"
+            },
+            if kind == ColumnKind::Text {
+                "CAST(MIN(CHAR_LENGTH(v)) AS SIGNED), CAST(MAX(CHAR_LENGTH(v)) AS SIGNED), AVG(CHAR_LENGTH(v)) + 0E0"
+            } else {
+                "NULL, NULL, NULL"
+            },
+            sample,
+        );
+        let (
+            rows,
+            values,
+            distinct_count,
+            min,
+            max,
+            min_length,
+            max_length,
+            average_length,
+            low,
+            high,
+        ): ProfileRow =
+            logging::record(&query, sqlx::query_as(&query).fetch_one(&self.pool)).await?;
 
-            schema_desc += &format!("Table: {}\n", table_name);
+        let query = format!(
+            "SELECT CAST(v AS CHAR), COUNT(*) FROM {} WHERE v IS NOT NULL GROUP BY v ORDER BY 2 DESC, 1 LIMIT {}",
+            sample, TOP_VALUES
+        );
+        let top_rows: Vec<(Vec<u8>, i64)> =
+            logging::record(&query, sqlx::query_as(&query).fetch_all(&self.pool)).await?;
 
-            let columns_rows: Vec<(
-                String,
-                Vec<u8>,
-                String,
-                Vec<u8>,
-                Option<String>,
-                Option<String>,
-            )> = sqlx::query_as(
+        let mut histogram = Vec::new();
+        if let (Some(low), Some(high)) = (low, high)
+            && low < high
+        {
+            // The maximum falls just past the last bucket, so it is put back into it.
+            let query = format!(
                 "
-                    SELECT column_name, column_type, is_nullable, column_key, column_default, extra
-                    FROM information_schema.columns
-                    WHERE table_schema = ? AND table_name = ?
-                    ORDER BY ordinal_position
+                    SELECT b, COUNT(*), CAST(MIN(v) AS CHAR), CAST(MAX(v) AS CHAR)
+                    FROM (
+                        SELECT v, CAST(LEAST(FLOOR(({} - ?) / ? * ?) + 1, ?) AS SIGNED) AS b
+                        FROM {}
+                        WHERE v IS NOT NULL
+                    ) t
+                    GROUP BY b
+                    ORDER BY b
                 ",
+                number, sample
+            );
+            let bucket_rows: Vec<(i64, i64, Vec<u8>, Vec<u8>)> = logging::record(
+                &query,
+                sqlx::query_as(&query)
+                    .bind(low)
+                    .bind(high - low)
+                    .bind(HISTOGRAM_BUCKETS as i64)
+                    .bind(HISTOGRAM_BUCKETS as i64)
+                    .fetch_all(&self.pool),
             )
-            .bind(&database_name)
-            .bind(&table_name)
-            .fetch_all(&self.pool)
             .await?;
+            histogram = bucket_rows
+                .into_iter()
+                .map(|(_, count, min, max)| Bucket {
+                    min: String::from_utf8_lossy(&min).into_owned(),
+                    max: String::from_utf8_lossy(&max).into_owned(),
+                    count,
+                })
+                .collect();
+        }
+
+        Ok(ColumnProfile {
+            column: column.name.clone(),
+            data_type: column.data_type.clone(),
+            rows,
+            sampled: !sampling.is_empty() || rows == budget as i64,
+            null_ratio: if rows > 0 {
+                (rows - values) as f64 / rows as f64
+            } else {
+                0.0
+            },
+            distinct_count: Some(distinct_count),
+            min: min.map(|min| String::from_utf8_lossy(&min).into_owned()),
+            max: max.map(|max| String::from_utf8_lossy(&max).into_owned()),
+            top_values: top_rows
+                .into_iter()
+                .map(|(value, count)| ValueCount {
+                    value: shorten(&String::from_utf8_lossy(&value), MAX_VALUE_CHARS),
+                    count,
+                })
+                .collect(),
+            length: min_length
+                .zip(max_length)
+                .zip(average_length)
+                .map(|((min, max), average)| LengthStats { min, max, average }),
+            histogram,
+        })
+    }
+
+    async fn search_value(
+        &self,
+        table: &TableSchema,
+        words: &[String],
+        limit: usize,
+        timeout: Duration,
+    ) -> AppResult<TableSearch> {
+        let mut columns: Vec<&ColumnSchema> = table
+            .columns
+            .iter()
+            .filter(|column| ColumnKind::of(&column.data_type) == ColumnKind::Text)
+            .collect();
+        if columns.is_empty() {
+            return Ok(TableSearch::NoTextColumns);
+        }
+
+        let (row_estimate,): (Option<i64>,) = sqlx::query_as(
+            "
+                SELECT CAST(table_rows AS SIGNED)
+                FROM information_schema.tables
+                WHERE table_schema = ? AND table_name = ?
+            ",
+        )
+        .bind(&table.schema)
+        .bind(&table.name)
+        .fetch_one(&self.pool)
+        .await?;
+        // `MATCH ... AGAINST` needs the exact columns of a FULLTEXT index, so only single-column ones are used.
+        let full_text: Vec<(Vec<u8>,)> = sqlx::query_as(
+            "
+                SELECT MIN(column_name)
+                FROM information_schema.statistics
+                WHERE table_schema = ? AND table_name = ? AND index_type = 'FULLTEXT'
+                GROUP BY index_name
+                HAVING COUNT(*) = 1
+            ",
+        )
+        .bind(&table.schema)
+        .bind(&table.name)
+        .fetch_all(&self.pool)
+        .await?;
+        let has_full_text = |column: &ColumnSchema| {
+            full_text
+                .iter()
+                .any(|(name,)| String::from_utf8_lossy(name) == column.name)
+        };
+        if row_estimate.is_some_and(|rows| rows > SCAN_ROWS) {
+            columns.retain(|column| has_full_text(column));
+            if columns.is_empty() {
+                return Ok(TableSearch::TooLarge);
+            }
+        }
+
+        // Placeholders are positional, so arguments are collected in the order conditions are written.
+        let mut arguments: Vec<String> = Vec::new();
+        let mut condition = |column: &ColumnSchema, word: &str| {
+            let name = quote_identifier(&column.name);
+            if has_full_text(column) {
+                arguments.push(format!("\"{}\"", word.replace('"', "")));
+                format!("MATCH({}) AGAINST (? IN BOOLEAN MODE)", name)
+            } else {
+                arguments.push(value_search::contains_pattern(word));
+                format!("{} LIKE ?", name)
+            }
+        };
+        let mut selected: Vec<String> = table
+            .columns
+            .iter()
+            .filter(|column| column.key.as_deref() == Some("PRI"))
+            .map(|column| quote_identifier(&column.name))
+            .collect();
+        for (i, column) in columns.iter().enumerate() {
+            let conditions: Vec<String> =
+                words.iter().map(|word| condition(column, word)).collect();
+            // `MATCH` gives a relevance rather than 0 or 1.
+            selected.push(format!(
+                "IF({}, 1, 0) AS {}",
+                conditions.join(" OR "),
+                value_search::match_alias(i)
+            ));
+        }
+        let mut every_word = Vec::with_capacity(words.len());
+        for word in words {
+            let conditions: Vec<String> = columns
+                .iter()
+                .map(|column| condition(column, word))
+                .collect();
+            every_word.push(format!("({})", conditions.join(" OR ")));
+        }
+        let query = format!(
+            "SELECT /*+ MAX_EXECUTION_TIME({}) */ {} FROM {}.{} WHERE {} LIMIT {}",
+            timeout.as_millis().max(1),
+            selected.join(", "),
+            quote_identifier(&table.schema),
+            quote_identifier(&table.name),
+            every_word.join(" AND "),
+            limit
+        );
 
-            for (column_name, column_type, is_nullable, column_key, column_default, extra) in
-                columns_rows
+        let mut statement = sqlx::query(&query);
+        for argument in &arguments {
+            statement = statement.bind(argument);
+        }
+        let rows = match logging::record(&query, statement.fetch_all(&self.pool)).await {
+            Ok(rows) => rows,
+            Err(sqlx::Error::Database(err))
+                if err
+                    .try_downcast_ref::<MySqlDatabaseError>()
+                    .is_some_and(|err| err.number() == QUERY_TIMEOUT) =>
             {
-                let mut col_desc = format!(
-                    "  - {}: {} ",
-                    column_name,
-                    String::from_utf8_lossy(&column_type),
-                );
-                if is_nullable == "NO" {
-                    col_desc += "| NOT NULL";
-                }
-                let key = String::from_utf8_lossy(&column_key);
-                if !key.is_empty() {
-                    col_desc += &format!("| KEY: {}", key);
-                }
-                if let Some(column_default) = column_default.filter(|s| !s.is_empty()) {
-                    col_desc += &format!("| DEFAULT: {}", column_default);
-                }
-                if let Some(extra) = extra.filter(|s| !s.is_empty()) {
-                    col_desc += &format!("| EXTRA: {}", extra);
+                return Ok(TableSearch::TimedOut);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut matches = Vec::with_capacity(rows.len());
+        for row in rows {
+            matches.push(value_search::row_match(from_row(row)?, &columns));
+        }
+        Ok(TableSearch::Searched(matches))
+    }
+
+    async fn advise_indexes(&self, queries: &[String], limit: usize) -> AppResult<IndexAdvice> {
+        let mut advice = if queries.is_empty() {
+            IndexAdvice::from_history("performance_schema", self.slow_queries(limit).await)
+        } else {
+            IndexAdvice::for_queries(queries)
+        };
+        let schema = self.get_schema().await?;
+        let queries: Vec<String> = advice
+            .queries
+            .iter()
+            .map(|query| query.query.clone())
+            .collect();
+
+        for (i, query) in queries.iter().enumerate() {
+            let plan = match self.explain(query).await {
+                Ok(plan) => plan,
+                Err(err) => {
+                    advice
+                        .notes
+                        .push(format!("Query {} can't be explained: {}", i + 1, err));
+                    continue;
                 }
-                schema_desc += &col_desc;
-                schema_desc += "\n";
+            };
+            for scan in index_advice::mysql_scans(&plan) {
+                let Some(table) = index_advice::resolve_table(&schema, &scan) else {
+                    continue;
+                };
+                let Some(table) = self.get_table(&table.schema, &table.name).await? else {
+                    continue;
+                };
+                let target = quote_identifier(&table.name);
+                let label = schema.qualified_name(&table);
+                advice.add_scan(&table, &label, &target, quote_identifier, &scan, i + 1);
             }
+        }
+        if !advice.candidates.is_empty() {
+            advice.notes.push(
+                "MySQL can't plan with hypothetical indexes, so the impact of the indexes isn't estimated."
+                    .into(),
+            );
+        }
+        Ok(advice)
+    }
 
-            schema_desc += "\n\n";
+    async fn try_migration(&self, up: &str, down: &str) -> AppResult<Vec<String>> {
+        // DDL commits implicitly, so the migration is tried on an empty copy of the schema in a scratch database.
+        // The connection switches databases, so it's closed afterwards rather than returned to the pool.
+        let schema = self.get_schema().await?;
+        if [up, down]
+            .iter()
+            .any(|sql| unqualified(sql, &schema.database) != *sql)
+        {
+            // It would be applied to the database itself rather than the scratch copy.
+            return Ok(vec![format!(
+                "Names are qualified with the database `{}`, leave them unqualified.",
+                schema.database
+            )]);
         }
+        let scratch = format!(
+            "airy_scratch_{:x}",
+            RandomState::new().hash_one(std::process::id())
+        );
+        let mut connection = self.pool.acquire().await?.detach();
+        connection
+            .execute(format!("CREATE DATABASE {}", quote_identifier(&scratch)).as_str())
+            .await?;
+
+        let result = try_in_scratch(&mut connection, &scratch, &schema, up, down).await;
 
-        Ok(CallToolResult::success(vec![Content::text(schema_desc)]))
+        connection
+            .execute(format!("DROP DATABASE {}", quote_identifier(&scratch)).as_str())
+            .await?;
+        result
     }
 
-    async fn execute_query(&self, params: ExecuteQueryParams) -> AppResult<CallToolResult> {
-        let query = params.query.trim();
-        // if !query.starts_with("SELECT") {
-        //     return Ok(CallToolResult::error(vec![Content::text(
-        //         "Error: Only SELECT queries are allowed with this tool for safety.",
-        //     )]));
-        // }
+    async fn begin_write(&self) -> AppResult<Box<dyn WriteTransaction>> {
+        Ok(Box::new(MySqlTransaction {
+            transaction: self.pool.begin().await?,
+        }))
+    }
 
-        let mut rows: Vec<serde_json::Value> = Vec::new();
-        for row in sqlx::query(query).fetch_all(&self.pool).await? {
-            rows.push(from_row(row)?);
+    async fn fetch_rows(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        context: Option<&RequestContext<RoleServer>>,
+    ) -> AppResult<Option<Vec<Row>>> {
+        let mut connection = self.pool.acquire().await?;
+        if self.read_only {
+            (&mut *connection)
+                .execute("START TRANSACTION READ ONLY")
+                .await?;
         }
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&rows)?,
-        )]))
+        let (connection_id,): (i64,) = sqlx::query_as("SELECT CAST(CONNECTION_ID() AS SIGNED)")
+            .fetch_one(&mut *connection)
+            .await?;
+
+        let rows = sqlx::query(query.trim())
+            .fetch(&mut *connection)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|row| -> AppResult<Row> { Ok(from_row(row?)?) });
+        let cancel = async {
+            sqlx::raw_sql(&format!("KILL QUERY {}", connection_id))
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        };
+
+        let rows = progress::collect_rows(rows, context, cancel).await;
+        if self.read_only {
+            // Settings changed in the transaction are reverted with it.
+            (&mut *connection).execute("ROLLBACK").await?;
+        }
+        rows
     }
 }
 
-#[tool(tool_box)]
-impl ServerHandler for MySqlManager {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                // .enable_prompts()
-                // .enable_resources()
-                .enable_tools()
-                .build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some(self.system_prompt().into()),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_definitions_into_another_database() {
+        assert_eq!(
+            unqualified(
+                "select `shop`.`t`.`id` AS `id` from `shop`.`t` join Shop.u on myshop.x = 1",
+                "shop"
+            ),
+            "select `t`.`id` AS `id` from `t` join u on myshop.x = 1"
+        );
+        assert_eq!(
+            without_definer(
+                "CREATE DEFINER=`app user`@`%` TRIGGER `t_bi` BEFORE INSERT ON `t` FOR EACH ROW SET NEW.a = 1"
+            ),
+            "CREATE TRIGGER `t_bi` BEFORE INSERT ON `t` FOR EACH ROW SET NEW.a = 1"
+        );
+        assert_eq!(capitalized("PROCEDURE"), "Procedure");
     }
 }
@@ -1,169 +1,1331 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
 use async_trait::async_trait;
-use rmcp::{
-    Error as McpError, ServerHandler,
-    model::{
-        CallToolResult, Content, Implementation, ProtocolVersion, ServerCapabilities, ServerInfo,
-    },
-    tool,
-};
+use futures::StreamExt;
+use rmcp::{RoleServer, service::RequestContext};
 use serde_sqlx::from_pg_row;
-use sqlx::{Pool, postgres::Postgres};
+use sqlx::{
+    Connection, Executor, Pool, Transaction,
+    postgres::{PgConnection, PgRow, Postgres},
+};
 
 use crate::{
-    error::AppResult,
-    tool::{ExecuteQueryParams, Manager},
+    dml::{self, Dml, DmlKind},
+    error::{AppError, AppResult},
+    index_advice::{self, CostEstimate, IndexAdvice, SlowQuery},
+    migration,
+    profile::{
+        BINARY_PREFIX_BYTES, Bucket, ColumnKind, ColumnProfile, HISTOGRAM_BUCKETS, LengthStats,
+        MAX_VALUE_CHARS, ROW_BUDGET, TOP_VALUES, ValueCount, sample_fraction,
+    },
+    schema::{
+        CheckSchema, ColumnSchema, DatabaseSchema, ForeignKeySchema, IndexSchema, RoutineSchema,
+        TableSchema, TriggerSchema, ViewSchema,
+    },
+    tool::{Manager, Row, Touched, WriteTransaction, logging, progress, schema_cache::SchemaCache},
+    utility::{shorten, shorten_json},
+    value_search::{self, SCAN_ROWS, TableSearch},
 };
 
 #[derive(Clone)]
 pub struct PostgresManager {
-    system_prompt: String,
     pool: Pool<Postgres>,
+    server_version: String,
+    schema_cache: Arc<SchemaCache>,
+    /// Runs fetched queries in read-only transactions, so that they can't write even by changing settings.
+    read_only: bool,
 }
 
 impl PostgresManager {
-    pub fn new(pool: Pool<Postgres>, system_prompt: String) -> Self {
-        Self {
+    pub async fn new(pool: Pool<Postgres>, read_only: bool) -> AppResult<Self> {
+        let (server_version,): (String,) =
+            sqlx::query_as("SELECT current_setting('server_version')")
+                .fetch_one(&pool)
+                .await?;
+
+        Ok(Self {
             pool,
-            system_prompt,
+            server_version,
+            schema_cache: Default::default(),
+            read_only,
+        })
+    }
+
+    /// Loads all tables and views, or a single table, with their columns, constraints and triggers.
+    /// Takes a fixed number of queries however many tables there are.
+    async fn load_relations(
+        &self,
+        table: Option<(&str, &str)>,
+    ) -> AppResult<(Vec<TableSchema>, Vec<ViewSchema>)> {
+        let (schema_filter, name_filter) = table.unzip();
+        let column_rows: Vec<ColumnRow> = sqlx::query_as(
+            "
+                SELECT
+                    n.nspname::text,
+                    c.relname::text,
+                    c.relkind::text,
+                    obj_description(c.oid, 'pg_class'),
+                    CASE WHEN c.relkind IN ('v', 'm') THEN pg_get_viewdef(c.oid) END,
+                    a.attname::text,
+                    format_type(a.atttypid, a.atttypmod),
+                    a.attnotnull,
+                    (
+                        SELECT CASE con.contype WHEN 'p' THEN 'PRI' WHEN 'u' THEN 'UNI' ELSE 'MUL' END
+                        FROM pg_constraint con
+                        WHERE con.conrelid = a.attrelid
+                            AND a.attnum = ANY(con.conkey)
+                            AND con.contype IN ('p', 'u', 'f')
+                        ORDER BY con.contype = 'p' DESC, con.contype = 'u' DESC
+                        LIMIT 1
+                    ),
+                    CASE WHEN a.attgenerated = '' THEN pg_get_expr(d.adbin, d.adrelid) END,
+                    CASE
+                        WHEN a.attidentity <> '' THEN 'identity'
+                        WHEN a.attgenerated <> '' THEN 'generated'
+                    END,
+                    CASE WHEN a.attgenerated <> '' THEN pg_get_expr(d.adbin, d.adrelid) END,
+                    col_description(a.attrelid, a.attnum)
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                LEFT JOIN pg_attribute a
+                    ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+                LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+                WHERE c.relkind IN ('r', 'p', 'v', 'm')
+                    AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+                    AND n.nspname NOT LIKE 'pg_toast%'
+                    AND ($1::text IS NULL OR (n.nspname = $1 AND c.relname = $2 AND c.relkind IN ('r', 'p')))
+                ORDER BY n.nspname, c.relname, a.attnum
+            ",
+        )
+        .bind(schema_filter)
+        .bind(name_filter)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tables: Vec<TableSchema> = Vec::new();
+        let mut views: Vec<ViewSchema> = Vec::new();
+        for (
+            relation_schema,
+            relation_name,
+            kind,
+            relation_comment,
+            definition,
+            column_name,
+            data_type,
+            not_null,
+            key,
+            column_default,
+            extra,
+            generated,
+            comment,
+        ) in column_rows
+        {
+            let is_view = kind == "v" || kind == "m";
+            if is_view
+                && !views.last().is_some_and(|view| {
+                    view.schema == relation_schema && view.name == relation_name
+                })
+            {
+                views.push(ViewSchema {
+                    schema: relation_schema,
+                    name: relation_name,
+                    comment: relation_comment,
+                    materialized: kind == "m",
+                    columns: Vec::new(),
+                    definition,
+                });
+            } else if !is_view
+                && !tables.last().is_some_and(|table| {
+                    table.schema == relation_schema && table.name == relation_name
+                })
+            {
+                tables.push(TableSchema {
+                    schema: relation_schema,
+                    name: relation_name,
+                    comment: relation_comment,
+                    columns: Vec::new(),
+                    indexes: Vec::new(),
+                    foreign_keys: Vec::new(),
+                    checks: Vec::new(),
+                    triggers: Vec::new(),
+                    row_estimate: None,
+                });
+            }
+            // Tables without columns have a single row without a column.
+            let (Some(column_name), Some(data_type)) = (column_name, data_type) else {
+                continue;
+            };
+            let column = ColumnSchema {
+                name: column_name,
+                data_type,
+                nullable: !not_null.unwrap_or(false),
+                key,
+                default: column_default.filter(|s| !s.is_empty()),
+                extra,
+                generated,
+                comment,
+            };
+            let columns = if is_view {
+                views.last_mut().map(|view| &mut view.columns)
+            } else {
+                tables.last_mut().map(|table| &mut table.columns)
+            };
+            if let Some(columns) = columns {
+                columns.push(column);
+            }
+        }
+
+        let foreign_key_rows: Vec<ForeignKeyRow> = sqlx::query_as(
+            "
+                SELECT
+                    n.nspname::text,
+                    c.relname::text,
+                    con.conname::text,
+                    fn.nspname::text,
+                    fc.relname::text,
+                    ARRAY(
+                        SELECT a.attname::text
+                        FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    ),
+                    ARRAY(
+                        SELECT a.attname::text
+                        FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    )
+                FROM pg_constraint con
+                JOIN pg_class c ON c.oid = con.conrelid
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                JOIN pg_class fc ON fc.oid = con.confrelid
+                JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+                WHERE con.contype = 'f'
+                    AND ($1::text IS NULL OR (n.nspname = $1 AND c.relname = $2))
+                ORDER BY n.nspname, c.relname, con.conname
+            ",
+        )
+        .bind(schema_filter)
+        .bind(name_filter)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let positions: HashMap<(String, String), usize> = tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| ((table.schema.clone(), table.name.clone()), i))
+            .collect();
+        for (
+            table_schema,
+            table_name,
+            name,
+            referenced_schema,
+            referenced_table,
+            columns,
+            referenced_columns,
+        ) in foreign_key_rows
+        {
+            if let Some(&i) = positions.get(&(table_schema, table_name)) {
+                tables[i].foreign_keys.push(ForeignKeySchema {
+                    name,
+                    columns,
+                    referenced_schema,
+                    referenced_table,
+                    referenced_columns,
+                });
+            }
+        }
+
+        let check_rows: Vec<(String, String, String, String)> = sqlx::query_as(
+            "
+                SELECT n.nspname::text, c.relname::text, con.conname::text, pg_get_expr(con.conbin, con.conrelid)
+                FROM pg_constraint con
+                JOIN pg_class c ON c.oid = con.conrelid
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE con.contype = 'c'
+                    AND ($1::text IS NULL OR (n.nspname = $1 AND c.relname = $2))
+                ORDER BY n.nspname, c.relname, con.conname
+            ",
+        )
+        .bind(schema_filter)
+        .bind(name_filter)
+        .fetch_all(&self.pool)
+        .await?;
+        for (table_schema, table_name, name, expression) in check_rows {
+            if let Some(&i) = positions.get(&(table_schema, table_name)) {
+                tables[i].checks.push(CheckSchema { name, expression });
+            }
+        }
+
+        let trigger_rows: Vec<TriggerRow> = sqlx::query_as(
+            "
+                SELECT
+                    event_object_schema::text,
+                    event_object_table::text,
+                    trigger_name::text,
+                    action_timing::text,
+                    array_agg(event_manipulation::text ORDER BY event_manipulation),
+                    action_statement::text
+                FROM information_schema.triggers
+                WHERE $1::text IS NULL OR (event_object_schema = $1 AND event_object_table = $2)
+                GROUP BY event_object_schema, event_object_table, trigger_name, action_timing, action_statement
+                ORDER BY event_object_schema, event_object_table, trigger_name
+            ",
+        )
+        .bind(schema_filter)
+        .bind(name_filter)
+        .fetch_all(&self.pool)
+        .await?;
+        for (table_schema, table_name, name, timing, events, action) in trigger_rows {
+            if let Some(&i) = positions.get(&(table_schema, table_name)) {
+                tables[i].triggers.push(TriggerSchema {
+                    name,
+                    timing,
+                    events,
+                    action,
+                });
+            }
         }
+
+        Ok((tables, views))
+    }
+
+    /// Loads the signatures of functions and procedures, leaving out those installed by extensions.
+    async fn load_routines(&self) -> AppResult<Vec<RoutineSchema>> {
+        let routine_rows: Vec<RoutineRow> = sqlx::query_as(
+            "
+                SELECT
+                    n.nspname::text,
+                    p.proname::text,
+                    CASE p.prokind WHEN 'p' THEN 'PROCEDURE' ELSE 'FUNCTION' END,
+                    pg_get_function_arguments(p.oid),
+                    CASE WHEN p.prokind = 'f' THEN pg_get_function_result(p.oid) END,
+                    obj_description(p.oid, 'pg_proc')
+                FROM pg_proc p
+                JOIN pg_namespace n ON n.oid = p.pronamespace
+                WHERE p.prokind IN ('f', 'p')
+                    AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+                    AND n.nspname NOT LIKE 'pg_toast%'
+                    AND NOT EXISTS (
+                        SELECT 1 FROM pg_depend d
+                        WHERE d.classid = 'pg_proc'::regclass AND d.objid = p.oid AND d.deptype = 'e'
+                    )
+                ORDER BY n.nspname, p.proname, p.oid
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(routine_rows
+            .into_iter()
+            .map(
+                |(schema, name, kind, arguments, returns, comment)| RoutineSchema {
+                    schema,
+                    name,
+                    kind,
+                    arguments,
+                    returns,
+                    comment,
+                },
+            )
+            .collect())
+    }
+
+    /// Digest of table, view, column, constraint, trigger and routine definitions,
+    /// to tell whether the cached schema is current.
+    async fn fingerprint(&self) -> AppResult<String> {
+        let (fingerprint,): (String,) = sqlx::query_as(
+            "
+                WITH tables AS (
+                    SELECT c.oid, c.relname, c.relkind, c.relfilenode
+                    FROM pg_class c
+                    JOIN pg_namespace n ON n.oid = c.relnamespace
+                    WHERE c.relkind IN ('r', 'p', 'v', 'm')
+                        AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+                        AND n.nspname NOT LIKE 'pg_toast%'
+                )
+                SELECT md5(concat_ws(
+                    '|',
+                    (
+                        SELECT string_agg(
+                            concat_ws(
+                                ':', t.oid, t.relname, t.relfilenode, obj_description(t.oid, 'pg_class'),
+                                CASE WHEN t.relkind IN ('v', 'm') THEN md5(pg_get_viewdef(t.oid)) END
+                            ),
+                            ',' ORDER BY t.oid
+                        )
+                        FROM tables t
+                    ),
+                    (
+                        SELECT string_agg(
+                            concat_ws(
+                                ':', a.attrelid, a.attnum, a.attname, a.atttypid, a.atttypmod,
+                                a.attnotnull, col_description(a.attrelid, a.attnum)
+                            ),
+                            ',' ORDER BY a.attrelid, a.attnum
+                        )
+                        FROM pg_attribute a
+                        JOIN tables t ON t.oid = a.attrelid
+                        WHERE a.attnum > 0 AND NOT a.attisdropped
+                    ),
+                    (
+                        SELECT string_agg(con.oid::text, ',' ORDER BY con.oid)
+                        FROM pg_constraint con
+                        JOIN tables t ON t.oid = con.conrelid
+                    ),
+                    (
+                        SELECT string_agg(tg.oid::text, ',' ORDER BY tg.oid)
+                        FROM pg_trigger tg
+                        JOIN tables t ON t.oid = tg.tgrelid
+                        WHERE NOT tg.tgisinternal
+                    ),
+                    (
+                        SELECT string_agg(
+                            concat_ws(':', p.oid, md5(p.prosrc), obj_description(p.oid, 'pg_proc')),
+                            ',' ORDER BY p.oid
+                        )
+                        FROM pg_proc p
+                        JOIN pg_namespace n ON n.oid = p.pronamespace
+                        WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+                            AND n.nspname NOT LIKE 'pg_toast%'
+                    )
+                ))
+            ",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(fingerprint)
+    }
+
+    /// Reads the read queries of the current database that took the most time from `pg_stat_statements`,
+    /// leaving out queries on the catalog, such as those of airy itself, and queries reading no table.
+    async fn slow_queries(&self, limit: usize) -> AppResult<Vec<SlowQuery>> {
+        let installed: Option<(i32,)> =
+            sqlx::query_as("SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements'")
+                .fetch_optional(&self.pool)
+                .await?;
+        if installed.is_none() {
+            return Err(AppError::InvalidArgument(
+                "the extension isn't installed in this database".into(),
+            ));
+        }
+        // Timing columns were renamed in PostgreSQL 13.
+        let (version,): (i32,) =
+            sqlx::query_as("SELECT current_setting('server_version_num')::int")
+                .fetch_one(&self.pool)
+                .await?;
+        let time = if version >= 130000 {
+            "exec_time"
+        } else {
+            "time"
+        };
+        let query = format!(
+            "
+                SELECT query, calls, total_{time}, mean_{time}
+                FROM pg_stat_statements
+                WHERE dbid = (SELECT oid FROM pg_database WHERE datname = current_database())
+                    AND query ~* '^\\s*(select|with)\\M'
+                    AND query !~* '\\m(pg_\\w+|information_schema)'
+                    AND query ~* '\\mfrom\\M'
+                ORDER BY total_{time} DESC
+                LIMIT $1
+            ",
+            time = time
+        );
+        let rows: Vec<(String, i64, f64, f64)> = logging::record(
+            &query,
+            sqlx::query_as(&query)
+                .bind(limit as i64)
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(query, calls, total_ms, mean_ms)| SlowQuery {
+                calls: Some(calls),
+                total_ms: Some(total_ms),
+                mean_ms: Some(mean_ms),
+                ..SlowQuery::new(&query)
+            })
+            .collect())
     }
 }
 
-#[tool(tool_box)]
-impl PostgresManager {
-    #[tool(
-        name = "postgresGetDatabaseSchema",
-        description = "Retrieves the schema (tables and columns)."
-    )]
-    pub async fn get_database_schema(&self) -> Result<CallToolResult, McpError> {
-        let res = Manager::get_database_schema(self).await?;
-        Ok(res)
-    }
-
-    #[tool(
-        name = "postgresExecuteQuery",
-        description = "Executes a SQL SELECT query and returns the results."
-    )]
-    pub async fn execute_query(
-        &self,
-        #[tool(aggr)] params: ExecuteQueryParams,
-    ) -> Result<CallToolResult, McpError> {
-        let res = Manager::execute_query(self, params).await?;
-        Ok(res)
+/// Relation schema, name, kind, comment and view definition,
+/// followed by column name, type, nullability, key, default, extra, generation expression and comment.
+/// Column fields are null for a table without columns.
+type ColumnRow = (
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<bool>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Table schema and name, constraint name, referenced schema and table, columns and referenced columns.
+type ForeignKeyRow = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    Vec<String>,
+    Vec<String>,
+);
+
+/// Table schema and name, trigger name, timing, events and action.
+type TriggerRow = (String, String, String, String, Vec<String>, String);
+
+/// Routine schema, name, kind, arguments, result type and comment.
+type RoutineRow = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+/// Rows, non-null values, distinct values, min, max, min, max and average length,
+/// and the range of the numbers a histogram is built over.
+type ProfileRow = (
+    i64,
+    i64,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+);
+
+/// Lists columns, indexes, constraints, views, triggers and functions by name and definition,
+/// to tell whether a migration that was reverted left the schema as it was.
+const SCHEMA_OBJECTS: &str = "
+    SELECT object FROM (
+        SELECT concat_ws(
+            ' ', 'column', table_schema || '.' || table_name || '.' || column_name,
+            data_type, is_nullable, column_default
+        ) AS object
+        FROM information_schema.columns
+        WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
+        UNION ALL
+        SELECT concat_ws(' ', 'index', indexdef)
+        FROM pg_indexes
+        WHERE schemaname NOT IN ('pg_catalog', 'information_schema')
+        UNION ALL
+        SELECT concat_ws(' ', 'constraint', n.nspname || '.' || r.relname, con.conname, pg_get_constraintdef(con.oid))
+        FROM pg_constraint con
+        JOIN pg_class r ON r.oid = con.conrelid
+        JOIN pg_namespace n ON n.oid = r.relnamespace
+        WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+        UNION ALL
+        SELECT concat_ws(' ', 'view', schemaname || '.' || viewname, definition)
+        FROM pg_views
+        WHERE schemaname NOT IN ('pg_catalog', 'information_schema')
+        UNION ALL
+        SELECT concat_ws(' ', 'trigger', pg_get_triggerdef(t.oid))
+        FROM pg_trigger t
+        WHERE NOT t.tgisinternal
+        UNION ALL
+        SELECT concat_ws(' ', 'type', n.nspname || '.' || t.typname, t.typtype)
+        FROM pg_type t
+        JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND n.nspname NOT LIKE 'pg_toast%'
+            AND t.typtype IN ('e', 'd', 'c')
+            AND (t.typrelid = 0 OR (SELECT c.relkind FROM pg_class c WHERE c.oid = t.typrelid) = 'c')
+        UNION ALL
+        SELECT concat_ws(' ', 'function', n.nspname || '.' || p.proname, pg_get_function_identity_arguments(p.oid), md5(p.prosrc))
+        FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+    ) objects
+    ORDER BY object
+";
+
+/// SQLSTATE of a statement cancelled by `statement_timeout`.
+const QUERY_CANCELED: &str = "57014";
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Select list of the given columns, with binary values summarized as their size and first bytes.
+fn select_list(columns: &[&ColumnSchema], qualifier: Option<&str>) -> String {
+    columns
+        .iter()
+        .map(|column| {
+            let alias = quote_identifier(&column.name);
+            let name = match qualifier {
+                Some(qualifier) => format!("{}.{}", qualifier, alias),
+                None => alias.clone(),
+            };
+            if ColumnKind::of(&column.data_type) == ColumnKind::Binary {
+                format!(
+                    "'0x' || encode(substring({name} FROM 1 FOR {prefix}), 'hex') \
+                     || CASE WHEN octet_length({name}) > {prefix} THEN '...' ELSE '' END \
+                     || ' (' || octet_length({name}) || ' bytes)' AS {alias}",
+                    prefix = BINARY_PREFIX_BYTES
+                )
+            } else if qualifier.is_some() {
+                format!("{} AS {}", name, alias)
+            } else {
+                name
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Decodes a row, shortening long values.
+fn shortened_row(row: PgRow) -> AppResult<Row> {
+    let mut row: Row = from_pg_row(row)?;
+    row.values_mut()
+        .for_each(|value| shorten_json(value, MAX_VALUE_CHARS));
+    Ok(row)
+}
+
+/// Explains a query with a generic plan, so that queries of `pg_stat_statements` with `$1` placeholders can be explained.
+/// The connection has to plan with `plan_cache_mode = force_generic_plan`, or the placeholders, bound to nulls, are folded away.
+async fn explain_generic(
+    connection: &mut PgConnection,
+    query: &str,
+) -> AppResult<serde_json::Value> {
+    index_advice::check_query(query)?;
+    let prepare = format!("PREPARE airy_advice AS {}", query);
+    logging::record(&prepare, connection.execute(prepare.as_str())).await?;
+    let plan = explain_prepared(connection).await;
+    connection.execute("DEALLOCATE airy_advice").await?;
+    plan
+}
+
+async fn explain_prepared(connection: &mut PgConnection) -> AppResult<serde_json::Value> {
+    let (parameters,): (Option<i32>,) = sqlx::query_as(
+        "SELECT cardinality(parameter_types) FROM pg_prepared_statements WHERE name = 'airy_advice'",
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+    let arguments = vec!["NULL"; parameters.unwrap_or(0) as usize].join(", ");
+    let execute = if arguments.is_empty() {
+        "EXECUTE airy_advice".to_string()
+    } else {
+        format!("EXECUTE airy_advice({})", arguments)
+    };
+    let explain = format!("EXPLAIN (FORMAT JSON, VERBOSE) {}", execute);
+    let row: Row = from_pg_row(
+        logging::record(&explain, sqlx::query(&explain).fetch_one(&mut *connection)).await?,
+    )?;
+    row.into_iter()
+        .next()
+        .and_then(|(_, plan)| index_advice::plan_json(plan))
+        .ok_or_else(|| AppError::InvalidArgument("EXPLAIN returned no plan".into()))
+}
+
+/// A transaction held open on its own connection.
+/// Each statement runs under a savepoint, so a failed one doesn't abort the whole transaction.
+pub struct PostgresTransaction {
+    transaction: Transaction<'static, Postgres>,
+}
+
+impl PostgresTransaction {
+    async fn touch(
+        &mut self,
+        statement: &str,
+        dml: &Dml,
+        table: Option<&TableSchema>,
+        limit: usize,
+    ) -> AppResult<Touched> {
+        let columns: Vec<&ColumnSchema> = table
+            .map(|table| table.columns.iter().collect())
+            .unwrap_or_default();
+        let key_columns: Vec<&ColumnSchema> = columns
+            .iter()
+            .copied()
+            .filter(|column| column.key.as_deref() == Some("PRI"))
+            .collect();
+
+        // Rows are read before the statement, with their keys as text to find them again afterwards.
+        let mut before = Vec::new();
+        let mut keys: Vec<Vec<String>> = vec![Vec::new(); key_columns.len()];
+        if let Some(target) = &dml.target
+            && table.is_some()
+            && dml.kind != DmlKind::Insert
+        {
+            let mut select = select_list(&columns, Some(target.qualifier()));
+            for (i, column) in key_columns.iter().enumerate() {
+                select += &format!(
+                    ", {}.{}::text AS {}",
+                    target.qualifier(),
+                    quote_identifier(&column.name),
+                    dml::key_alias(i)
+                );
+            }
+            let query = format!("{}\nLIMIT {}", target.select(&select), limit);
+            for row in sqlx::query(&query)
+                .fetch_all(&mut *self.transaction)
+                .await?
+            {
+                let mut row = shortened_row(row)?;
+                for (texts, text) in keys
+                    .iter_mut()
+                    .zip(dml::take_key(&mut row, key_columns.len()))
+                {
+                    texts.push(text);
+                }
+                before.push(row);
+            }
+        }
+
+        let mut after = Vec::new();
+        let rows_affected = match table {
+            // Inserted rows are only known from what the statement returns.
+            Some(_) if dml.kind == DmlKind::Insert && !dml.returning => {
+                let query = format!(
+                    "{}\nRETURNING {}",
+                    statement.trim().trim_end_matches(';'),
+                    select_list(&columns, None)
+                );
+                let mut rows = sqlx::query(&query).fetch(&mut *self.transaction);
+                let mut count = 0;
+                while let Some(row) = rows.next().await {
+                    let row = row?;
+                    if after.len() < limit {
+                        after.push(shortened_row(row)?);
+                    }
+                    count += 1;
+                }
+                count
+            }
+            _ => sqlx::query(statement)
+                .execute(&mut *self.transaction)
+                .await?
+                .rows_affected(),
+        };
+
+        if let Some(table) = table
+            && dml.kind == DmlKind::Update
+            && !key_columns.is_empty()
+            && !before.is_empty()
+        {
+            let query = format!(
+                "SELECT {} FROM {}.{} WHERE ({}) IN (SELECT * FROM unnest({}))",
+                select_list(&columns, None),
+                quote_identifier(&table.schema),
+                quote_identifier(&table.name),
+                key_columns
+                    .iter()
+                    .map(|column| format!("{}::text", quote_identifier(&column.name)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                (1..=key_columns.len())
+                    .map(|i| format!("${}::text[]", i))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            let mut query = sqlx::query(&query);
+            for texts in keys {
+                query = query.bind(texts);
+            }
+            for row in query.fetch_all(&mut *self.transaction).await? {
+                after.push(shortened_row(row)?);
+            }
+        }
+
+        Ok(Touched {
+            rows_affected,
+            before,
+            after,
+        })
+    }
+}
+
+#[async_trait]
+impl WriteTransaction for PostgresTransaction {
+    async fn execute(
+        &mut self,
+        statement: &str,
+        dml: &Dml,
+        table: Option<&TableSchema>,
+        limit: usize,
+    ) -> AppResult<Touched> {
+        sqlx::query("SAVEPOINT airy_write")
+            .execute(&mut *self.transaction)
+            .await?;
+        match self.touch(statement, dml, table, limit).await {
+            Ok(touched) => {
+                sqlx::query("RELEASE SAVEPOINT airy_write")
+                    .execute(&mut *self.transaction)
+                    .await?;
+                Ok(touched)
+            }
+            Err(err) => {
+                sqlx::query("ROLLBACK TO SAVEPOINT airy_write")
+                    .execute(&mut *self.transaction)
+                    .await?;
+                Err(err)
+            }
+        }
+    }
+
+    async fn commit(self: Box<Self>) -> AppResult<()> {
+        Ok(self.transaction.commit().await?)
+    }
+
+    async fn rollback(self: Box<Self>) -> AppResult<()> {
+        Ok(self.transaction.rollback().await?)
     }
 }
 
 #[async_trait]
 impl Manager for PostgresManager {
-    fn system_prompt(&self) -> &str {
-        &self.system_prompt
+    fn dialect(&self) -> &str {
+        "PostgreSQL"
     }
 
-    async fn get_database_schema(&self) -> AppResult<CallToolResult> {
-        let (database_name,): (String,) = sqlx::query_as("SELECT DATABASE()")
-            .fetch_one(&self.pool)
-            .await?;
+    fn server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    async fn get_schema(&self) -> AppResult<DatabaseSchema> {
+        self.schema_cache
+            .get(self.fingerprint(), async {
+                let (database,): (String,) = sqlx::query_as("SELECT current_database()::text")
+                    .fetch_one(&self.pool)
+                    .await?;
+                let (tables, views) = self.load_relations(None).await?;
+                Ok(DatabaseSchema {
+                    database,
+                    tables,
+                    views,
+                    routines: self.load_routines().await?,
+                })
+            })
+            .await
+    }
+
+    async fn refresh_schema(&self) -> AppResult<DatabaseSchema> {
+        self.schema_cache.invalidate().await;
+        self.get_schema().await
+    }
+
+    async fn get_table(&self, schema: &str, name: &str) -> AppResult<Option<TableSchema>> {
+        let row_estimate: Option<(i64,)> = sqlx::query_as(
+            "
+                SELECT c.reltuples::bigint
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE n.nspname = $1 AND c.relname = $2 AND c.relkind IN ('r', 'p')
+            ",
+        )
+        .bind(schema)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some((row_estimate,)) = row_estimate else {
+            return Ok(None);
+        };
+        let Some(mut table) = self.load_relations(Some((schema, name))).await?.0.pop() else {
+            return Ok(None);
+        };
 
-        let tables: Vec<(Vec<u8>,)> = sqlx::query_as(
+        let index_rows: Vec<(String, bool, bool, Vec<String>)> = sqlx::query_as(
             "
-                SELECT table_name
-                FROM information_schema.tables
-                WHERE table_schema = ? AND table_type = 'BASE TABLE'
+                SELECT
+                    i.relname::text,
+                    ix.indisunique,
+                    ix.indisprimary,
+                    ARRAY(
+                        SELECT a.attname::text
+                        FROM unnest(ix.indkey::int2[]) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = ix.indrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    )
+                FROM pg_index ix
+                JOIN pg_class i ON i.oid = ix.indexrelid
+                JOIN pg_class t ON t.oid = ix.indrelid
+                JOIN pg_namespace n ON n.oid = t.relnamespace
+                WHERE n.nspname = $1 AND t.relname = $2
+                ORDER BY i.relname
             ",
         )
-        .bind(&database_name)
+        .bind(schema)
+        .bind(name)
         .fetch_all(&self.pool)
         .await?;
 
-        if tables.is_empty() {
-            return Ok(CallToolResult::success(vec![Content::text(format!(
-                "No tables found in database '{}'.",
-                database_name,
-            ))]));
+        table.indexes = index_rows
+            .into_iter()
+            .map(|(name, unique, primary, columns)| IndexSchema {
+                name,
+                columns,
+                unique,
+                primary,
+            })
+            .collect();
+        // `reltuples` is -1 for tables that have never been analyzed.
+        table.row_estimate = Some(row_estimate).filter(|rows| *rows >= 0);
+        Ok(Some(table))
+    }
+
+    async fn sample_rows(
+        &self,
+        table: &TableSchema,
+        columns: &[&ColumnSchema],
+        limit: usize,
+    ) -> AppResult<Vec<Row>> {
+        let columns: Vec<&ColumnSchema> = if columns.is_empty() {
+            table.columns.iter().collect()
+        } else {
+            columns.to_vec()
+        };
+        // Sorting at random is cheap on small tables, and large ones are sampled by blocks first.
+        let sampling = match table.row_estimate {
+            None => String::new(),
+            Some(rows) => match sample_fraction(Some(rows), ROW_BUDGET) {
+                Some(fraction) => format!(
+                    " TABLESAMPLE SYSTEM ({}) ORDER BY random()",
+                    fraction * 100.0
+                ),
+                None => " ORDER BY random()".into(),
+            },
+        };
+        let query = format!(
+            "SELECT {} FROM {}.{}{} LIMIT {}",
+            select_list(&columns, None),
+            quote_identifier(&table.schema),
+            quote_identifier(&table.name),
+            sampling,
+            limit,
+        );
+        let mut rows = Vec::new();
+        for row in logging::record(&query, sqlx::query(&query).fetch_all(&self.pool)).await? {
+            rows.push(shortened_row(row)?);
         }
+        Ok(rows)
+    }
+
+    async fn profile_column(
+        &self,
+        table: &TableSchema,
+        column: &ColumnSchema,
+        budget: usize,
+    ) -> AppResult<ColumnProfile> {
+        let kind = ColumnKind::of(&column.data_type);
+        let relation = format!(
+            "{}.{}",
+            quote_identifier(&table.schema),
+            quote_identifier(&table.name)
+        );
+        // Block sampling reads only the sampled pages, and is repeatable so that all queries see the same rows.
+        let sampling = sample_fraction(table.row_estimate, budget)
+            .map(|fraction| format!(" TABLESAMPLE SYSTEM ({}) REPEATABLE (0)", fraction * 100.0))
+            .unwrap_or_default();
+        let sample = format!(
+            "WITH s AS (SELECT {} AS v FROM {}{} LIMIT {})",
+            quote_identifier(&column.name),
+            relation,
+            sampling,
+            budget
+        );
+        let number = match kind {
+            ColumnKind::Numeric => "v::float8",
+            ColumnKind::Temporal => "extract(epoch FROM v)::float8",
+            _ => "NULL::float8",
+        };
+        let query = format!(
+            "{} SELECT COUNT(*), COUNT(v), COUNT(DISTINCT {}), {}, {}, MIN({number}), MAX({number}) FROM s",
+            sample,
+            if kind == ColumnKind::Other {
+                "v::text"
+            } else {
+                "v"
+            },
+            if kind.is_ordered() {
+                "MIN(v)::text, MAX(v)::text"
+            } else {
+                "NULL::text, NULL::text"
+            },
+            if kind == ColumnKind::Text {
+                "MIN(length(v))::bigint, MAX(length(v))::bigint, AVG(length(v))::float8"
+            } else {
+                "NULL::bigint, NULL::bigint, NULL::float8"
+            },
+        );
+        let (
+            rows,
+            values,
+            distinct_count,
+            min,
+            max,
+            min_length,
+            max_length,
+            average_length,
+            low,
+            high,
+        ): ProfileRow =
+            logging::record(&query, sqlx::query_as(&query).fetch_one(&self.pool)).await?;
 
-        let mut schema_desc = format!("Schema for database '{}':\n\n", database_name);
+        let query = format!(
+            "{} SELECT v::text, COUNT(*) FROM s WHERE v IS NOT NULL GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT {}",
+            sample, TOP_VALUES
+        );
+        let top_rows: Vec<(String, i64)> =
+            logging::record(&query, sqlx::query_as(&query).fetch_all(&self.pool)).await?;
 
-        for (table_name_bin,) in tables {
-            let table_name = if let Ok(table_name) = String::from_utf8(table_name_bin) {
-                table_name
+        let mut histogram = Vec::new();
+        if let (Some(low), Some(high)) = (low, high)
+            && low < high
+        {
+            // The maximum falls just past the last bucket, so it is put back into it.
+            let query = format!(
+                "
+                    {}
+                    SELECT b, COUNT(*), MIN(v)::text, MAX(v)::text
+                    FROM (SELECT v, LEAST(width_bucket({}, $1, $2, $3), $3) AS b FROM s WHERE v IS NOT NULL) t
+                    GROUP BY b
+                    ORDER BY b
+                ",
+                sample, number
+            );
+            let bucket_rows: Vec<(i32, i64, String, String)> = logging::record(
+                &query,
+                sqlx::query_as(&query)
+                    .bind(low)
+                    .bind(high)
+                    .bind(HISTOGRAM_BUCKETS as i32)
+                    .fetch_all(&self.pool),
+            )
+            .await?;
+            histogram = bucket_rows
+                .into_iter()
+                .map(|(_, count, min, max)| Bucket { min, max, count })
+                .collect();
+        }
+
+        Ok(ColumnProfile {
+            column: column.name.clone(),
+            data_type: column.data_type.clone(),
+            rows,
+            sampled: !sampling.is_empty() || rows == budget as i64,
+            null_ratio: if rows > 0 {
+                (rows - values) as f64 / rows as f64
             } else {
-                continue;
-            };
+                0.0
+            },
+            distinct_count,
+            min,
+            max,
+            top_values: top_rows
+                .into_iter()
+                .map(|(value, count)| ValueCount {
+                    value: shorten(&value, MAX_VALUE_CHARS),
+                    count,
+                })
+                .collect(),
+            length: min_length
+                .zip(max_length)
+                .zip(average_length)
+                .map(|((min, max), average)| LengthStats { min, max, average }),
+            histogram,
+        })
+    }
 
-            schema_desc += &format!("Table: {}\n", table_name);
+    async fn search_value(
+        &self,
+        table: &TableSchema,
+        words: &[String],
+        limit: usize,
+        timeout: Duration,
+    ) -> AppResult<TableSearch> {
+        let mut columns: Vec<&ColumnSchema> = table
+            .columns
+            .iter()
+            .filter(|column| {
+                ColumnKind::of(&column.data_type) == ColumnKind::Text
+                    || column.data_type == "tsvector"
+            })
+            .collect();
+        if columns.is_empty() {
+            return Ok(TableSearch::NoTextColumns);
+        }
 
-            let columns_rows: Vec<(
-                String,
-                Vec<u8>,
-                String,
-                Vec<u8>,
-                Option<String>,
-                Option<String>,
-            )> = sqlx::query_as(
+        let (row_estimate,): (i64,) = sqlx::query_as(
+            "
+                SELECT c.reltuples::bigint
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE n.nspname = $1 AND c.relname = $2
+            ",
+        )
+        .bind(&table.schema)
+        .bind(&table.name)
+        .fetch_one(&self.pool)
+        .await?;
+        if row_estimate > SCAN_ROWS {
+            // Trigram indexes serve `ILIKE '%...%'`, and text search indexes serve `@@`.
+            let indexed: Vec<(String,)> = sqlx::query_as(
                 "
-                    SELECT column_name, column_type, is_nullable, column_key, column_default, extra
-                    FROM information_schema.columns
-                    WHERE table_schema = ? AND table_name = ?
-                    ORDER BY ordinal_position
+                    SELECT DISTINCT a.attname::text
+                    FROM pg_index ix
+                    JOIN pg_class t ON t.oid = ix.indrelid
+                    JOIN pg_namespace n ON n.oid = t.relnamespace
+                    CROSS JOIN LATERAL unnest(ix.indkey::int2[], ix.indclass::oid[]) AS k(attnum, opclass)
+                    JOIN pg_opclass oc ON oc.oid = k.opclass
+                    JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum
+                    WHERE n.nspname = $1 AND t.relname = $2
+                        AND oc.opcname IN ('gin_trgm_ops', 'gist_trgm_ops', 'tsvector_ops')
                 ",
             )
-            .bind(&database_name)
-            .bind(&table_name)
+            .bind(&table.schema)
+            .bind(&table.name)
             .fetch_all(&self.pool)
             .await?;
+            columns.retain(|column| indexed.iter().any(|(name,)| *name == column.name));
+            if columns.is_empty() {
+                return Ok(TableSearch::TooLarge);
+            }
+        }
 
-            for (column_name, column_type, is_nullable, column_key, column_default, extra) in
-                columns_rows
-            {
-                let mut col_desc = format!(
-                    "  - {}: {} ",
-                    column_name,
-                    String::from_utf8_lossy(&column_type),
-                );
-                if is_nullable == "NO" {
-                    col_desc += "| NOT NULL";
-                }
-                let key = String::from_utf8_lossy(&column_key);
-                if !key.is_empty() {
-                    col_desc += &format!("| KEY: {}", key);
-                }
-                if let Some(column_default) = column_default.filter(|s| !s.is_empty()) {
-                    col_desc += &format!("| DEFAULT: {}", column_default);
+        // `$1` holds the words, and `$2` the patterns matching text that contains them.
+        let condition = |column: &ColumnSchema, i: usize| {
+            let name = quote_identifier(&column.name);
+            if column.data_type == "tsvector" {
+                format!("{} @@ plainto_tsquery($1[{}])", name, i + 1)
+            } else {
+                format!("{} ILIKE $2[{}]", name, i + 1)
+            }
+        };
+        let any_word = |column: &ColumnSchema| {
+            let conditions: Vec<String> = (0..words.len()).map(|i| condition(column, i)).collect();
+            format!("COALESCE({}, false)", conditions.join(" OR "))
+        };
+        let mut selected: Vec<String> = table
+            .columns
+            .iter()
+            .filter(|column| column.key.as_deref() == Some("PRI"))
+            .map(|column| quote_identifier(&column.name))
+            .collect();
+        for (i, column) in columns.iter().enumerate() {
+            selected.push(format!(
+                "{} AS {}",
+                any_word(column),
+                value_search::match_alias(i)
+            ));
+        }
+        let every_word: Vec<String> = (0..words.len())
+            .map(|i| {
+                let conditions: Vec<String> =
+                    columns.iter().map(|column| condition(column, i)).collect();
+                format!("({})", conditions.join(" OR "))
+            })
+            .collect();
+        let query = format!(
+            "SELECT {} FROM {}.{} WHERE {} LIMIT {}",
+            selected.join(", "),
+            quote_identifier(&table.schema),
+            quote_identifier(&table.name),
+            every_word.join(" AND "),
+            limit
+        );
+        let patterns: Vec<String> = words
+            .iter()
+            .map(|word| value_search::contains_pattern(word))
+            .collect();
+
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(&format!(
+            "SET LOCAL statement_timeout = {}",
+            timeout.as_millis().max(1)
+        ))
+        .execute(&mut *transaction)
+        .await?;
+        let rows = logging::record(
+            &query,
+            sqlx::query(&query)
+                .bind(words)
+                .bind(&patterns)
+                .fetch_all(&mut *transaction),
+        )
+        .await;
+        transaction.rollback().await?;
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(sqlx::Error::Database(err)) if err.code().as_deref() == Some(QUERY_CANCELED) => {
+                return Ok(TableSearch::TimedOut);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut matches = Vec::with_capacity(rows.len());
+        for row in rows {
+            matches.push(value_search::row_match(from_pg_row(row)?, &columns));
+        }
+        Ok(TableSearch::Searched(matches))
+    }
+
+    async fn advise_indexes(&self, queries: &[String], limit: usize) -> AppResult<IndexAdvice> {
+        let mut advice = if queries.is_empty() {
+            IndexAdvice::from_history("pg_stat_statements", self.slow_queries(limit).await)
+        } else {
+            IndexAdvice::for_queries(queries)
+        };
+        let schema = self.get_schema().await?;
+        let queries: Vec<String> = advice
+            .queries
+            .iter()
+            .map(|query| query.query.clone())
+            .collect();
+
+        // Prepared statements, settings and hypothetical indexes last as long as the session,
+        // so the connection is closed afterwards rather than returned to the pool.
+        let mut connection = self.pool.acquire().await?.detach();
+        connection
+            .execute("SET plan_cache_mode = force_generic_plan")
+            .await?;
+        let mut costs = Vec::with_capacity(queries.len());
+        for (i, query) in queries.iter().enumerate() {
+            let plan = match explain_generic(&mut connection, query).await {
+                Ok(plan) => plan,
+                Err(err) => {
+                    costs.push(None);
+                    advice
+                        .notes
+                        .push(format!("Query {} can't be explained: {}", i + 1, err));
+                    continue;
                 }
-                if let Some(extra) = extra.filter(|s| !s.is_empty()) {
-                    col_desc += &format!("| EXTRA: {}", extra);
+            };
+            costs.push(index_advice::postgres_cost(&plan));
+            for scan in index_advice::postgres_scans(&plan) {
+                let Some(table) = self
+                    .get_table(scan.schema.as_deref().unwrap_or("public"), &scan.table)
+                    .await?
+                else {
+                    continue;
+                };
+                let target = format!(
+                    "{}.{}",
+                    quote_identifier(&table.schema),
+                    quote_identifier(&table.name)
+                );
+                let label = schema.qualified_name(&table);
+                advice.add_scan(&table, &label, &target, quote_identifier, &scan, i + 1);
+            }
+        }
+
+        if !advice.candidates.is_empty() {
+            let hypopg: Option<(i32,)> =
+                sqlx::query_as("SELECT 1 FROM pg_extension WHERE extname = 'hypopg'")
+                    .fetch_optional(&mut connection)
+                    .await?;
+            if hypopg.is_some() {
+                for candidate in &mut advice.candidates {
+                    let statement = candidate.statement.trim_end_matches(';');
+                    logging::record(
+                        &format!("SELECT hypopg_create_index('{}')", statement),
+                        sqlx::query("SELECT hypopg_create_index($1)")
+                            .bind(statement)
+                            .execute(&mut connection),
+                    )
+                    .await?;
+                    for &query in &candidate.queries {
+                        if let Some(before) = costs[query - 1]
+                            && let Ok(plan) =
+                                explain_generic(&mut connection, &queries[query - 1]).await
+                            && let Some(after) = index_advice::postgres_cost(&plan)
+                        {
+                            candidate.estimates.push(CostEstimate {
+                                query,
+                                before,
+                                after,
+                            });
+                        }
+                    }
+                    connection.execute("SELECT hypopg_reset()").await?;
                 }
-                schema_desc += &col_desc;
-                schema_desc += "\n";
+            } else {
+                advice.notes.push(
+                    "Install the hypopg extension to estimate the impact of the indexes.".into(),
+                );
             }
+        }
+        connection.close().await?;
+        Ok(advice)
+    }
 
-            schema_desc += "\n\n";
+    async fn try_migration(&self, up: &str, down: &str) -> AppResult<Vec<String>> {
+        // DDL is transactional, so the migration is tried in a transaction that is always rolled back.
+        let mut transaction = self.pool.begin().await?;
+        let before: Vec<(String,)> = sqlx::query_as(SCHEMA_OBJECTS)
+            .fetch_all(&mut *transaction)
+            .await?;
+        let mut problems = Vec::new();
+        // Without arguments, statements are sent as a simple query, which may hold several of them.
+        if let Err(err) = logging::record(up, (&mut *transaction).execute(up)).await {
+            problems.push(format!("The up migration fails: {}", err));
+        } else if let Err(err) = logging::record(down, (&mut *transaction).execute(down)).await {
+            problems.push(format!("The down migration fails: {}", err));
+        } else {
+            let after: Vec<(String,)> = sqlx::query_as(SCHEMA_OBJECTS)
+                .fetch_all(&mut *transaction)
+                .await?;
+            let before: Vec<String> = before.into_iter().map(|(object,)| object).collect();
+            let after: Vec<String> = after.into_iter().map(|(object,)| object).collect();
+            problems = migration::unrestored(&before, &after);
         }
+        transaction.rollback().await?;
+        Ok(problems)
+    }
 
-        Ok(CallToolResult::success(vec![Content::text(schema_desc)]))
+    async fn begin_write(&self) -> AppResult<Box<dyn WriteTransaction>> {
+        Ok(Box::new(PostgresTransaction {
+            transaction: self.pool.begin().await?,
+        }))
     }
 
-    async fn execute_query(&self, params: ExecuteQueryParams) -> AppResult<CallToolResult> {
-        let query = params.query.trim();
-        let mut rows: Vec<serde_json::Value> = Vec::new();
-        for row in sqlx::query(query).fetch_all(&self.pool).await? {
-            rows.push(from_pg_row(row)?);
+    /// Fetches rows on a dedicated connection, so that the query can be cancelled with `pg_cancel_backend`.
+    /// Returns `None` if the request was cancelled.
+    async fn fetch_rows(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        context: Option<&RequestContext<RoleServer>>,
+    ) -> AppResult<Option<Vec<Row>>> {
+        let mut connection = self.pool.acquire().await?;
+        if self.read_only {
+            (&mut *connection).execute("BEGIN READ ONLY").await?;
         }
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&rows)?,
-        )]))
-    }
-}
+        let (backend_pid,): (i32,) = sqlx::query_as("SELECT pg_backend_pid()")
+            .fetch_one(&mut *connection)
+            .await?;
+
+        let rows = sqlx::query(query.trim())
+            .fetch(&mut *connection)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|row| -> AppResult<Row> { Ok(from_pg_row(row?)?) });
+        let cancel = async {
+            sqlx::query("SELECT pg_cancel_backend($1)")
+                .bind(backend_pid)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        };
 
-#[tool(tool_box)]
-impl ServerHandler for PostgresManager {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some(self.system_prompt().into()),
+        let rows = progress::collect_rows(rows, context, cancel).await;
+        if self.read_only {
+            // Settings changed in the transaction are reverted with it.
+            (&mut *connection).execute("ROLLBACK").await?;
         }
+        rows
     }
 }
@@ -0,0 +1,66 @@
+use std::{pin::pin, time::Duration};
+
+use futures::{Stream, StreamExt};
+use rmcp::{RoleServer, model::ProgressNotificationParam, service::RequestContext};
+use tokio::time::Instant;
+
+use crate::error::AppResult;
+
+/// How often progress is reported while a query runs.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Collects rows of a running query.
+/// When called for an MCP request, reports progress to the client if it asked for it,
+/// and stops once the client cancels the request.
+/// `cancel` aborts the query on the server and runs before the row stream is dropped.
+/// Returns `None` if the request was cancelled.
+pub async fn collect_rows<T>(
+    rows: impl Stream<Item = AppResult<T>>,
+    context: Option<&RequestContext<RoleServer>>,
+    cancel: impl Future<Output = AppResult<()>>,
+) -> AppResult<Option<Vec<T>>> {
+    let ct = context
+        .map(|context| context.ct.clone())
+        .unwrap_or_default();
+    let progress = context.and_then(|context| {
+        let progress_token = context.meta.get_progress_token()?;
+        Some((&context.peer, progress_token))
+    });
+
+    let started = Instant::now();
+    let mut ticker = tokio::time::interval_at(started + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+    let mut rows = pin!(rows);
+    let mut collected = Vec::new();
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = ct.cancelled() => {
+                cancel.await?;
+                return Ok(None);
+            }
+            row = rows.next() => match row {
+                Some(row) => collected.push(row?),
+                None => return Ok(Some(collected)),
+            },
+            _ = ticker.tick(), if progress.is_some() => {
+                let Some((peer, progress_token)) = &progress else {
+                    continue;
+                };
+                // Progress is best-effort, a client that went away is noticed elsewhere.
+                let _ = peer
+                    .notify_progress(ProgressNotificationParam {
+                        progress_token: progress_token.clone(),
+                        progress: collected.len() as f64,
+                        total: None,
+                        message: Some(format!(
+                            "{} rows fetched in {:.1}s",
+                            collected.len(),
+                            started.elapsed().as_secs_f64()
+                        )),
+                    })
+                    .await;
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use rmcp::model::{
+    GetPromptResult, JsonObject, ListPromptsResult, Prompt, PromptArgument, PromptMessage,
+    PromptMessageRole,
+};
+
+use crate::{
+    error::{AppError, AppResult},
+    schema::TableSchema,
+    tool::Manager,
+};
+
+/// Number of sample rows included in prompts about a single table.
+const SAMPLE_ROWS: usize = 5;
+
+/// Lists the prompts. With several databases, each prompt takes the name of the database it's about.
+pub fn list_prompts(multiple_databases: bool) -> ListPromptsResult {
+    fn argument(name: &str, description: &str, required: bool) -> PromptArgument {
+        PromptArgument {
+            name: name.into(),
+            title: None,
+            description: Some(description.into()),
+            required: Some(required),
+        }
+    }
+
+    let mut prompts = vec![
+        Prompt::new(
+            "explainTable",
+            Some("Explain the purpose and structure of a table."),
+            Some(vec![argument(
+                "table",
+                "Table name, optionally qualified with a schema.",
+                true,
+            )]),
+        ),
+        Prompt::new(
+            "writeQuery",
+            Some("Write a SQL query that answers a question."),
+            Some(vec![argument(
+                "question",
+                "Question to answer with a query.",
+                true,
+            )]),
+        ),
+        Prompt::new(
+            "findDataQualityIssues",
+            Some("Find data-quality issues in a table."),
+            Some(vec![argument(
+                "table",
+                "Table name, optionally qualified with a schema.",
+                true,
+            )]),
+        ),
+        Prompt::new(
+            "optimizeQuery",
+            Some("Suggest how to make a SQL query faster."),
+            Some(vec![argument("query", "SQL query to optimize.", true)]),
+        ),
+        Prompt::new(
+            "summarizeRecentChanges",
+            Some("Summarize recently added or changed data."),
+            Some(vec![
+                argument(
+                    "table",
+                    "Limit the summary to a table. Defaults to all tables.",
+                    false,
+                ),
+                argument(
+                    "since",
+                    "How far back to look, such as \"7 days\". Defaults to 7 days.",
+                    false,
+                ),
+            ]),
+        ),
+    ];
+    if multiple_databases {
+        for prompt in &mut prompts {
+            prompt.arguments.get_or_insert_default().push(argument(
+                "database",
+                "Name of the database. Defaults to the first one.",
+                false,
+            ));
+        }
+    }
+
+    ListPromptsResult {
+        prompts,
+        next_cursor: None,
+    }
+}
+
+pub async fn get_prompt(
+    manager: &(dyn Manager + Sync),
+    name: &str,
+    arguments: Option<JsonObject>,
+) -> AppResult<GetPromptResult> {
+    let arguments = arguments.unwrap_or_default();
+    let dialect = manager.dialect();
+
+    let text = match name {
+        "explainTable" => {
+            let table = describe_table(manager, required_argument(&arguments, "table")?).await?;
+            format!(
+                "Explain the purpose of the following {dialect} table in plain language.\n\
+                Describe what each column most likely means, how the table relates to other tables, \
+                and point out anything unusual in the sample rows.\n\n{table}"
+            )
+        }
+        "writeQuery" => {
+            let question = required_argument(&arguments, "question")?;
+            let schema = manager.get_schema().await?;
+            format!(
+                "Write a {dialect} query that answers the question below.\n\
+                Only use tables and columns from the schema. Return the query with a short explanation.\n\n\
+                Question: {question}\n\n{schema}"
+            )
+        }
+        "findDataQualityIssues" => {
+            let table = describe_table(manager, required_argument(&arguments, "table")?).await?;
+            format!(
+                "Find data-quality issues in the following {dialect} table.\n\
+                Look for unexpected NULLs, duplicates, orphaned references, out-of-range or inconsistent values \
+                and wrong data types. Use the query tool to verify each suspicion, \
+                and report every issue together with the query that shows it.\n\n{table}"
+            )
+        }
+        "optimizeQuery" => {
+            let query = required_argument(&arguments, "query")?;
+            let schema = manager.get_schema().await?;
+            let mut tables = String::new();
+            for table in schema
+                .tables
+                .iter()
+                .filter(|table| mentions(query, &table.name))
+            {
+                if let Some(table) = manager.get_table(&table.schema, &table.name).await? {
+                    tables += &format!("{}\n", table);
+                }
+            }
+            format!(
+                "Suggest how to make the following {dialect} query faster.\n\
+                Consider rewriting the query and adding indexes. Explain the reasoning behind each suggestion, \
+                and use EXPLAIN through the query tool where it helps.\n\n\
+                Query:\n```sql\n{query}\n```\n\nReferenced tables:\n\n{tables}"
+            )
+        }
+        "summarizeRecentChanges" => {
+            let since = optional_argument(&arguments, "since").unwrap_or("7 days");
+            let schema = manager.get_schema().await?;
+            let tables: Vec<&TableSchema> = match optional_argument(&arguments, "table") {
+                Some(name) => vec![schema.resolve_table(name).ok_or_else(|| {
+                    AppError::InvalidArgument(format!("unknown table: {}", name))
+                })?],
+                None => schema.tables.iter().collect(),
+            };
+
+            let mut temporal_columns = String::new();
+            for table in tables {
+                let columns: Vec<&str> = table
+                    .columns
+                    .iter()
+                    .filter(|column| {
+                        let data_type = column.data_type.to_lowercase();
+                        data_type.contains("date") || data_type.contains("time")
+                    })
+                    .map(|column| column.name.as_str())
+                    .collect();
+                if !columns.is_empty() {
+                    temporal_columns += &format!(
+                        "- {}.{}: {}\n",
+                        table.schema,
+                        table.name,
+                        columns.join(", ")
+                    );
+                }
+            }
+            if temporal_columns.is_empty() {
+                temporal_columns = "No date or time columns found.\n".into();
+            }
+
+            format!(
+                "Summarize the data that was added or changed in the last {since} in this {dialect} database.\n\
+                Use the query tool to inspect rows by the date and time columns listed below. \
+                Report counts and notable records, and say so if recent changes can't be detected.\n\n\
+                Date and time columns:\n{temporal_columns}"
+            )
+        }
+        _ => {
+            return Err(AppError::InvalidArgument(format!(
+                "unknown prompt: {}",
+                name
+            )));
+        }
+    };
+
+    Ok(GetPromptResult {
+        description: None,
+        messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+    })
+}
+
+/// Renders a table's definition and sample rows.
+async fn describe_table(manager: &(dyn Manager + Sync), name: &str) -> AppResult<String> {
+    let schema = manager.get_schema().await?;
+    let table = schema
+        .resolve_table(name)
+        .ok_or_else(|| AppError::InvalidArgument(format!("unknown table: {}", name)))?;
+    let table = manager
+        .get_table(&table.schema, &table.name)
+        .await?
+        .ok_or_else(|| AppError::InvalidArgument(format!("unknown table: {}", name)))?;
+    let sample_rows = manager.sample_rows(&table, &[], SAMPLE_ROWS).await?;
+
+    Ok(format!(
+        "{}\nSample rows:\n{}",
+        table,
+        serde_json::to_string_pretty(&sample_rows)?,
+    ))
+}
+
+fn required_argument<'a>(arguments: &'a JsonObject, name: &str) -> AppResult<&'a str> {
+    optional_argument(arguments, name)
+        .ok_or_else(|| AppError::InvalidArgument(format!("missing argument: {}", name)))
+}
+
+fn optional_argument<'a>(arguments: &'a JsonObject, name: &str) -> Option<&'a str> {
+    arguments
+        .get(name)
+        .and_then(|value| value.as_str())
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Whether the query mentions an identifier as a whole word.
+fn mentions(query: &str, identifier: &str) -> bool {
+    query
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .any(|word| word.eq_ignore_ascii_case(identifier))
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rmcp::{
+    Peer, RoleServer,
+    model::{
+        AnnotateAble, ListResourcesResult, RawResource, ReadResourceResult, ResourceContents,
+        ResourceUpdatedNotificationParam,
+    },
+};
+use serde::Serialize;
+
+use crate::{
+    database::{Database, Databases},
+    error::{AppError, AppResult},
+    schema::{DatabaseSchema, TableSchema},
+    tool::Row,
+};
+
+/// Number of rows included in table resources.
+const SAMPLE_ROWS: usize = 5;
+
+/// How often the schema is checked for changes while a client is connected.
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Resource URIs a client has subscribed to.
+pub type ResourceSubscriptions = Arc<Mutex<HashSet<String>>>;
+
+/// URI of the whole schema, such as `schema://employee`.
+/// With several databases it names the connection instead, such as `schema://app`.
+fn schema_uri(databases: &Databases, database: &Database, schema: &DatabaseSchema) -> String {
+    if databases.is_multiple() {
+        format!("schema://{}", database.name)
+    } else {
+        format!("schema://{}", schema.database)
+    }
+}
+
+/// URI of a table, such as `table://public/users`.
+/// With several databases it starts with the connection, such as `table://app/public/users`.
+fn table_uri(databases: &Databases, database: &Database, table: &TableSchema) -> String {
+    if databases.is_multiple() {
+        format!("table://{}/{}/{}", database.name, table.schema, table.name)
+    } else {
+        format!("table://{}/{}", table.schema, table.name)
+    }
+}
+
+/// Splits the connection name off a resource path when several databases are connected.
+fn split_database<'a>(databases: &'a Databases, path: &'a str) -> Option<(&'a Database, &'a str)> {
+    if !databases.is_multiple() {
+        return Some((databases.get(None).ok()?, path));
+    }
+    let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+    Some((databases.get(Some(name)).ok()?, rest))
+}
+
+pub async fn list_resources(databases: &Databases) -> AppResult<ListResourcesResult> {
+    let mut resources = Vec::new();
+
+    for database in databases.iter() {
+        let schema = database.manager.get_schema().await?;
+        let label = if databases.is_multiple() {
+            format!("{}.", database.name)
+        } else {
+            String::new()
+        };
+
+        resources.push(
+            RawResource {
+                uri: schema_uri(databases, database, &schema),
+                name: format!("Schema of {}", schema.database),
+                description: Some("Tables and columns of the whole database.".into()),
+                mime_type: Some("text/plain".into()),
+                size: None,
+                title: None,
+                icons: None,
+            }
+            .no_annotation(),
+        );
+        for table in &schema.tables {
+            resources.push(
+                RawResource {
+                    uri: table_uri(databases, database, table),
+                    name: format!("Table {}{}.{}", label, table.schema, table.name),
+                    description: Some(
+                        "Columns, keys, indexes, row count estimate and sample rows.".into(),
+                    ),
+                    mime_type: Some("application/json".into()),
+                    size: None,
+                    title: None,
+                    icons: None,
+                }
+                .no_annotation(),
+            );
+        }
+    }
+
+    Ok(ListResourcesResult {
+        resources,
+        next_cursor: None,
+    })
+}
+
+pub async fn read_resource(databases: &Databases, uri: &str) -> AppResult<ReadResourceResult> {
+    #[derive(Serialize)]
+    struct TableResource {
+        #[serde(flatten)]
+        table: TableSchema,
+        sample_rows: Vec<Row>,
+    }
+
+    let contents = if let Some((database, _)) = uri
+        .strip_prefix("schema://")
+        .and_then(|path| split_database(databases, path))
+    {
+        let schema = database.manager.get_schema().await?;
+        if schema_uri(databases, database, &schema) != uri {
+            return Err(AppError::ResourceNotFound(uri.into()));
+        }
+        ResourceContents::TextResourceContents {
+            uri: uri.into(),
+            mime_type: Some("text/plain".into()),
+            text: schema.to_string(),
+            meta: None,
+        }
+    } else if let Some((database, (schema, name))) = uri
+        .strip_prefix("table://")
+        .and_then(|path| split_database(databases, path))
+        .and_then(|(database, path)| Some((database, path.split_once('/')?)))
+    {
+        let table = database
+            .manager
+            .get_table(schema, name)
+            .await?
+            .ok_or_else(|| AppError::ResourceNotFound(uri.into()))?;
+        let sample_rows = database
+            .manager
+            .sample_rows(&table, &[], SAMPLE_ROWS)
+            .await?;
+        ResourceContents::TextResourceContents {
+            uri: uri.into(),
+            mime_type: Some("application/json".into()),
+            text: serde_json::to_string_pretty(&TableResource { table, sample_rows })?,
+            meta: None,
+        }
+    } else {
+        return Err(AppError::ResourceNotFound(uri.into()));
+    };
+
+    Ok(ReadResourceResult {
+        contents: vec![contents],
+    })
+}
+
+pub fn subscribe(subscriptions: &ResourceSubscriptions, uri: String) {
+    subscriptions.lock().unwrap().insert(uri);
+}
+
+pub fn unsubscribe(subscriptions: &ResourceSubscriptions, uri: &str) {
+    subscriptions.lock().unwrap().remove(uri);
+}
+
+/// Periodically compares the schemas with the previous check and notifies the client about changes.
+/// Stops once the client is gone, even if nothing changed since it disconnected.
+pub fn watch_schema(
+    databases: Arc<Databases>,
+    peer: Peer<RoleServer>,
+    subscriptions: ResourceSubscriptions,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        let mut previous: Option<BTreeMap<String, u64>> = None;
+
+        loop {
+            interval.tick().await;
+            if peer.is_transport_closed() {
+                return;
+            }
+            let Ok(current) = fingerprint(&databases).await else {
+                continue;
+            };
+
+            if let Some(previous) = previous.replace(current.clone()) {
+                if previous.keys().ne(current.keys())
+                    && peer.notify_resource_list_changed().await.is_err()
+                {
+                    return;
+                }
+
+                let changed_uris: Vec<String> = subscriptions
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|uri| previous.get(*uri) != current.get(*uri))
+                    .cloned()
+                    .collect();
+                for uri in changed_uris {
+                    if peer
+                        .notify_resource_updated(ResourceUpdatedNotificationParam { uri })
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Hashes each resource's structure, keyed by resource URI.
+async fn fingerprint(databases: &Databases) -> AppResult<BTreeMap<String, u64>> {
+    fn hash(value: &impl Serialize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(value)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let mut fingerprint = BTreeMap::new();
+    for database in databases.iter() {
+        let schema = database.manager.get_schema().await?;
+        for table in &schema.tables {
+            fingerprint.insert(table_uri(databases, database, table), hash(table));
+        }
+        fingerprint.insert(schema_uri(databases, database, &schema), hash(&schema));
+    }
+    Ok(fingerprint)
+}
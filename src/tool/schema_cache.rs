@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::{error::AppResult, schema::DatabaseSchema};
+
+/// How long a cached schema is used without checking its fingerprint.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Max age of a cached schema, for changes a fingerprint doesn't cover.
+const TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Schema of a database, reloaded once its fingerprint changes.
+/// A fingerprint is a cheap digest of the catalog, such as a hash over table and column definitions.
+#[derive(Default)]
+pub struct SchemaCache {
+    entry: Mutex<Option<Entry>>,
+}
+
+struct Entry {
+    schema: DatabaseSchema,
+    fingerprint: String,
+    loaded_at: Instant,
+    checked_at: Instant,
+}
+
+impl SchemaCache {
+    /// Returns the cached schema if it's still current, or awaits `load` otherwise.
+    /// Concurrent callers wait for a single load.
+    pub async fn get(
+        &self,
+        fingerprint: impl Future<Output = AppResult<String>>,
+        load: impl Future<Output = AppResult<DatabaseSchema>>,
+    ) -> AppResult<DatabaseSchema> {
+        let mut entry = self.entry.lock().await;
+        let now = Instant::now();
+        if let Some(cached) = entry.as_ref()
+            && now - cached.loaded_at < TTL
+            && now - cached.checked_at < CHECK_INTERVAL
+        {
+            return Ok(cached.schema.clone());
+        }
+
+        // The fingerprint is taken before loading, so changes made during a load cause another one.
+        let fingerprint = fingerprint.await?;
+        if let Some(cached) = entry.as_mut()
+            && now - cached.loaded_at < TTL
+            && cached.fingerprint == fingerprint
+        {
+            cached.checked_at = now;
+            return Ok(cached.schema.clone());
+        }
+
+        let schema = load.await?;
+        *entry = Some(Entry {
+            schema: schema.clone(),
+            fingerprint,
+            loaded_at: now,
+            checked_at: now,
+        });
+        Ok(schema)
+    }
+
+    pub async fn invalidate(&self) {
+        *self.entry.lock().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::AppError, schema::fixture};
+
+    fn schema(database: &str) -> DatabaseSchema {
+        fixture::database(database, Vec::new())
+    }
+
+    async fn unreachable_load() -> AppResult<DatabaseSchema> {
+        Err(AppError::Config("loaded again".into()))
+    }
+
+    #[tokio::test]
+    async fn reuses_schema_until_invalidated() {
+        let cache = SchemaCache::default();
+        let loaded = cache
+            .get(async { Ok("a".into()) }, async { Ok(schema("first")) })
+            .await
+            .unwrap();
+        assert_eq!(loaded.database, "first");
+
+        let cached = cache
+            .get(async { Ok("b".into()) }, unreachable_load())
+            .await
+            .unwrap();
+        assert_eq!(cached.database, "first");
+
+        cache.invalidate().await;
+        let reloaded = cache
+            .get(async { Ok("a".into()) }, async { Ok(schema("second")) })
+            .await
+            .unwrap();
+        assert_eq!(reloaded.database, "second");
+    }
+}
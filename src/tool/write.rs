@@ -0,0 +1,115 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::sync::Mutex;
+
+use crate::{
+    database::Database,
+    dml::Dml,
+    error::{AppError, AppResult},
+    schema::TableSchema,
+    tool::{Touched, WriteTransaction},
+};
+
+/// Time changes wait to be committed before their transaction is rolled back.
+pub const COMMIT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A transaction held open with the statements run in it.
+pub struct PendingWrite {
+    /// Tells the transaction apart from later ones, so that its timeout doesn't roll them back.
+    id: u64,
+    pub database: String,
+    pub statements: usize,
+    pub rows_affected: u64,
+    transaction: Box<dyn WriteTransaction>,
+}
+
+impl PendingWrite {
+    pub async fn commit(self) -> AppResult<()> {
+        self.transaction.commit().await
+    }
+
+    pub async fn rollback(self) -> AppResult<()> {
+        self.transaction.rollback().await
+    }
+}
+
+/// Transaction of a session whose changes wait to be committed or rolled back.
+pub type OpenTransaction = Arc<Mutex<Option<PendingWrite>>>;
+
+/// Runs a statement in the open transaction, or in a new one that is rolled back after `COMMIT_TIMEOUT`.
+/// Only one database can be written to at a time.
+pub async fn execute(
+    open: &OpenTransaction,
+    database: &Database,
+    statement: &str,
+    dml: &Dml,
+    table: Option<&TableSchema>,
+    limit: usize,
+) -> AppResult<(Touched, usize)> {
+    let mut guard = open.lock().await;
+    if let Some(pending) = guard.as_ref()
+        && pending.database != database.name
+    {
+        return Err(AppError::InvalidArgument(format!(
+            "changes to `{}` have to be committed or rolled back first",
+            pending.database
+        )));
+    }
+    if guard.is_none() {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        *guard = Some(PendingWrite {
+            id,
+            database: database.name.clone(),
+            statements: 0,
+            rows_affected: 0,
+            transaction: database.manager.begin_write().await?,
+        });
+        expire(open.clone(), id);
+    }
+
+    let Some(pending) = guard.as_mut() else {
+        unreachable!()
+    };
+    match pending
+        .transaction
+        .execute(statement, dml, table, limit)
+        .await
+    {
+        Ok(touched) => {
+            pending.statements += 1;
+            pending.rows_affected += touched.rows_affected;
+            Ok((touched, pending.statements))
+        }
+        Err(err) => {
+            // Nothing to keep the transaction open for.
+            if pending.statements == 0
+                && let Some(pending) = guard.take()
+            {
+                pending.rollback().await?;
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Rolls the transaction back once it has waited too long, unless it was finished already.
+fn expire(open: OpenTransaction, id: u64) {
+    tokio::spawn(async move {
+        tokio::time::sleep(COMMIT_TIMEOUT).await;
+        let mut guard = open.lock().await;
+        if guard.as_ref().is_some_and(|pending| pending.id == id)
+            && let Some(pending) = guard.take()
+        {
+            // The connection is closed if the rollback fails, which rolls it back too.
+            let _ = pending.rollback().await;
+        }
+    });
+}
@@ -1,4 +1,5 @@
 use openai_api_rs::v1::chat_completion::ToolCall;
+use serde::de::DeserializeOwned;
 
 /// Fixes the tool call schema.
 /// Not doing this will cause "Internal Server Error" for some models.
@@ -8,3 +9,49 @@ pub fn fix_tool_call(mut tool_call: ToolCall) -> ToolCall {
     }
     tool_call
 }
+
+/// Escapes text for HTML content and attribute values.
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn html_unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&gt;", ">")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+/// Cuts text to at most `max_len` bytes on a character boundary, marking the cut with `...`.
+pub fn shorten(text: &str, max_len: usize) -> String {
+    if text.len() <= max_len {
+        return text.to_string();
+    }
+    format!("{}...", &text[..text.floor_char_boundary(max_len)])
+}
+
+/// Shortens long strings in a JSON value, and arrays and objects whose JSON is long, which become strings.
+pub fn shorten_json(value: &mut serde_json::Value, max_len: usize) {
+    match value {
+        serde_json::Value::String(text) if text.len() > max_len => {
+            *text = shorten(text, max_len);
+        }
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            let text = value.to_string();
+            if text.len() > max_len {
+                *value = serde_json::Value::String(shorten(&text, max_len));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reads the JSON object in a model reply, which models tend to wrap in prose or code fences.
+pub fn json_in_reply<T: DeserializeOwned>(reply: &str) -> Option<T> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    serde_json::from_str(reply.get(start..=end)?).ok()
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use rmcp::schemars::{self, JsonSchema};
+use serde::Serialize;
+
+use crate::{schema::ColumnSchema, tool::Row};
+
+/// Time spent searching all tables for a value.
+pub const SEARCH_TIME: Duration = Duration::from_secs(10);
+/// Tables estimated to have more rows are searched only through text indexes.
+pub const SCAN_ROWS: i64 = 1_000_000;
+/// Max number of words of a searched text.
+const MAX_WORDS: usize = 5;
+
+/// A row containing the searched value.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct RowMatch {
+    /// Columns that contain one of the words.
+    pub columns: Vec<String>,
+    /// Primary key of the row, or nothing if the table has no primary key.
+    pub key: Row,
+}
+
+/// Outcome of searching one table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableSearch {
+    Searched(Vec<RowMatch>),
+    /// The table has no text columns.
+    NoTextColumns,
+    /// The table is too large to scan and has no text index.
+    TooLarge,
+    TimedOut,
+}
+
+/// Splits a searched text into distinct words, which all have to be found in a row.
+pub fn words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        if !words.iter().any(|known| known.eq_ignore_ascii_case(word)) {
+            words.push(word.to_string());
+        }
+    }
+    words.truncate(MAX_WORDS);
+    words
+}
+
+/// `LIKE` pattern matching text that contains `word`, escaping wildcards with backslashes.
+pub fn contains_pattern(word: &str) -> String {
+    let escaped = word
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Alias of the flag telling whether the `i`th searched column matched.
+pub fn match_alias(i: usize) -> String {
+    format!("airy_match_{}", i)
+}
+
+/// Splits a result row into the primary key and the names of the matching columns, given by flags named by `match_alias`.
+pub fn row_match(mut row: Row, columns: &[&ColumnSchema]) -> RowMatch {
+    let mut matched = Vec::new();
+    for (i, column) in columns.iter().enumerate() {
+        let flag = row.remove(&match_alias(i));
+        // MySQL reports conditions as 0 or 1.
+        if flag.is_some_and(|flag| flag.as_bool() == Some(true) || flag.as_i64() == Some(1)) {
+            matched.push(column.name.clone());
+        }
+    }
+    RowMatch {
+        columns: matched,
+        key: row,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::fixture::column;
+
+    #[test]
+    fn splits_distinct_words() {
+        assert_eq!(
+            words("  Georgi  facello georgi "),
+            vec!["Georgi", "facello"]
+        );
+        assert_eq!(words("a b c d e f g").len(), MAX_WORDS);
+        assert!(words(" ").is_empty());
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(contains_pattern("50%_off\\"), "%50\\%\\_off\\\\%");
+    }
+
+    #[test]
+    fn separates_flags_from_key() {
+        let (first, last) = (column("first_name", "text"), column("last_name", "text"));
+        let row = json!({"emp_no": 10001, "airy_match_0": true, "airy_match_1": 0});
+        let serde_json::Value::Object(row) = row else {
+            unreachable!()
+        };
+        let row_match = row_match(row, &[&first, &last]);
+        assert_eq!(row_match.columns, vec!["first_name"]);
+        assert_eq!(
+            serde_json::Value::Object(row_match.key),
+            json!({"emp_no": 10001})
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::net::{SocketAddr, TcpListener};
+
+use airy::{
+    error::AppError,
+    server::{self, HttpServerOptions, TlsOptions},
+};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+use rmcp::{
+    RoleClient, ServerHandler, ServiceExt,
+    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    model::{CallToolRequestParam, ServerCapabilities, ServerInfo},
+    schemars,
+    service::RunningService,
+    tool, tool_handler, tool_router,
+    transport::{
+        SseClientTransport, StreamableHttpClientTransport, sse_client::SseClientConfig,
+        streamable_http_client::StreamableHttpClientTransportConfig,
+    },
+};
+use serde::Deserialize;
+use tokio::sync::oneshot;
+
+const TOKEN: &str = "secret-token";
+
+#[derive(Clone)]
+struct EchoServer {
+    tool_router: ToolRouter<Self>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct EchoParams {
+    message: String,
+}
+
+#[tool_router]
+impl EchoServer {
+    fn new() -> Self {
+        Self {
+            tool_router: Self::tool_router(),
+        }
+    }
+
+    #[tool(description = "Returns the message.")]
+    fn echo(&self, Parameters(EchoParams { message }): Parameters<EchoParams>) -> String {
+        message
+    }
+}
+
+#[tool_handler]
+impl ServerHandler for EchoServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            ..Default::default()
+        }
+    }
+}
+
+struct TestServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl TestServer {
+    fn start(options: HttpServerOptions) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        tokio::spawn(server::serve(listener, options, EchoServer::new, async {
+            let _ = shutdown_rx.await;
+        }));
+        Self {
+            addr,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    fn url(&self, scheme: &str, path: &str) -> String {
+        format!("{}://localhost:{}{}", scheme, self.addr.port(), path)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn options_with_token() -> HttpServerOptions {
+    HttpServerOptions {
+        auth_token: Some(TOKEN.into()),
+        ..Default::default()
+    }
+}
+
+fn http_client(header_name: &'static str, header_value: &str) -> reqwest::Client {
+    let mut headers = HeaderMap::new();
+    headers.insert(header_name, HeaderValue::from_str(header_value).unwrap());
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
+async fn assert_echo(client: RunningService<RoleClient, ()>) {
+    let tools = client.list_all_tools().await.unwrap();
+    assert_eq!(
+        tools
+            .iter()
+            .map(|tool| tool.name.as_ref())
+            .collect::<Vec<_>>(),
+        ["echo"]
+    );
+
+    let result = client
+        .call_tool(CallToolRequestParam {
+            name: "echo".into(),
+            arguments: serde_json::json!({ "message": "hello" })
+                .as_object()
+                .cloned(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        result.content[0].as_text().map(|text| text.text.as_str()),
+        Some("hello")
+    );
+
+    client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn streamable_http_with_bearer_token() {
+    let server = TestServer::start(options_with_token());
+    let transport = StreamableHttpClientTransport::with_client(
+        http_client(AUTHORIZATION.as_str(), &format!("Bearer {}", TOKEN)),
+        StreamableHttpClientTransportConfig::with_uri(
+            server.url("http", server::STREAMABLE_HTTP_PATH),
+        ),
+    );
+    assert_echo(().serve(transport).await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn sse_with_api_key() {
+    let server = TestServer::start(options_with_token());
+    let transport = SseClientTransport::start_with_client(
+        http_client("x-api-key", TOKEN),
+        SseClientConfig {
+            sse_endpoint: server.url("http", server::SSE_PATH).into(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_echo(().serve(transport).await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn rejects_missing_or_wrong_token() {
+    let server = TestServer::start(options_with_token());
+
+    for client in [
+        reqwest::Client::new(),
+        http_client(AUTHORIZATION.as_str(), "Bearer wrong-token"),
+        http_client("x-api-key", "wrong-token"),
+    ] {
+        let response = client
+            .post(server.url("http", server::STREAMABLE_HTTP_PATH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(server.url("http", server::SSE_PATH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn refuses_public_host_without_token() {
+    let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+    let result = server::serve(
+        listener,
+        HttpServerOptions::default(),
+        EchoServer::new,
+        async {},
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::InvalidArgument(_))));
+
+    let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+    let result = server::serve(
+        listener,
+        HttpServerOptions {
+            allow_unauthenticated: true,
+            ..Default::default()
+        },
+        EchoServer::new,
+        async {},
+    )
+    .await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn streamable_http_over_tls() {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let dir = std::env::temp_dir().join(format!("airy-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    std::fs::write(&cert_path, cert.pem()).unwrap();
+    std::fs::write(&key_path, key_pair.serialize_pem()).unwrap();
+
+    let server = TestServer::start(HttpServerOptions {
+        auth_token: None,
+        tls: Some(TlsOptions {
+            cert_path,
+            key_path,
+        }),
+        ..Default::default()
+    });
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert.pem().as_bytes()).unwrap())
+        .build()
+        .unwrap();
+    let transport = StreamableHttpClientTransport::with_client(
+        client,
+        StreamableHttpClientTransportConfig::with_uri(
+            server.url("https", server::STREAMABLE_HTTP_PATH),
+        ),
+    );
+    assert_echo(().serve(transport).await.unwrap()).await;
+
+    std::fs::remove_dir_all(dir).unwrap();
+}